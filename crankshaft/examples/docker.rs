@@ -1,6 +1,7 @@
 //! An example for runner a task using the Docker backend service.
 use std::io::Write;
 
+use crankshaft::engine::service::runner::backend::docker::DockerBackend;
 use crankshaft::engine::task::input;
 use crankshaft::engine::task::Execution;
 use crankshaft::engine::task::Input;
@@ -9,6 +10,7 @@ use crankshaft::engine::Task;
 use tempfile::NamedTempFile;
 use tracing::info;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt as _;
 use tracing_subscriber::util::SubscriberInitExt as _;
 use tracing_subscriber::EnvFilter;
@@ -16,11 +18,16 @@ use tracing_subscriber::EnvFilter;
 #[tokio::main]
 async fn main() {
     tracing_subscriber::registry()
-        .with(fmt::layer())
+        .with(fmt::layer().with_span_events(FmtSpan::CLOSE))
         .with(EnvFilter::from_default_env())
         .init();
 
-    let mut engine = Engine::default();
+    // NOTE: the executions share a container, so the listing written by the
+    // first execution is still there for the second.
+    let backend = DockerBackend::try_new(true)
+        .expect("docker client to connect")
+        .with_shared_container(true);
+    let mut engine = Engine::new_with_backend("docker", backend);
 
     let mut temp_file = NamedTempFile::new().unwrap();
     writeln!(temp_file, "Hello, world from an input").unwrap();
@@ -44,13 +51,17 @@ async fn main() {
                 .args(&[
                     String::from("bash"),
                     String::from("-c"),
-                    String::from("ls /volA"),
+                    String::from("ls /volA > /tmp/listing.txt"),
                 ])
                 .try_build()
                 .unwrap(),
             Execution::builder()
                 .image("ubuntu")
-                .args(&[String::from("cat"), String::from("/volA/test_input.txt")])
+                .args(&[
+                    String::from("cat"),
+                    String::from("/tmp/listing.txt"),
+                    String::from("/volA/test_input.txt"),
+                ])
                 .try_build()
                 .unwrap(),
         ])
@@ -58,13 +69,11 @@ async fn main() {
         .try_build()
         .unwrap();
 
-    let receivers = (0..10)
-        .map(|_| engine.submit("docker", task.clone()).callback)
-        .collect::<Vec<_>>();
+    let handles = engine.submit_batch("docker", (0..10).map(|_| task.clone()));
 
     engine.run().await;
 
-    for rx in receivers {
-        info!(runner = "Docker", reply = ?rx.await.unwrap());
+    for (index, handle) in handles {
+        info!(runner = "Docker", index, reply = ?handle.await.unwrap());
     }
 }
@@ -3,7 +3,7 @@
 use crankshaft::engine::config::Config;
 use crankshaft::engine::service::runner::backend::config::BackendType;
 use crankshaft::engine::service::runner::backend::generic::GenericBackend;
-use crankshaft::engine::service::runner::backend::tes::TesBackend;
+use crankshaft::engine::service::runner::backend::tes::Auth;
 use crankshaft::engine::task::Execution;
 use crankshaft::engine::Engine;
 use crankshaft::engine::Task;
@@ -23,7 +23,7 @@ async fn main() {
         .with(EnvFilter::from_default_env())
         .init();
 
-    let token = std::env::var(TOKEN_ENV_NAME).ok();
+    let auth = std::env::var(TOKEN_ENV_NAME).map_or(Auth::None, Auth::Bearer);
 
     let url = std::env::args().nth(1).expect("no url provided");
     let config = Config::new(std::env::args().nth(2).expect("no config provided"))
@@ -36,7 +36,8 @@ async fn main() {
     let mut engine = Engine::empty()
         .with_docker(false)
         .expect("docker daemon to be alive and reachable")
-        .with_backend("tes", TesBackend::new(url, token))
+        .with_tes("tes", url, auth)
+        .expect("the TES backend to be created")
         .with_backend(
             "lsf",
             GenericBackend::try_from(config)
@@ -57,7 +58,7 @@ async fn main() {
         .try_build()
         .unwrap();
 
-    let mut receivers = Vec::new();
+    let mut handles = Vec::new();
     let runners = engine.runners().map(|s| s.to_owned()).collect::<Vec<_>>();
 
     for runner in &runners {
@@ -68,13 +69,13 @@ async fn main() {
         info!("creating jobs within {runner}");
 
         for _ in 0..10 {
-            receivers.push(engine.submit(runner, task.clone()).callback);
+            handles.push(engine.submit(runner, task.clone()));
         }
     }
 
     engine.run().await;
 
-    for rx in receivers {
-        info!(reply = ?rx.await.unwrap());
+    for handle in handles {
+        info!(reply = ?handle.await.unwrap());
     }
 }
@@ -0,0 +1,45 @@
+//! An example for runner a task using the local backend service.
+
+use crankshaft::engine::service::runner::backend::local::LocalBackend;
+use crankshaft::engine::task::Execution;
+use crankshaft::engine::Engine;
+use crankshaft::engine::Task;
+use tracing::info;
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::from_default_env())
+        .init();
+
+    let mut engine = Engine::new_with_backend("local", LocalBackend::new());
+
+    let task = Task::builder()
+        .name("my-example-task")
+        .description("a longer description")
+        .extend_executions(vec![Execution::builder()
+            .image("ubuntu")
+            .args(&[
+                String::from("sh"),
+                String::from("-c"),
+                String::from("echo \"hello, $NAME!\""),
+            ])
+            .env("NAME", "world")
+            .try_build()
+            .unwrap()])
+        .try_build()
+        .unwrap();
+
+    let handles = engine.submit_batch("local", (0..10).map(|_| task.clone()));
+
+    engine.run().await;
+
+    for (index, handle) in handles {
+        info!(runner = "Local", index, reply = ?handle.await.unwrap());
+    }
+}
@@ -43,13 +43,11 @@ async fn main() {
         .try_build()
         .unwrap();
 
-    let receivers = (0..10000)
-        .map(|_| engine.submit("generic", task.clone()).callback)
-        .collect::<Vec<_>>();
+    let handles = engine.submit_batch("generic", (0..10000).map(|_| task.clone()));
 
     engine.run().await;
 
-    for rx in receivers {
-        info!(runner = "LSF", reply = ?rx.await.unwrap());
+    for (index, handle) in handles {
+        info!(runner = "LSF", index, reply = ?handle.await.unwrap());
     }
 }
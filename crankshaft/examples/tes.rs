@@ -1,6 +1,6 @@
 //! An example for runner a task using the TES backend service.
 
-use crankshaft::engine::service::runner::backend::tes::TesBackend;
+use crankshaft::engine::service::runner::backend::tes::Auth;
 use crankshaft::engine::task::Execution;
 use crankshaft::engine::Engine;
 use crankshaft::engine::Task;
@@ -23,7 +23,8 @@ async fn main() {
     let token = std::env::var(TOKEN_ENV_NAME).unwrap();
 
     let url = std::env::args().nth(1).expect("no url provided");
-    let mut engine = Engine::new_with_backend("tes", TesBackend::new(url, Some(token)));
+    let mut engine = Engine::new_with_tes("tes", url, Auth::Bearer(token))
+        .expect("the TES backend to be created");
 
     let task = Task::builder()
         .name("my-example-task")
@@ -38,13 +39,11 @@ async fn main() {
         .try_build()
         .unwrap();
 
-    let receivers = (0..10)
-        .map(|_| engine.submit("tes", task.clone()).callback)
-        .collect::<Vec<_>>();
+    let handles = engine.submit_batch("tes", (0..10).map(|_| task.clone()));
 
     engine.run().await;
 
-    for rx in receivers {
-        info!(runner = "TES", reply = ?rx.await.unwrap());
+    for (index, handle) in handles {
+        info!(runner = "TES", index, reply = ?handle.await.unwrap());
     }
 }
@@ -1,60 +1,143 @@
-//! A command to evaluate WDL.
-
-use clap::{Arg, Command};
-use serde::{Deserialize, Serialize};
-use std::fs;
-use std::process::Command as ProcessCommand;
-
-/// A simple task representation for JSON/YAML serialization.
-#[derive(Debug, Deserialize, Serialize)]
-struct Task {
-    /// The name of the task.
-    name: String,
-    /// The command to run.
-    command: String,
+//! A command to run a task defined in JSON or YAML.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use crankshaft::engine::{
+    service::runner::backend::{local::LocalBackend, tes::Auth, Exit, Outcome},
+    Engine, Task,
+};
+use std::{fs, path::Path};
+
+/// Reads a task from a JSON or YAML file.
+///
+/// The format is determined by the extension of the file.
+fn read_task(path: &Path) -> Result<Task> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read task file `{path}`", path = path.display()))?;
+
+    let task = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(anyhow::Error::from),
+        Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(anyhow::Error::from),
+        _ => bail!(
+            "task file `{path}` must have a `.json`, `.yaml`, or `.yml` extension",
+            path = path.display()
+        ),
+    };
+
+    task.with_context(|| format!("failed to parse task file `{path}`", path = path.display()))
+}
+
+/// Creates an engine with the backend selected on the command line.
+///
+/// Returns the name of the backend alongside the engine.
+fn create_engine(matches: &ArgMatches) -> Result<(&str, Engine)> {
+    let backend = matches.get_one::<String>("BACKEND").unwrap();
+
+    let engine = match backend.as_str() {
+        "docker" => {
+            let cleanup = !matches.get_flag("NO_CLEANUP");
+            Engine::new_with_docker(cleanup).context("failed to connect to Docker")?
+        }
+        "local" => Engine::new_with_backend(backend, LocalBackend::new()),
+        "tes" => {
+            let url = matches
+                .get_one::<String>("TES_URL")
+                .context("the `tes` backend requires a `--tes-url`")?;
+            Engine::new_with_tes(backend, url, Auth::None)
+                .map_err(|e| anyhow!("failed to create the TES backend: {e}"))?
+        }
+        _ => unreachable!("unknown backend `{backend}`"),
+    };
+
+    Ok((backend, engine))
 }
 
-fn main() {
+#[tokio::main]
+async fn main() -> Result<()> {
     let matches = Command::new("crankshaft")
         .version("1.0")
-        .about("A simple task runner CLI using JSON and YAML")
+        .about("Runs a task defined in JSON or YAML")
         .arg(
-            Arg::new("file")
+            Arg::new("FILE")
                 .help("Task definition file (JSON or YAML)")
                 .required(true),
         )
+        .arg(
+            Arg::new("BACKEND")
+                .long("backend")
+                .help("The backend to run the task on")
+                .value_parser(["docker", "local", "tes"])
+                .default_value("docker"),
+        )
+        .arg(
+            Arg::new("TES_URL")
+                .long("tes-url")
+                .help("The URL of the TES server (for the `tes` backend)"),
+        )
+        .arg(
+            Arg::new("NO_CLEANUP")
+                .long("no-cleanup")
+                .help("Keep the containers of the task (for the `docker` backend)")
+                .action(ArgAction::SetTrue),
+        )
         .get_matches();
 
-    if let Some(task_file) = matches.get_one::<String>("file") {
-        if let Ok(content) = fs::read_to_string(task_file) {
-            let task: Result<Task, Box<dyn std::error::Error>> =
-                if task_file.ends_with(".yaml") || task_file.ends_with(".yml") {
-                    serde_yaml::from_str(&content)
-                        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-                } else if task_file.ends_with(".json") {
-                    serde_json::from_str(&content)
-                        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-                } else {
-                    Err("Unsupported file format".into())
-                };
-
-            if let Ok(task) = task {
-                let shell = if cfg!(target_os = "windows") {
-                    "cmd"
-                } else {
-                    "sh"
-                };
-                let arg = if cfg!(target_os = "windows") {
-                    "/C"
-                } else {
-                    "-c"
-                };
-
-                let _ = ProcessCommand::new(shell)
-                    .arg(arg)
-                    .arg(&task.command)
-                    .status();
-            }
+    let path = Path::new(matches.get_one::<String>("FILE").unwrap());
+    let task = read_task(path)?;
+    if let Err(problems) = task.validate() {
+        bail!(
+            "task file `{path}` is invalid:\n{problems}",
+            path = path.display(),
+            problems = problems.join("\n")
+        );
+    }
+
+    let (backend, mut engine) = create_engine(&matches)?;
+    let handle = engine.submit(backend, task);
+    engine.run().await;
+
+    let reply = handle.await.context("failed to receive reply")?;
+    for execution in reply.executions.iter().flatten() {
+        print!("{}", execution.stdout);
+        eprint!("{}", execution.stderr);
+    }
+
+    // The backend identifier of a failed execution helps with a post-mortem
+    // (e.g., `docker inspect` of its container)
+    if let Outcome::Failed {
+        execution_index, ..
+    } = &reply.outcome
+    {
+        if let Some(id) = reply
+            .executions
+            .as_ref()
+            .and_then(|executions| executions.get(*execution_index))
+            .and_then(|execution| execution.id.as_deref())
+        {
+            eprintln!("execution {execution_index} ran as `{id}` on the `{backend}` backend");
         }
     }
+
+    match reply.outcome {
+        Outcome::Completed => Ok(()),
+        Outcome::Failed {
+            execution_index,
+            status,
+        } => match reply
+            .executions
+            .as_ref()
+            .and_then(|executions| executions.get(execution_index))
+            .map(|execution| execution.exit)
+        {
+            Some(exit @ Exit::Signal(_)) => Err(anyhow!(
+                "execution {execution_index} of the task was terminated by {exit}"
+            )),
+            _ => Err(anyhow!(
+                "execution {execution_index} of the task failed with exit code {status}"
+            )),
+        },
+        Outcome::BackendError(message) => Err(anyhow!("task could not be run: {message}")),
+        Outcome::Cancelled => Err(anyhow!("task was cancelled")),
+        Outcome::TimedOut => Err(anyhow!("task timed out")),
+    }
 }
@@ -1,7 +1,7 @@
 //! A testing implementation for a `sprocket run` command.
 
 use anyhow::{anyhow, bail, Context, Result};
-use clap::{Arg, Command};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use codespan_reporting::{
     files::SimpleFile,
     term::{
@@ -12,18 +12,28 @@ use codespan_reporting::{
 };
 use colored::Colorize;
 use crankshaft::engine::{
+    config::Config as EngineConfig,
+    service::runner::backend::{config::BackendType, generic::GenericBackend, tes::Auth, Outcome},
     task::{
         input::{self, Contents},
-        Execution, Input,
+        resources, Execution, Input,
     },
     Engine, Task,
 };
 use std::{borrow::Cow, collections::HashMap, fs, io::IsTerminal, path::PathBuf};
 use tempfile::tempdir;
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter,
+};
 use wdl_analysis::{AnalysisResult, Analyzer};
-use wdl_ast::{AstToken, Diagnostic, Severity, SyntaxNode};
+use wdl_ast::{AstToken, Diagnostic, Ident, Severity, SyntaxNode, TokenStrHash};
 use wdl_runtime::{Runtime, TaskEvaluator, Value};
 
+/// The number of bytes in a gibibyte.
+///
+/// The Docker backend treats the requested resource sizes as gibibytes.
+const GIBIBYTE: f64 = (1024 * 1024 * 1024) as f64;
+
 /// Emits the given diagnostics to the output stream.
 ///
 /// The use of color is determined by the presence of a terminal.
@@ -49,6 +59,28 @@ fn emit_diagnostics(path: &str, source: &str, diagnostics: &[Diagnostic]) -> Res
     Ok(())
 }
 
+/// Initializes logging at a level based on the number of `--verbose` flags.
+///
+/// Only warnings and errors are logged by default; `RUST_LOG` may be used to
+/// override the level.
+fn init_logging(verbosity: u8) {
+    let level = match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(
+            EnvFilter::builder()
+                .with_default_directive(level.into())
+                .from_env_lossy(),
+        )
+        .init();
+}
+
 #[tokio::main]
 async fn main() {
     if let Err(e) = inner_main().await {
@@ -71,6 +103,14 @@ async fn inner_main() -> Result<()> {
     let matches = Command::new("sprocket")
         .version("1.0")
         .about("Runs a WDL task")
+        .arg(
+            Arg::new("VERBOSE")
+                .short('v')
+                .long("verbose")
+                .help("Increases the verbosity of logging (may be repeated)")
+                .action(ArgAction::Count)
+                .global(true),
+        )
         .subcommand(
             Command::new("run")
                 .about("Runs a WDL task")
@@ -89,15 +129,69 @@ async fn inner_main() -> Result<()> {
                     Arg::new("INPUTS")
                         .long("inputs")
                         .help("The inputs JSON file"),
+                )
+                .arg(
+                    Arg::new("OUTPUT")
+                        .long("output")
+                        .help("The path of the JSON file to write the task outputs to"),
+                )
+                .arg(
+                    Arg::new("RUNTIME")
+                        .long("runtime")
+                        .value_name("NAME=VALUE")
+                        .help(
+                            "Overrides a runtime requirement of the task (e.g. `cpu=4`, \
+                             `memory=16GB`, or `container=ubuntu:24.04`); may be repeated",
+                        )
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("BACKEND")
+                        .long("backend")
+                        .help("The backend to run the task with")
+                        .value_parser(["docker", "tes", "generic"])
+                        .default_value("docker"),
+                )
+                .arg(
+                    Arg::new("DOCKER_CLEANUP")
+                        .long("docker-cleanup")
+                        .help("Whether to remove containers once the task completes")
+                        .value_parser(value_parser!(bool))
+                        .default_value("true"),
+                )
+                .arg(
+                    Arg::new("TES_URL")
+                        .long("tes-url")
+                        .help("The URL of the TES server (required for the `tes` backend)"),
+                )
+                .arg(
+                    Arg::new("TES_TOKEN")
+                        .long("tes-token")
+                        .help("The token used to authenticate with the TES server"),
+                )
+                .arg(
+                    Arg::new("TES_AUTH")
+                        .long("tes-auth")
+                        .help("The scheme used to authenticate with the TES server")
+                        .value_parser(["basic", "bearer"])
+                        .default_value("basic"),
+                )
+                .arg(
+                    Arg::new("CONFIG")
+                        .long("config")
+                        .help("The crankshaft config file (required for the `generic` backend)"),
                 ),
         )
         .arg_required_else_help(true)
         .get_matches();
 
+    init_logging(matches.get_count("VERBOSE"));
+
     if let Some(matches) = matches.subcommand_matches("run") {
         let task_file = matches.get_one::<String>("PATH").unwrap();
         let task_name = matches.get_one::<String>("TASK").unwrap();
         let inputs_file = matches.get_one::<String>("INPUTS");
+        let output_file = matches.get_one::<String>("OUTPUT");
         let result = analyze_wdl(PathBuf::from(task_file)).await?;
 
         let document = result
@@ -116,28 +210,62 @@ async fn inner_main() -> Result<()> {
                     .ok_or_else(|| {
                         anyhow!("document does not contain a task named `{task_name}`")
                     })?;
-                let mut runtime = Runtime::new(result.scope());
-                let evaluator = TaskEvaluator::new(task);
+                let dir = tempdir().context("failed to create temp directory")?;
+                let mut runtime = Runtime::new_with_execution_dir(result.scope(), dir.path());
+                let evaluator = match TaskEvaluator::new(task) {
+                    Ok(evaluator) => evaluator,
+                    Err(diagnostic) => {
+                        emit_diagnostics(
+                            task_file,
+                            &result
+                                .parse_result()
+                                .root()
+                                .map(|n| SyntaxNode::new_root(n.clone()).text().to_string())
+                                .unwrap_or(String::new()),
+                            &[diagnostic],
+                        )?;
+
+                        bail!("aborting due to evaluation error");
+                    }
+                };
 
                 let inputs = if let Some(inputs_file) = inputs_file {
-                    read_inputs(&mut runtime, inputs_file)?
+                    read_inputs(&mut runtime, task_name, inputs_file)?
                 } else {
                     Default::default()
                 };
 
                 match evaluator.evaluate(&mut runtime, &inputs, "/tmp") {
-                    Ok(evaluated) => {
-                        let container = match evaluated
-                            .requirements()
-                            .get("container")
-                            .or_else(|| evaluated.requirements().get("docker"))
-                        {
-                            Some(container) => container.unwrap_string(&runtime),
+                    Ok(mut evaluated) => {
+                        for arg in matches.get_many::<String>("RUNTIME").into_iter().flatten() {
+                            let (name, value) = parse_runtime_override(&mut runtime, arg)?;
+                            evaluated.override_requirement(name, value);
+                        }
+
+                        let container = match evaluated.requirement(&["container", "docker"]) {
+                            Some(Value::String(sym)) => runtime.resolve_str(*sym),
+                            Some(_) => bail!("requirement `container` must be a `String`"),
                             None => {
                                 bail!("task `{task_name}` is missing a `container` requirement");
                             }
                         };
 
+                        let requested = evaluated
+                            .resources(&runtime)
+                            .context("failed to evaluate task resources")?;
+                        let mut resources = resources::Builder::default();
+                        if let Some(cpu) = requested.cpu() {
+                            resources = resources.cpu_cores(cpu.ceil() as u64);
+                        }
+
+                        if let Some(memory) = requested.memory() {
+                            resources = resources.ram_gb(memory as f64 / GIBIBYTE);
+                        }
+
+                        if let Some(disks) = requested.disks() {
+                            resources = resources.disk_gb(disks as f64 / GIBIBYTE);
+                        }
+
                         let input = Input::builder()
                             .contents(Contents::Literal(evaluated.command().to_string()))
                             .path("/exec/command")
@@ -145,28 +273,75 @@ async fn inner_main() -> Result<()> {
                             .try_build()
                             .unwrap();
 
-                        let mut engine = Engine::default();
-                        let task = Task::builder()
+                        let mut inputs = vec![input];
+                        for (path, localized) in evaluated.paths() {
+                            let directory = evaluated.directories().contains(path);
+                            let path = fs::canonicalize(path)
+                                .with_context(|| format!("failed to find input `{path}`"))?;
+                            let r#type = match (directory, path.is_dir()) {
+                                (true, true) => input::Type::Directory,
+                                (false, false) => input::Type::File,
+                                (true, false) => bail!(
+                                    "directory input `{path}` is not a directory",
+                                    path = path.display()
+                                ),
+                                (false, true) => bail!(
+                                    "file input `{path}` is a directory",
+                                    path = path.display()
+                                ),
+                            };
+
+                            inputs.push(
+                                Input::builder()
+                                    .contents(path)
+                                    .path(localized)
+                                    .r#type(r#type)
+                                    .try_build()
+                                    .context("failed to build input definition")?,
+                            );
+                        }
+
+                        let (backend, mut engine) = create_engine(matches)?;
+                        let mut task = Task::builder()
                             .name(task_name)
-                            .extend_inputs([input])
+                            .extend_inputs(inputs)
+                            .resources(resources.build())
                             .extend_executions([Execution::builder()
                                 .image(container)
                                 .args(["bash", "-C", "/exec/command"])
                                 .stdout("stdout.txt")
                                 .stderr("stderr.txt")
                                 .try_build()
-                                .context("failed to build execution definition")?])
+                                .context("failed to build execution definition")?]);
+
+                        if let Some(max_retries) = requested.max_retries() {
+                            task = task.max_retries(
+                                max_retries
+                                    .try_into()
+                                    .context("requirement `max_retries` is too large")?,
+                            );
+                        }
+
+                        let task = task
                             .try_build()
                             .context("failed to build task definition")?;
 
-                        let receivers = (0..1)
-                            .map(|_| engine.submit("docker", task.clone()).callback)
+                        if let Err(problems) = task.validate() {
+                            bail!("task `{task_name}` is invalid:\n{}", problems.join("\n"));
+                        }
+
+                        let handles = (0..1)
+                            .map(|_| engine.submit(backend, task.clone()))
                             .collect::<Vec<_>>();
 
                         engine.run().await;
 
-                        for rx in receivers {
-                            let reply = rx.await.expect("failed to receive reply");
+                        for handle in handles {
+                            let reply = handle.await.expect("failed to receive reply");
+                            if let Outcome::BackendError(message) = &reply.outcome {
+                                bail!("task `{task_name}` could not be run: {message}");
+                            }
+
                             let exec_result =
                                 &reply.executions.expect("should have execution result")[0];
                             if exec_result.status != 0 {
@@ -177,7 +352,6 @@ async fn inner_main() -> Result<()> {
                                 );
                             }
 
-                            let dir = tempdir().context("failed to create temp directory")?;
                             let stdout = dir.path().join("stdout");
                             fs::write(&stdout, &exec_result.stdout).with_context(|| {
                                 format!(
@@ -196,6 +370,10 @@ async fn inner_main() -> Result<()> {
 
                             match evaluated.outputs(&mut runtime, stdout, stderr) {
                                 Ok(outputs) => {
+                                    if let Some(output_file) = output_file {
+                                        write_outputs(&runtime, task_name, &outputs, output_file)?;
+                                    }
+
                                     for (name, value) in outputs {
                                         println!(
                                             "Output `{name}`:\n{value}",
@@ -243,36 +421,142 @@ async fn inner_main() -> Result<()> {
     Ok(())
 }
 
+/// Creates an engine with the backend selected on the command line.
+///
+/// Returns the name of the backend alongside the engine.
+fn create_engine(matches: &ArgMatches) -> Result<(&str, Engine)> {
+    let backend = matches.get_one::<String>("BACKEND").unwrap();
+
+    let engine = match backend.as_str() {
+        "docker" => {
+            let cleanup = *matches.get_one::<bool>("DOCKER_CLEANUP").unwrap();
+            Engine::empty()
+                .with_docker(cleanup)
+                .context("failed to connect to Docker")?
+        }
+        "tes" => {
+            let url = matches
+                .get_one::<String>("TES_URL")
+                .context("the `tes` backend requires a `--tes-url`")?;
+            let auth = match (
+                matches.get_one::<String>("TES_AUTH").map(String::as_str),
+                matches.get_one::<String>("TES_TOKEN"),
+            ) {
+                (_, None) => Auth::None,
+                (Some("bearer"), Some(token)) => Auth::Bearer(token.clone()),
+                (_, Some(token)) => Auth::Basic(token.clone()),
+            };
+            Engine::new_with_tes(backend, url, auth)
+                .map_err(|e| anyhow!("failed to create the TES backend: {e}"))?
+        }
+        "generic" => {
+            let path = matches
+                .get_one::<String>("CONFIG")
+                .context("the `generic` backend requires a `--config`")?;
+            let config = EngineConfig::new(path)
+                .with_context(|| format!("failed to load config file `{path}`"))?
+                .backends
+                .into_iter()
+                .find(|backend| matches!(backend.kind, BackendType::Generic(_)))
+                .with_context(|| {
+                    format!("config file `{path}` does not contain a generic backend")
+                })?;
+            let generic = GenericBackend::try_from(config)
+                .map_err(|_| anyhow!("invalid generic backend in config file `{path}`"))?;
+            Engine::empty().with_backend(backend, generic.to_runner())
+        }
+        _ => unreachable!("unknown backend `{backend}`"),
+    };
+
+    Ok((backend, engine))
+}
+
+/// Writes task outputs to a given JSON file.
+///
+/// The output names are qualified with the name of the task (e.g.
+/// `task_name.output_name`).
+fn write_outputs(
+    runtime: &Runtime<'_>,
+    task_name: &str,
+    outputs: &HashMap<TokenStrHash<Ident>, Value>,
+    output_file: &str,
+) -> Result<()> {
+    let outputs = outputs
+        .iter()
+        .map(|(name, value)| {
+            (
+                format!("{task_name}.{name}", name = name.as_ref().as_str()),
+                value.to_json(runtime),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    let contents = serde_json::to_string_pretty(&outputs).context("failed to serialize outputs")?;
+    fs::write(output_file, contents)
+        .with_context(|| format!("failed to write outputs file `{output_file}`"))
+}
+
 /// Reads task inputs from a given JSON file.
-fn read_inputs(runtime: &mut Runtime<'_>, inputs_file: &str) -> Result<HashMap<String, Value>> {
+///
+/// Input names may be qualified with the name of the task (e.g.
+/// `task_name.input_name`), in which case the qualifier is stripped; an error
+/// is returned if an input is qualified with the name of a different task.
+fn read_inputs(
+    runtime: &mut Runtime<'_>,
+    task_name: &str,
+    inputs_file: &str,
+) -> Result<HashMap<String, Value>> {
     let contents = &fs::read_to_string(inputs_file)
         .with_context(|| format!("failed to read inputs file `{inputs_file}`"))?;
     let inputs: serde_json::Value = serde_json::from_str(contents)
         .with_context(|| format!("failed to deserialize JSON inputs file `{inputs_file}`"))?;
-    let object = inputs
-        .as_object()
-        .with_context(|| format!("inputs file `{inputs_file}` is not a JSON object"))?;
+    let serde_json::Value::Object(object) = inputs else {
+        bail!("inputs file `{inputs_file}` is not a JSON object");
+    };
 
     let mut inputs = HashMap::new();
-    for (name, value) in object.iter() {
-        let value = match value {
-            serde_json::Value::Bool(v) => (*v).into(),
-            serde_json::Value::Number(v) => v
-                .as_i64()
-                .with_context(|| {
-                    format!("input value `{name}` cannot be represented as a 64-bit signed integer")
-                })?
-                .into(),
-            serde_json::Value::String(s) => runtime.new_string(s),
-            _ => bail!("input value `{name}` has an unsupported type"),
+    for (name, value) in object {
+        let name = match name.split_once('.') {
+            Some((task, name)) if task == task_name => name.to_string(),
+            Some((task, _)) => bail!(
+                "input `{name}` in inputs file `{inputs_file}` is for task `{task}`, but task \
+                 `{task_name}` is being run"
+            ),
+            None => name,
         };
 
-        inputs.insert(name.clone(), value);
+        let value = runtime
+            .value_from_json(&value)
+            .with_context(|| format!("input value `{name}` is invalid"))?;
+        inputs.insert(name, value);
     }
 
     Ok(inputs)
 }
 
+/// Parses a `--runtime` override of the form `NAME=VALUE`.
+///
+/// The value is read as JSON if possible (e.g. `cpu=4` is an `Int`) and is
+/// otherwise a `String` (e.g. `memory=16GB`).
+fn parse_runtime_override(runtime: &mut Runtime<'_>, arg: &str) -> Result<(String, Value)> {
+    let Some((name, value)) = arg.split_once('=') else {
+        bail!("runtime override `{arg}` must be of the form `NAME=VALUE`");
+    };
+
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("runtime override `{arg}` is missing a requirement name");
+    }
+
+    let json = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    let value = runtime
+        .value_from_json(&json)
+        .with_context(|| format!("runtime override `{arg}` has an invalid value"))?;
+
+    Ok((name.to_string(), value))
+}
+
 /// Analyzes the given WDL document.
 async fn analyze_wdl(wdl_path: PathBuf) -> Result<AnalysisResult> {
     let analyzer = Analyzer::new(|_: (), _, _, _| async {});
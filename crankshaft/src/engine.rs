@@ -1,19 +1,33 @@
 //!  Engine.
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
+use futures::future::join_all;
+use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
+use futures::FutureExt as _;
 use futures::StreamExt;
 use indexmap::IndexMap;
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
+use tracing::error;
 
+use crate::engine::cache::ResultCache;
 use crate::engine::service::runner::backend::docker;
 use crate::engine::service::runner::backend::docker::DockerBackend;
+use crate::engine::service::runner::backend::tes;
+use crate::engine::service::runner::backend::tes::Auth;
+use crate::engine::service::runner::backend::tes::TesBackend;
 use crate::engine::service::runner::backend::Backend;
+use crate::engine::service::runner::backend::Outcome;
+use crate::engine::service::runner::backend::Result;
 use crate::engine::service::runner::Handle;
 use crate::engine::service::runner::Runner;
+use crate::engine::service::runner::RunnerStats;
 
+pub mod cache;
 pub mod config;
 pub mod service;
 pub mod task;
@@ -23,11 +37,36 @@ pub use task::Task;
 /// The runners stored within the engine.
 type Runners = IndexMap<String, Runner>;
 
+/// A snapshot of the number of tasks at each stage of their lifecycle, per
+/// backend.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EngineStats {
+    /// The statistics of each backend (by name).
+    pub backends: IndexMap<String, RunnerStats>,
+}
+
+impl EngineStats {
+    /// Gets the statistics summed across every backend.
+    pub fn total(&self) -> RunnerStats {
+        self.backends
+            .values()
+            .fold(RunnerStats::default(), |total, stats| RunnerStats {
+                queued: total.queued + stats.queued,
+                running: total.running + stats.running,
+                completed: total.completed + stats.completed,
+                failed: total.failed + stats.failed,
+            })
+    }
+}
+
 /// An engine.
 #[derive(Debug)]
 pub struct Engine {
     /// The task runner(s).
     runners: Runners,
+
+    /// The cache shared by every runner (if configured).
+    cache: Option<Arc<dyn ResultCache>>,
 }
 
 impl Engine {
@@ -35,14 +74,39 @@ impl Engine {
     pub fn empty() -> Self {
         Self {
             runners: Default::default(),
+            cache: None,
         }
     }
 
     /// Adds a [`Backend`] to the engine.
     pub fn with_backend(mut self, name: impl Into<String>, backend: impl Backend) -> Self {
         let name = name.into();
-        self.runners
-            .insert(name.clone(), Runner::new(name, backend));
+        let mut runner = Runner::new(name.clone(), backend);
+        if let Some(cache) = &self.cache {
+            runner = runner.with_cache(cache.clone());
+        }
+
+        self.runners.insert(name, runner);
+        self
+    }
+
+    /// Adds a [`ResultCache`] to the engine.
+    ///
+    /// Before a task is run, the cache is consulted for the reply of an
+    /// identical task that already completed successfully on the same backend;
+    /// if one is found, it is returned without running the task again.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous cache provided to the engine.
+    pub fn with_cache(mut self, cache: impl ResultCache) -> Self {
+        let cache: Arc<dyn ResultCache> = Arc::new(cache);
+        self.runners = self
+            .runners
+            .into_iter()
+            .map(|(name, runner)| (name, runner.with_cache(cache.clone())))
+            .collect();
+        self.cache = Some(cache);
         self
     }
 
@@ -59,9 +123,27 @@ impl Engine {
 
     /// Gets a new engine with a default Docker backend.
     pub fn new_with_docker(cleanup: bool) -> docker::Result<Self> {
-        Ok(Self::empty()
-            .with_docker(cleanup)
-            .expect("docker client to connect"))
+        Self::empty().with_docker(cleanup)
+    }
+
+    /// Adds a TES backend to a [`Engine`].
+    pub fn with_tes(
+        self,
+        name: impl Into<String>,
+        url: impl Into<String>,
+        auth: Auth,
+    ) -> tes::Result<Self> {
+        let backend = TesBackend::new_with_auth(url, auth)?;
+        Ok(self.with_backend(name, backend))
+    }
+
+    /// Gets a new engine with a TES backend.
+    pub fn new_with_tes(
+        name: impl Into<String>,
+        url: impl Into<String>,
+        auth: Auth,
+    ) -> tes::Result<Self> {
+        Self::empty().with_tes(name, url, auth)
     }
 
     /// Gets the names of the runners.
@@ -69,10 +151,24 @@ impl Engine {
         self.runners.keys().map(|key| key.as_ref())
     }
 
+    /// Gets a snapshot of the number of tasks queued, running, completed, and
+    /// failed on each backend.
+    ///
+    /// The counts cover every task submitted since the engine was created.
+    pub fn stats(&self) -> EngineStats {
+        EngineStats {
+            backends: self
+                .runners
+                .iter()
+                .map(|(name, runner)| (name.clone(), runner.stats()))
+                .collect(),
+        }
+    }
+
     /// Submits a [`Task`] to be executed.
     ///
-    /// A [`Handle`] is returned, which contains a channel that can be awaited
-    /// for the result of the job.
+    /// A [`Handle`] is returned, which can be awaited for the result of the
+    /// job once the engine is [run](Self::run).
     pub fn submit(&mut self, name: impl AsRef<str>, task: Task) -> Handle {
         let name = name.as_ref();
 
@@ -84,37 +180,289 @@ impl Engine {
         backend.submit(task)
     }
 
-    /// Runs all of the tasks scheduled in the engine.
-    pub async fn run(self) {
+    /// Submits a batch of [`Task`]s to be executed.
+    ///
+    /// Each returned [`Handle`] is paired with the index of its task within
+    /// the batch, so the results can be matched back to the tasks that
+    /// produced them.
+    pub fn submit_batch(
+        &mut self,
+        name: impl AsRef<str>,
+        tasks: impl IntoIterator<Item = Task>,
+    ) -> Vec<(usize, Handle)> {
+        let name = name.as_ref();
+
+        tasks
+            .into_iter()
+            .map(|task| self.submit(name, task))
+            .enumerate()
+            .collect()
+    }
+
+    /// Submits a [`Task`] to be executed, streaming its output as it is
+    /// produced.
+    ///
+    /// This is the same as [`submit()`](Self::submit), except that the
+    /// returned [`Handle`] also carries a channel of output chunks that can be
+    /// tailed while the task runs.
+    pub fn submit_with_logs(&mut self, name: impl AsRef<str>, task: Task) -> Handle {
+        let name = name.as_ref();
+
+        let backend = self
+            .runners
+            .get(name)
+            .unwrap_or_else(|| panic!("backend not found: {name}"));
+
+        backend.submit_with_logs(task)
+    }
+
+    /// Checks the health of every backend, returning whether each one (by
+    /// name) is healthy.
+    ///
+    /// This is intended to be called before submitting tasks so that an
+    /// unreachable backend is discovered up front rather than once tasks have
+    /// been queued for it. Backends are checked concurrently.
+    pub async fn healthcheck(&self) -> HashMap<String, bool> {
+        let checks = self.runners.iter().map(|(name, runner)| {
+            runner
+                .healthcheck()
+                .map(move |healthy| (name.clone(), healthy))
+        });
+
+        join_all(checks).await.into_iter().collect()
+    }
+
+    /// Shuts down the engine.
+    ///
+    /// Every runner cancels the tasks that have been submitted to it but have
+    /// not yet completed and then asks its backend to clean up any resources it
+    /// has left behind. For Docker backends, this removes every container that
+    /// was created by the backend (i.e., that has its run id), including
+    /// containers leaked by a previous process with the same run id that was
+    /// killed mid-run.
+    ///
+    /// All runners are shut down even if one of them fails; the first error
+    /// encountered is returned.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        let mut result = Ok(());
+
+        for runner in self.runners.values_mut() {
+            let shutdown = runner.shutdown().await;
+
+            if let Err(e) = shutdown {
+                error!(error = %e, "failed to shut down runner");
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Runs all of the tasks currently scheduled in the engine.
+    ///
+    /// The engine can be reused afterwards: tasks submitted once this returns
+    /// are run by the next call.
+    pub async fn run(&mut self) {
         let mut futures = FuturesUnordered::new();
 
-        for (_, runner) in self.runners {
+        for runner in self.runners.values_mut() {
             futures.extend(runner.tasks());
         }
 
-        let task_completion_bar = ProgressBar::new(futures.len() as u64);
-        task_completion_bar.set_style(
-            ProgressStyle::with_template(
-                "{spinner:.cyan/blue} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos:>7}/{len:7} {msg}",
-            )
-            .unwrap()
-            .progress_chars("#>-"),
-        );
+        drain(&mut futures).await;
+    }
 
-        let mut count = 1;
-        task_completion_bar.inc(0);
-        task_completion_bar.enable_steady_tick(Duration::from_millis(100));
+    /// Runs all of the tasks scheduled in the engine, giving up once the
+    /// deadline has elapsed.
+    ///
+    /// If any tasks are still running when the deadline elapses, they are
+    /// cancelled (their handles will resolve to a reply with an
+    /// [`Outcome::TimedOut`] outcome), the engine is [shut
+    /// down](Self::shutdown) so that backends clean up after the cancelled
+    /// tasks, and an error is returned.
+    pub async fn run_with_deadline(&mut self, deadline: Duration) -> Result<()> {
+        let mut futures = FuturesUnordered::new();
 
-        while let Some(()) = futures.next().await {
-            task_completion_bar.set_message(format!("task #{}", count));
-            task_completion_bar.inc(1);
-            count += 1;
+        for runner in self.runners.values_mut() {
+            futures.extend(runner.tasks());
         }
+
+        let drained = tokio::time::timeout(deadline, drain(&mut futures)).await;
+        if drained.is_ok() {
+            return Ok(());
+        }
+
+        error!("engine run exceeded its deadline of {deadline:?}; cancelling remaining tasks");
+
+        // The tasks still running reply that they timed out as their futures
+        // are dropped
+        for runner in self.runners.values() {
+            runner.set_cancellation(Outcome::TimedOut);
+        }
+
+        drop(futures);
+
+        for runner in self.runners.values() {
+            runner.set_cancellation(Outcome::Cancelled);
+        }
+
+        // NOTE: the outcome of the shutdown is logged by `shutdown()` itself;
+        // exceeding the deadline is the error reported to the caller.
+        let _ = self.shutdown().await;
+        Err(format!("engine run exceeded its deadline of {deadline:?}").into())
+    }
+}
+
+/// Drives the futures of the scheduled tasks to completion, reporting
+/// progress as each one completes.
+async fn drain(futures: &mut FuturesUnordered<BoxFuture<'static, ()>>) {
+    let task_completion_bar = ProgressBar::new(futures.len() as u64);
+    task_completion_bar.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.cyan/blue} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos:>7}/{len:7} {msg}",
+        )
+        .unwrap()
+        .progress_chars("#>-"),
+    );
+
+    let mut count = 1;
+    task_completion_bar.inc(0);
+    task_completion_bar.enable_steady_tick(Duration::from_millis(100));
+
+    while let Some(()) = futures.next().await {
+        task_completion_bar.set_message(format!("task #{}", count));
+        task_completion_bar.inc(1);
+        count += 1;
     }
 }
 
+/// The default engine is [empty](Engine::empty); backends, such as Docker
+/// (see [`Engine::new_with_docker()`]), must be added explicitly.
 impl Default for Engine {
     fn default() -> Self {
-        Self::empty().with_docker(true).unwrap()
+        Self::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::service::runner::backend::local::LocalBackend;
+    use crate::engine::task::testing::task;
+
+    #[tokio::test]
+    async fn engines_can_run_more_than_once() {
+        let mut engine = Engine::new_with_backend("local", LocalBackend::new());
+
+        for message in ["first", "second"] {
+            let task = task(["echo", message]);
+
+            let handle = engine.submit("local", task);
+            engine.run().await;
+
+            let reply = handle.await.unwrap();
+            assert_eq!(reply.executions.unwrap()[0].stdout, format!("{message}\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn batches_are_matched_to_their_tasks() {
+        let mut engine = Engine::new_with_backend("local", LocalBackend::new());
+        let tasks = ["a", "b", "c"].map(|message| task(["echo", message]));
+
+        let handles = engine.submit_batch("local", tasks);
+        engine.run().await;
+
+        for ((index, handle), (expected_index, expected)) in
+            handles
+                .into_iter()
+                .zip([(0, "a\n"), (1, "b\n"), (2, "c\n")])
+        {
+            assert_eq!(index, expected_index);
+            assert_eq!(
+                handle.await.unwrap().executions.unwrap()[0].stdout,
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn stats_follow_tasks_through_their_lifecycle() {
+        let mut engine = Engine::new_with_backend("local", LocalBackend::new())
+            .with_backend("other", LocalBackend::new());
+        let tasks = ["true", "false"].map(|program| task([program]));
+
+        let handles = engine.submit_batch("local", tasks);
+        assert_eq!(
+            engine.stats().backends["local"],
+            RunnerStats {
+                queued: 2,
+                ..Default::default()
+            }
+        );
+
+        engine.run().await;
+        for (_, handle) in handles {
+            handle.await.unwrap();
+        }
+
+        let stats = engine.stats();
+        assert_eq!(
+            stats.backends["local"],
+            RunnerStats {
+                completed: 1,
+                failed: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats.backends["other"], RunnerStats::default());
+        assert_eq!(stats.total(), stats.backends["local"]);
+    }
+
+    #[test]
+    fn default_engines_are_empty() {
+        assert_eq!(Engine::default().runners().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn healthchecks_report_every_backend() {
+        let engine = Engine::new_with_backend("local", LocalBackend::new())
+            .with_backend("other", LocalBackend::new());
+
+        assert_eq!(
+            engine.healthcheck().await,
+            HashMap::from([(String::from("local"), true), (String::from("other"), true)])
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_past_their_deadline_are_cancelled() {
+        let mut engine = Engine::new_with_backend("local", LocalBackend::new());
+        let task = task(["sleep", "10"]);
+
+        let handle = engine.submit("local", task);
+        let result = engine.run_with_deadline(Duration::from_millis(100)).await;
+
+        assert!(result.is_err());
+        let reply = handle.await.unwrap();
+        assert_eq!(reply.backend, "local");
+        assert_eq!(reply.outcome, Outcome::TimedOut);
+        assert_eq!(reply.attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn runs_within_their_deadline_complete() {
+        let mut engine = Engine::new_with_backend("local", LocalBackend::new());
+        let task = task(["true"]);
+
+        let handle = engine.submit("local", task);
+        engine
+            .run_with_deadline(Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(handle.await.unwrap().executions.unwrap()[0].status, 0);
     }
 }
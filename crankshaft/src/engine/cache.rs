@@ -0,0 +1,187 @@
+//! Caches of the replies of completed tasks.
+//!
+//! When an [`Engine`](crate::engine::Engine) has a [`ResultCache`], a task
+//! that has already completed successfully on a backend is not run again:
+//! the cached [`Reply`] is returned through its handle instead.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::engine::service::runner::backend::Reply;
+use crate::engine::task::input::Contents;
+use crate::engine::Task;
+
+/// A summary of the contents of a local input, taken from its metadata.
+///
+/// For a directory, the stamp covers every file beneath it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+struct Stamp {
+    /// The number of files.
+    files: u64,
+
+    /// The total size of the files (in bytes).
+    size: u64,
+
+    /// The latest modification time of the files (if known).
+    modified: Option<SystemTime>,
+}
+
+impl Stamp {
+    /// Takes the stamp of the file or directory at a path.
+    ///
+    /// Returns [`None`] if the path cannot be read.
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+
+        if !metadata.is_dir() {
+            return Some(Self {
+                files: 1,
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
+        }
+
+        let mut stamp = Self::default();
+
+        for entry in fs::read_dir(path).ok()? {
+            let entry = Self::of(&entry.ok()?.path())?;
+            stamp.files += entry.files;
+            stamp.size += entry.size;
+            stamp.modified = stamp.modified.max(entry.modified);
+        }
+
+        Some(stamp)
+    }
+}
+
+/// The key identifying a task within a [`ResultCache`].
+///
+/// Keys are compared in full, so two tasks only share a key when they are
+/// identical (see [`Task`]), are run on the same backend, and their local
+/// inputs are unchanged.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// The name of the backend.
+    backend: String,
+
+    /// The task.
+    task: Task,
+
+    /// The stamps of the inputs of the task (in order).
+    inputs: Vec<Option<Stamp>>,
+}
+
+/// Gets the key for a task run on the backend with the given name.
+///
+/// # Notes
+///
+/// The contents of `file://` inputs are summarized by the size and the
+/// modification time of their files, so editing an input changes the key.
+/// Remote and missing inputs are keyed by their URL alone.
+pub fn key(backend: &str, task: &Task) -> Key {
+    let inputs = task
+        .inputs()
+        .into_iter()
+        .flatten()
+        .map(|input| match input.contents() {
+            Contents::URL(url) if url.scheme() == "file" => {
+                url.to_file_path().ok().and_then(|path| Stamp::of(&path))
+            }
+            _ => None,
+        })
+        .collect();
+
+    Key {
+        backend: backend.to_owned(),
+        task: task.clone(),
+        inputs,
+    }
+}
+
+/// A cache of the replies of completed tasks.
+///
+/// Only the replies of tasks whose executions all succeeded are stored.
+pub trait ResultCache: Debug + Send + Sync + 'static {
+    /// Gets the cached reply for a key (if one exists).
+    fn get(&self, key: &Key) -> Option<Reply>;
+
+    /// Stores the reply for a key.
+    fn put(&self, key: Key, reply: Reply);
+}
+
+/// A [`ResultCache`] that is held in memory for the life of the process.
+#[derive(Debug, Default)]
+pub struct InMemoryCache {
+    /// The cached replies.
+    replies: Mutex<HashMap<Key, Reply>>,
+}
+
+impl InMemoryCache {
+    /// Creates a new, empty [`InMemoryCache`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ResultCache for InMemoryCache {
+    fn get(&self, key: &Key) -> Option<Reply> {
+        self.replies.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: Key, reply: Reply) {
+        self.replies.lock().unwrap().insert(key, reply);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::engine::service::runner::backend::Outcome;
+    use crate::engine::task::input::Type;
+    use crate::engine::task::testing::execution;
+    use crate::engine::task::Input;
+
+    fn task(path: PathBuf) -> Task {
+        Task::builder()
+            .extend_inputs([Input::builder()
+                .contents(path)
+                .path("/inputs/file.txt")
+                .r#type(Type::File)
+                .try_build()
+                .unwrap()])
+            .extend_executions([execution(["cat", "/inputs/file.txt"])])
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn edited_inputs_are_not_served_from_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let cache = InMemoryCache::new();
+        let before = key("docker", &task(path.clone()));
+        let reply = Reply {
+            backend: String::from("docker"),
+            outcome: Outcome::Completed,
+            executions: None,
+            outputs: None,
+            attempts: 1,
+        };
+        cache.put(before.clone(), reply);
+        assert!(cache.get(&key("docker", &task(path.clone()))).is_some());
+        assert!(cache.get(&key("local", &task(path.clone()))).is_none());
+
+        fs::write(&path, "hello, world!").unwrap();
+        let after = key("docker", &task(path));
+        assert_ne!(before, after);
+        assert!(cache.get(&after).is_none());
+    }
+}
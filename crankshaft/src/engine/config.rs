@@ -6,6 +6,7 @@ use config::ConfigError;
 use serde::{Deserialize, Serialize};
 
 use crate::engine::service::runner::backend;
+use crate::engine::Engine;
 
 /// The config loaded from a global file.
 /// Currently contains just a list of available backends
@@ -17,12 +18,76 @@ pub struct Config {
 
 impl Config {
     /// Loads a new configuration file from a path.
+    ///
+    /// The loaded configuration is [validated](Self::validate); if it is
+    /// invalid, every problem found is reported in the returned error.
     pub fn new(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
         let path = path.as_ref();
         let file = config::File::<_, _>::from(path);
 
         let settings = config::Config::builder().add_source(file);
-        settings.build()?.try_deserialize()
+        let config: Self = settings.build()?.try_deserialize()?;
+
+        config.validate().map_err(|errors| {
+            ConfigError::Message(
+                errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        })?;
+
+        Ok(config)
+    }
+
+    /// Validates the configuration.
+    ///
+    /// This checks the invariants that cannot be expressed by the types of the
+    /// configuration (e.g., a generic backend with a `monitor` command must
+    /// also have a `job_id_regex`) and that backend names are unique. Each
+    /// error names the offending backend and its index.
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        for (index, backend) in self.backends.iter().enumerate() {
+            let mut problems = backend.problems();
+
+            if self.backends[..index]
+                .iter()
+                .any(|other| other.name == backend.name)
+            {
+                problems.push(String::from("the backend name is not unique"));
+            }
+
+            errors.extend(problems.into_iter().map(|problem| {
+                ConfigError::Message(format!(
+                    "invalid backend `{name}` (#{index}): {problem}",
+                    name = backend.name
+                ))
+            }));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Creates an [`Engine`] with every configured backend registered under its
+    /// configured name.
+    pub fn to_engine(&self) -> backend::Result<Engine> {
+        self.backends
+            .iter()
+            .try_fold(Engine::empty(), |engine, backend| {
+                Ok(engine.with_backend(&backend.name, backend.to_backend()?))
+            })
+    }
+
+    /// Gets the config for a backend by name (if it exists).
+    pub fn backend(&self, name: &str) -> Option<&backend::Config> {
+        self.backends.iter().find(|backend| backend.name == name)
     }
 
     /// Loads a config from a test fixture.
@@ -58,4 +123,38 @@ mod tests {
         assert_eq!(backend.default_cpu, Some(1));
         assert_eq!(backend.default_ram, Some(1));
     }
+
+    #[test]
+    fn invalid_backends_are_reported() {
+        let error = Config::fixture("invalid.toml").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid backend `lsf` (#0): `monitor` requires a `job_id_regex`\n\
+             invalid backend `lsf` (#1): `job_id_regex` must have a capture group for the job \
+             id\n\
+             invalid backend `lsf` (#1): the backend name is not unique"
+        );
+    }
+
+    #[test]
+    fn engines_register_every_backend() {
+        let engine = Config::fixture("generic.toml")
+            .unwrap()
+            .to_engine()
+            .unwrap();
+        assert_eq!(
+            engine.runners().collect::<Vec<_>>(),
+            ["HelloGeneric", "HelloGenericWithDefaults"]
+        );
+    }
+
+    #[test]
+    fn backends_are_found_by_name() {
+        let config = Config::fixture("full.toml").unwrap();
+        assert_eq!(config.backend("quux").unwrap().default_cpu, Some(1));
+        assert!(config.backend("missing").is_none());
+
+        let backend = config.backend("test").unwrap().to_backend().unwrap();
+        assert!(format!("{backend:?}").contains("echo ~{name}"));
+    }
 }
@@ -1,3 +1,19 @@
 //! Services for various functionality within the execution engine.
 
+pub mod catalog;
+pub mod logger;
 pub mod runner;
+
+pub use catalog::Catalog;
+pub use logger::Logger;
+pub use runner::Runner;
+
+/// A service that can be registered with a [`Catalog`].
+#[derive(Debug)]
+pub enum Service {
+    /// A logging service.
+    Logger(Logger),
+
+    /// A task runner service.
+    Runner(Runner),
+}
@@ -0,0 +1,114 @@
+//! A catalog of the services registered with the execution engine.
+
+use indexmap::IndexMap;
+
+pub mod builder;
+pub mod daemon;
+pub mod name;
+
+pub use builder::Builder;
+pub use daemon::Daemon;
+pub use name::Name;
+
+/// A catalog of running services, keyed by their [`Name`].
+#[derive(Debug, Default)]
+pub struct Catalog(IndexMap<Name, Daemon>);
+
+impl Catalog {
+    /// Gets a new catalog builder.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Gets the daemon registered with the given name (if it exists).
+    pub fn get(&self, name: &Name) -> Option<&Daemon> {
+        self.0.get(name)
+    }
+
+    /// Deregisters the daemon with the given name, stopping it before it is
+    /// returned.
+    ///
+    /// If no daemon is registered with the name, [`None`] is returned.
+    pub async fn remove(&mut self, name: &Name) -> Option<Daemon> {
+        let daemon = self.0.shift_remove(name)?;
+        daemon.stop().await;
+        Some(daemon)
+    }
+}
+
+impl From<IndexMap<Name, Daemon>> for Catalog {
+    fn from(daemons: IndexMap<Name, Daemon>) -> Self {
+        Self(daemons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::service::runner::backend::local::LocalBackend;
+    use crate::engine::service::Logger;
+    use crate::engine::service::Runner;
+    use crate::engine::task::testing::task;
+
+    #[tokio::test]
+    async fn services_are_looked_up_by_name() {
+        let catalog = Catalog::builder()
+            .add_logger("default", Logger::new())
+            .unwrap()
+            .add_runner(
+                "local",
+                Runner::new(String::from("local"), LocalBackend::new()),
+            )
+            .unwrap()
+            .build();
+
+        let name = "runner/local".parse::<Name>().unwrap();
+        let handle = catalog
+            .get(&name)
+            .and_then(Daemon::as_runner)
+            .unwrap()
+            .submit(task(["echo", "hello"]))
+            .await
+            .unwrap();
+
+        let reply = handle.await.unwrap();
+        assert_eq!(reply.executions.unwrap()[0].stdout, "hello\n");
+
+        let name = "logging/default".parse::<Name>().unwrap();
+        assert!(catalog.get(&name).unwrap().as_logger().is_some());
+        assert!(catalog.get(&"runner/docker".parse().unwrap()).is_none());
+    }
+
+    #[tokio::test]
+    async fn removed_services_are_stopped() {
+        let mut catalog = Catalog::builder()
+            .add_runner(
+                "local",
+                Runner::new(String::from("local"), LocalBackend::new()),
+            )
+            .unwrap()
+            .build();
+
+        let name = "runner/local".parse::<Name>().unwrap();
+        let runner = catalog.get(&name).unwrap().as_runner().unwrap().clone();
+
+        let daemon = catalog.remove(&name).await.unwrap();
+        assert!(catalog.get(&name).is_none());
+        assert!(catalog.remove(&name).await.is_none());
+        assert!(runner.submit(task(["echo", "hello"])).await.is_none());
+
+        // NOTE: stopping a daemon that has already stopped does nothing.
+        daemon.stop().await;
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let err = Catalog::builder()
+            .add_logger("default", Logger::new())
+            .unwrap()
+            .add_logger("default", Logger::new())
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "service already exists: logging/default");
+    }
+}
@@ -1,91 +1,246 @@
 //! Daemons within the service catalog.
 
-use kameo::actor::ActorRef;
+use futures::StreamExt as _;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tokio::sync::watch;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
+use crate::engine::service::runner::backend::LogOutput;
+use crate::engine::service::runner::Handle;
 use crate::engine::service::Logger;
 use crate::engine::service::Runner;
 use crate::engine::service::Service;
+use crate::engine::Task;
+
+/// A message to a running [`Runner`]: a task to submit and the channel over
+/// which to send its [`Handle`].
+type Submission = (Task, oneshot::Sender<Handle>);
+
+/// A reference to a running [`Logger`].
+#[derive(Clone, Debug)]
+pub struct LoggerRef(UnboundedSender<LogOutput>);
+
+impl LoggerRef {
+    /// Sends output to the logger.
+    ///
+    /// Returns `false` if the logger has stopped.
+    pub fn log(&self, output: LogOutput) -> bool {
+        self.0.send(output).is_ok()
+    }
+}
+
+/// A reference to a running [`Runner`].
+#[derive(Clone, Debug)]
+pub struct RunnerRef(UnboundedSender<Submission>);
+
+impl RunnerRef {
+    /// Submits a task to the runner (see [`Runner::submit()`]).
+    ///
+    /// Returns [`None`] if the runner has stopped.
+    pub async fn submit(&self, task: Task) -> Option<Handle> {
+        let (tx, rx) = oneshot::channel();
+        self.0.send((task, tx)).ok()?;
+        rx.await.ok()
+    }
+}
+
+/// A reference to a running service.
+#[derive(Debug)]
+enum ServiceRef {
+    /// A reference to a logger service.
+    Logger(LoggerRef),
+
+    /// A reference to a task runner service.
+    Runner(RunnerRef),
+}
 
 /// An indefinitely running service.
+///
+/// Each daemon runs its service on its own Tokio task, which processes the
+/// messages sent through the references to the service in order.
 #[derive(Debug)]
-pub enum Daemon {
-    /// An executing logger service.
-    Logger(ActorRef<Logger>),
+pub struct Daemon {
+    /// The reference to the service.
+    service: ServiceRef,
+
+    /// Signals the service to stop.
+    stop: watch::Sender<bool>,
 
-    /// An executing task runner.
-    Runner(ActorRef<Runner>),
+    /// The Tokio task running the service (until the daemon is stopped).
+    task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl Daemon {
     /// Spawns a new daemon from a [`Service`] definition.
+    ///
+    /// # Panics
+    ///
+    /// If called outside of a Tokio runtime.
     pub fn spawn(service: Service) -> Self {
-        match service {
-            Service::Logger(svc) => Self::Logger(kameo::spawn(svc)),
-            Service::Runner(svc) => Self::Runner(kameo::spawn(svc)),
+        let (stop, stopped) = watch::channel(false);
+
+        let (service, task) = match service {
+            Service::Logger(svc) => {
+                let (tx, rx) = unbounded_channel();
+                let task = tokio::spawn(serve_logger(svc, rx, stopped));
+                (ServiceRef::Logger(LoggerRef(tx)), task)
+            }
+            Service::Runner(svc) => {
+                let (tx, rx) = unbounded_channel();
+                let task = tokio::spawn(serve_runner(svc, rx, stopped));
+                (ServiceRef::Runner(RunnerRef(tx)), task)
+            }
+        };
+
+        Self {
+            service,
+            stop,
+            task: Mutex::new(Some(task)),
         }
     }
 
-    /// Attempts to get a reference to the inner [`ActorRef<Logger>`].
+    /// Stops the daemon.
     ///
-    /// * If `self` is a [`Self::Logger`], then a reference to the inner [`ActorRef<Logger>`] wrapped in [`Some`] is returned.
+    /// The service finishes processing any messages it has already received
+    /// (a runner also runs the tasks already submitted to it to completion)
+    /// before it stops. Stopping a daemon that has already stopped does
+    /// nothing.
+    pub async fn stop(&self) {
+        self.stop.send_replace(true);
+
+        let task = self.task.lock().await.take();
+        if let Some(task) = task {
+            // NOTE: an error here only means that the service panicked, in
+            // which case it is no longer running anyway.
+            let _ = task.await;
+        }
+    }
+
+    /// Attempts to get a reference to the inner [`LoggerRef`].
+    ///
+    /// * If `self` is a logger daemon, then a reference to the inner [`LoggerRef`] wrapped in [`Some`] is returned.
     /// * Else, [`None`] is returned.
-    pub fn as_logger(&self) -> Option<&ActorRef<Logger>> {
-        match self {
-            Self::Logger(logger) => Some(logger),
+    pub fn as_logger(&self) -> Option<&LoggerRef> {
+        match &self.service {
+            ServiceRef::Logger(logger) => Some(logger),
             _ => None,
         }
     }
 
-    /// Consumes `self` and attempts to return the inner [`ActorRef<Logger>`].
+    /// Consumes `self` and attempts to return the inner [`LoggerRef`].
     ///
-    /// * If `self` is a [`Self::Logger`], then the inner [`ActorRef<Logger>`] wrapped in [`Some`] is returned.
+    /// * If `self` is a logger daemon, then the inner [`LoggerRef`] wrapped in [`Some`] is returned.
     /// * Else, [`None`] is returned.
-    pub fn into_logger(self) -> Option<ActorRef<Logger>> {
-        match self {
-            Self::Logger(logger) => Some(logger),
+    ///
+    /// The service keeps running until it is sent no more messages.
+    pub fn into_logger(self) -> Option<LoggerRef> {
+        match self.service {
+            ServiceRef::Logger(logger) => Some(logger),
             _ => None,
         }
     }
 
-    /// Consumes `self` and returns the inner [`ActorRef<Logger>`].
+    /// Consumes `self` and returns the inner [`LoggerRef`].
     ///
     /// # Panics
     ///
-    /// If `self` is not a [`Self::Logger`].
-    pub fn unwrap_logger(self) -> ActorRef<Logger> {
+    /// If `self` is not a logger daemon.
+    pub fn unwrap_logger(self) -> LoggerRef {
         self.into_logger()
             .expect("expected `Logger` but got a different variant")
     }
 
-    /// Attempts to get a reference to the inner [`ActorRef<Runner>`].
+    /// Attempts to get a reference to the inner [`RunnerRef`].
     ///
-    /// * If `self` is a [`Self::Runner`], then a reference to the inner [`ActorRef<Runner>`] wrapped in [`Some`] is returned.
+    /// * If `self` is a runner daemon, then a reference to the inner [`RunnerRef`] wrapped in [`Some`] is returned.
     /// * Else, [`None`] is returned.
-    pub fn as_runner(&self) -> Option<&ActorRef<Runner>> {
-        match self {
-            Self::Runner(runner) => Some(runner),
+    pub fn as_runner(&self) -> Option<&RunnerRef> {
+        match &self.service {
+            ServiceRef::Runner(runner) => Some(runner),
             _ => None,
         }
     }
 
-    /// Consumes `self` and attempts to return the inner [`ActorRef<Runner>`].
+    /// Consumes `self` and attempts to return the inner [`RunnerRef`].
     ///
-    /// * If `self` is a [`Self::Runner`], then the inner [`ActorRef<Runner>`] wrapped in [`Some`] is returned.
+    /// * If `self` is a runner daemon, then the inner [`RunnerRef`] wrapped in [`Some`] is returned.
     /// * Else, [`None`] is returned.
-    pub fn into_runner(self) -> Option<ActorRef<Runner>> {
-        match self {
-            Self::Runner(runner) => Some(runner),
+    ///
+    /// The service keeps running until it is sent no more messages.
+    pub fn into_runner(self) -> Option<RunnerRef> {
+        match self.service {
+            ServiceRef::Runner(runner) => Some(runner),
             _ => None,
         }
     }
 
-    /// Consumes `self` and returns the inner [`ActorRef<Runner>`].
+    /// Consumes `self` and returns the inner [`RunnerRef`].
     ///
     /// # Panics
     ///
-    /// If `self` is not a [`Self::Runner`].
-    pub fn unwrap_runner(self) -> ActorRef<Runner> {
+    /// If `self` is not a runner daemon.
+    pub fn unwrap_runner(self) -> RunnerRef {
         self.into_runner()
             .expect("expected `Runner` but got a different variant")
     }
 }
+
+/// Serves a [`Logger`] until it is stopped or every reference to it is
+/// dropped.
+async fn serve_logger(
+    logger: Logger,
+    mut rx: UnboundedReceiver<LogOutput>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            output = rx.recv() => match output {
+                Some(output) => logger.log(&output),
+                None => return,
+            },
+            Ok(_) = stopped.wait_for(|stopped| *stopped) => break,
+        }
+    }
+
+    rx.close();
+    // NOTE: output that was sent before the logger stopped is still recorded.
+    let mut pending = Vec::new();
+    rx.recv_many(&mut pending, usize::MAX).await;
+    for output in pending {
+        logger.log(&output);
+    }
+}
+
+/// Serves a [`Runner`] until it is stopped or every reference to it is
+/// dropped, driving the tasks submitted to it in the meantime.
+async fn serve_runner(
+    mut runner: Runner,
+    mut rx: UnboundedReceiver<Submission>,
+    mut stopped: watch::Receiver<bool>,
+) {
+    loop {
+        tokio::select! {
+            submission = rx.recv() => match submission {
+                Some((task, tx)) => {
+                    let _ = tx.send(runner.submit(task));
+                }
+                None => break,
+            },
+            Some(()) = runner.tasks.next() => {}
+            Ok(_) = stopped.wait_for(|stopped| *stopped) => break,
+        }
+    }
+
+    rx.close();
+    let mut pending = Vec::new();
+    rx.recv_many(&mut pending, usize::MAX).await;
+    for (task, tx) in pending {
+        let _ = tx.send(runner.submit(task));
+    }
+
+    runner.run().await;
+}
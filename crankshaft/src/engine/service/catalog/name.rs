@@ -32,17 +32,29 @@ pub enum Name {
     Runner(String),
 }
 
-impl std::fmt::Display for Name {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Name {
+    /// Gets the namespace of the [`Name`] (the part before the separator).
+    pub fn namespace(&self) -> &str {
         match self {
-            // NOTE: if you add an option here, be sure to also add it to
-            // [`FromStr`] below (and add a test)!
-            Name::Logging(name) => write!(f, "{}{}{}", "logging", SEPARATOR, name),
-            Name::Runner(name) => write!(f, "{}{}{}", "runner", SEPARATOR, name),
+            Name::Logging(_) => "logging",
+            Name::Runner(_) => "runner",
+        }
+    }
+
+    /// Gets the local name of the [`Name`] (the part after the separator).
+    pub fn local_name(&self) -> &str {
+        match self {
+            Name::Logging(name) | Name::Runner(name) => name,
         }
     }
 }
 
+impl std::fmt::Display for Name {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.namespace(), SEPARATOR, self.local_name())
+    }
+}
+
 impl std::str::FromStr for Name {
     type Err = ParseError;
 
@@ -65,7 +77,7 @@ impl std::str::FromStr for Name {
 
         match namespace {
             // NOTE: if you add an option here, be sure to also add it to
-            // [`std::fmt::Display`] above (and add a test)!
+            // [`Name::namespace()`] above (and to the round trip test)!
             "logging" => Ok(Name::Logging(String::from(local_name))),
             "runner" => Ok(Name::Runner(String::from(local_name))),
             _ => Err(ParseError(format!("unknown namespace: {}", namespace))),
@@ -98,4 +110,29 @@ mod tests {
         let err = "foo/name".parse::<Name>().unwrap_err();
         assert_eq!(err, ParseError::new("unknown namespace: foo"));
     }
+
+    #[test]
+    fn accessors() {
+        let name = "logging/foo".parse::<Name>().unwrap();
+        assert_eq!(name.namespace(), "logging");
+        assert_eq!(name.local_name(), "foo");
+
+        let name = "runner/bar".parse::<Name>().unwrap();
+        assert_eq!(name.namespace(), "runner");
+        assert_eq!(name.local_name(), "bar");
+    }
+
+    #[test]
+    fn round_trip() {
+        for namespace in ["logging", "runner"] {
+            for local_name in ["", "a", "docker", "my-runner", "tes_1", "with space", "ü"] {
+                let s = format!("{namespace}{SEPARATOR}{local_name}");
+                let name = s.parse::<Name>().unwrap();
+
+                assert_eq!(name.namespace(), namespace);
+                assert_eq!(name.local_name(), local_name);
+                assert_eq!(name.to_string(), s);
+            }
+        }
+    }
 }
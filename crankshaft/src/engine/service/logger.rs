@@ -0,0 +1,22 @@
+//! Logging services.
+
+use tracing::info;
+
+use crate::engine::service::runner::backend::LogOutput;
+
+/// A service that records the output of tasks through [`tracing`].
+#[derive(Debug, Default)]
+pub struct Logger;
+
+impl Logger {
+    /// Creates a new [`Logger`].
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Records a chunk of output from a task.
+    pub fn log(&self, output: &LogOutput) {
+        let output = output.to_string();
+        info!("{}", output.trim_end());
+    }
+}
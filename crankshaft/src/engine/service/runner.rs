@@ -1,32 +1,241 @@
 //! Task runner services.
 
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
 use futures::future::join_all;
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::oneshot::Receiver;
+use tokio::sync::oneshot::Sender;
+use tracing::debug;
+use tracing::info_span;
+use tracing::instrument;
 use tracing::trace;
+use tracing::warn;
+use tracing::Instrument as _;
 
+use crate::engine::cache;
+use crate::engine::cache::ResultCache;
 use crate::engine::service::runner::backend::Backend;
+use crate::engine::service::runner::backend::LogOutput;
+use crate::engine::service::runner::backend::Outcome;
 use crate::engine::service::runner::backend::Reply;
 use crate::engine::Task;
 
 pub mod backend;
 
 /// A submitted task handle.
+///
+/// A [`Handle`] is a [`Future`] that resolves to the [`Reply`] for the task
+/// once it has completed, so it can be awaited directly. An error is returned
+/// if the backend dropped the task without replying.
 #[derive(Debug)]
 pub struct Handle {
     /// The callback that is executed when a task is completed.
+    ///
+    /// Most callers should await the [`Handle`] itself instead.
     pub callback: Receiver<Reply>,
+
+    /// The stream of output produced while the task runs.
+    ///
+    /// This is only populated for tasks submitted with
+    /// [`Runner::submit_with_logs()`]. Take the receiver out of the handle
+    /// before awaiting it to tail the output while the task runs.
+    pub logs: Option<UnboundedReceiver<LogOutput>>,
+}
+
+impl Future for Handle {
+    type Output = Result<Reply, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.callback).poll(cx)
+    }
+}
+
+/// A snapshot of the number of tasks of a [`Runner`] at each stage of their
+/// lifecycle.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RunnerStats {
+    /// The number of tasks that were submitted but have not started running.
+    pub queued: usize,
+
+    /// The number of tasks that are running (including retries).
+    pub running: usize,
+
+    /// The number of tasks that completed successfully (including tasks whose
+    /// reply was cached).
+    pub completed: usize,
+
+    /// The number of tasks that did not complete successfully.
+    ///
+    /// This includes tasks that were cancelled or dropped without a reply,
+    /// such as those cancelled by [`Runner::shutdown()`].
+    pub failed: usize,
+}
+
+/// The shared counters behind a [`RunnerStats`].
+#[derive(Debug, Default)]
+struct Counters {
+    /// The number of queued tasks.
+    queued: AtomicUsize,
+
+    /// The number of running tasks.
+    running: AtomicUsize,
+
+    /// The number of successfully completed tasks.
+    completed: AtomicUsize,
+
+    /// The number of failed tasks.
+    failed: AtomicUsize,
+}
+
+/// Tracks a single task through the [`Counters`] of its runner.
+///
+/// The task is accounted for when the tracker is dropped, so a task whose
+/// future is dropped before it replies is still counted (as failed).
+#[derive(Debug)]
+struct Tracker {
+    /// The counters of the runner.
+    counters: Arc<Counters>,
+
+    /// Whether the task has started running.
+    running: bool,
+
+    /// Whether the task completed successfully.
+    succeeded: bool,
+}
+
+impl Tracker {
+    /// Creates a new [`Tracker`] for a queued task.
+    fn new(counters: Arc<Counters>) -> Self {
+        counters.queued.fetch_add(1, Ordering::Relaxed);
+        Self {
+            counters,
+            running: false,
+            succeeded: false,
+        }
+    }
+
+    /// Marks the task as running.
+    fn start(&mut self) {
+        if !self.running {
+            self.counters.queued.fetch_sub(1, Ordering::Relaxed);
+            self.counters.running.fetch_add(1, Ordering::Relaxed);
+            self.running = true;
+        }
+    }
+
+    /// Marks the task as finished with the given outcome.
+    fn finish(mut self, outcome: &Outcome) {
+        self.succeeded = outcome.is_success();
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        let stage = if self.running {
+            &self.counters.running
+        } else {
+            &self.counters.queued
+        };
+        stage.fetch_sub(1, Ordering::Relaxed);
+
+        let result = if self.succeeded {
+            &self.counters.completed
+        } else {
+            &self.counters.failed
+        };
+        result.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The sender of the reply to a task's [`Handle`].
+///
+/// If the future of the task is dropped before it replies (e.g., because the
+/// engine run exceeded its deadline or the runner was shut down), a reply with
+/// the runner's cancellation outcome is sent when this is dropped.
+#[derive(Debug)]
+struct PendingReply {
+    /// The sender (until a reply is sent).
+    tx: Option<Sender<Reply>>,
+
+    /// The name of the backend.
+    backend: String,
+
+    /// The number of times the task has been attempted so far.
+    attempts: u32,
+
+    /// The outcome replied with if the task is dropped.
+    cancellation: Arc<Mutex<Outcome>>,
+}
+
+impl PendingReply {
+    /// Sends the reply of the task.
+    fn send(mut self, reply: Reply) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(reply);
+        }
+    }
+
+    /// Drops the sender without replying, so the handle resolves to an error.
+    fn abandon(mut self) {
+        self.tx = None;
+    }
+}
+
+impl Drop for PendingReply {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let outcome = self
+                .cancellation
+                .lock()
+                .map(|outcome| outcome.clone())
+                .unwrap_or(Outcome::Cancelled);
+
+            let _ = tx.send(Reply {
+                backend: std::mem::take(&mut self.backend),
+                outcome,
+                executions: None,
+                outputs: None,
+                attempts: self.attempts,
+            });
+        }
+    }
 }
 
 /// A generic task runner.
+///
+/// Each submitted task runs within a `task` span (and each attempt at it
+/// within an `attempt` span) carrying the task and backend names, so the
+/// output of a batch of tasks can be correlated and timed by subscribing to
+/// [`tracing`] events.
 #[derive(Debug)]
 pub struct Runner {
     /// The name of the backend.
     name: String,
 
     /// The task runner itself.
-    backend: Box<dyn Backend>,
+    backend: Arc<dyn Backend>,
+
+    /// The cache of the replies of completed tasks (if configured).
+    cache: Option<Arc<dyn ResultCache>>,
+
+    /// The counters of the tasks submitted to the runner.
+    counters: Arc<Counters>,
+
+    /// The outcome replied with for tasks that are dropped before they
+    /// complete.
+    cancellation: Arc<Mutex<Outcome>>,
 
     /// The list of submitted tasks.
     pub tasks: FuturesUnordered<BoxFuture<'static, ()>>,
@@ -37,29 +246,324 @@ impl Runner {
     pub fn new(name: String, backend: impl Backend) -> Self {
         Self {
             name,
-            backend: Box::new(backend),
+            backend: Arc::new(backend),
+            cache: None,
+            counters: Default::default(),
+            cancellation: Arc::new(Mutex::new(Outcome::Cancelled)),
             tasks: Default::default(),
         }
     }
 
+    /// Sets the cache consulted before a task is run.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous cache provided to the
+    /// runner.
+    pub fn with_cache(mut self, cache: Arc<dyn ResultCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
     /// Submits a task to be executed by the backend.
+    ///
+    /// If the task specifies a maximum number of retries, it is run again
+    /// whenever it does not complete successfully (unless it was cancelled)
+    /// until either it succeeds or the retries are exhausted. The reply from
+    /// the last attempt is returned.
+    #[instrument(skip_all, fields(backend = %self.name, task = task.name()))]
     pub fn submit(&self, task: Task) -> Handle {
         trace!(backend = ?self.backend, task = ?task);
 
+        Handle {
+            callback: self.spawn(task, None),
+            logs: None,
+        }
+    }
+
+    /// Submits a task to be executed by the backend, streaming the task's
+    /// output through [`Handle::logs`] as it is produced.
+    ///
+    /// Backends that cannot stream output close the channel without sending
+    /// anything; the output is still available in the final reply. Output
+    /// from every attempt of a retried task is sent through the same channel.
+    #[instrument(skip_all, fields(backend = %self.name, task = task.name()))]
+    pub fn submit_with_logs(&self, task: Task) -> Handle {
+        trace!(backend = ?self.backend, task = ?task);
+
+        let (logs_tx, logs_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        Handle {
+            callback: self.spawn(task, Some(logs_tx)),
+            logs: Some(logs_rx),
+        }
+    }
+
+    /// Schedules a task on the backend, retrying it as requested by the task.
+    ///
+    /// The [placeholders](Task::resolve_placeholders) of the task are
+    /// resolved first.
+    ///
+    /// If the runner has a cache that holds a reply for the task, the cached
+    /// reply is sent immediately instead; otherwise, the reply of a successful
+    /// task is stored in the cache.
+    fn spawn(&self, task: Task, logs: Option<UnboundedSender<LogOutput>>) -> Receiver<Reply> {
+        let task = task.resolve_placeholders();
         let (tx, rx) = tokio::sync::oneshot::channel();
-        self.tasks
-            .push(Box::pin(self.backend.run(self.name.clone(), task, tx)));
+        let key = cache::key(&self.name, &task);
+
+        if let Some(reply) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            debug!(backend = %self.name, task = task.name(), "using the cached reply");
+            Tracker::new(self.counters.clone()).finish(&reply.outcome);
+            let _ = tx.send(reply);
+            return rx;
+        }
+
+        let mut tracker = Tracker::new(self.counters.clone());
+        let mut pending = PendingReply {
+            tx: Some(tx),
+            backend: self.name.clone(),
+            attempts: 0,
+            cancellation: self.cancellation.clone(),
+        };
+
+        let cache = self.cache.clone();
+        let backend = self.backend.clone();
+        let name = self.name.clone();
+        let max_retries = task.retries();
+        let span = info_span!("task", backend = %name, task = task.name());
+
+        self.tasks.push(Box::pin(
+            async move {
+                tracker.start();
+                let mut attempts = 0;
+
+                loop {
+                    attempts += 1;
+                    pending.attempts = attempts;
+
+                    let (attempt_tx, attempt_rx) = tokio::sync::oneshot::channel();
+                    backend
+                        .run(name.clone(), task.clone(), attempt_tx, logs.clone())
+                        .instrument(info_span!("attempt", attempt = attempts))
+                        .await;
+
+                    // If the backend dropped the task without replying, the
+                    // handle resolves to an error as well.
+                    let Ok(mut reply) = attempt_rx.await else {
+                        pending.abandon();
+                        return;
+                    };
+
+                    // NOTE: a cancelled task is not retried, as it was stopped
+                    // deliberately.
+                    let failed = !matches!(reply.outcome, Outcome::Completed | Outcome::Cancelled);
+
+                    if failed && attempts <= max_retries {
+                        warn!(
+                            "task `{}` failed on attempt {attempts} of {}; retrying",
+                            task.name().unwrap_or("<unnamed>"),
+                            max_retries + 1
+                        );
+                        continue;
+                    }
+
+                    reply.attempts = attempts;
+
+                    if let Some(cache) = cache.filter(|_| reply.outcome.is_success()) {
+                        cache.put(key, reply.clone());
+                    }
+
+                    tracker.finish(&reply.outcome);
+                    pending.send(reply);
+                    return;
+                }
+            }
+            .instrument(span),
+        ));
+
+        rx
+    }
+
+    /// Takes the tasks that are currently scheduled in the runner.
+    ///
+    /// The runner is left empty, so further tasks can be submitted to it.
+    pub fn tasks(&mut self) -> FuturesUnordered<BoxFuture<'static, ()>> {
+        std::mem::take(&mut self.tasks)
+    }
+
+    /// Gets a snapshot of the number of tasks of the [`Runner`] at each stage
+    /// of their lifecycle.
+    ///
+    /// The counters are updated independently, so a snapshot taken while
+    /// tasks are moving between stages may be off by the tasks in transit.
+    pub fn stats(&self) -> RunnerStats {
+        RunnerStats {
+            queued: self.counters.queued.load(Ordering::Relaxed),
+            running: self.counters.running.load(Ordering::Relaxed),
+            completed: self.counters.completed.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Checks whether the backend of the [`Runner`] is healthy.
+    pub fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        self.backend.healthcheck()
+    }
+
+    /// Sets the outcome replied with for tasks whose futures are dropped
+    /// before they complete ([`Outcome::Cancelled`] by default).
+    pub(crate) fn set_cancellation(&self, outcome: Outcome) {
+        if let Ok(mut cancellation) = self.cancellation.lock() {
+            *cancellation = outcome;
+        }
+    }
+
+    /// Shuts down the [`Runner`].
+    ///
+    /// Any submitted tasks that have not yet completed are cancelled (their
+    /// handles will resolve to a reply with an [`Outcome::Cancelled`]
+    /// outcome), and the backend is asked to clean up any resources it has
+    /// left behind.
+    pub async fn shutdown(&mut self) -> backend::Result<()> {
+        self.tasks.clear();
+        self.backend.shutdown().await
+    }
+
+    /// Runs all of the tasks currently scheduled in the [`Runner`].
+    ///
+    /// The runner can be reused afterwards: tasks submitted once this returns
+    /// are run by the next call.
+    pub async fn run(&mut self) {
+        join_all(self.tasks()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::cache::InMemoryCache;
+    use crate::engine::service::runner::backend::local::LocalBackend;
+    use crate::engine::task::resources::PREEMPTIBLE_MAX_RETRIES;
+    use crate::engine::task::testing::execution;
+    use crate::engine::task::testing::task;
+    use crate::engine::task::Resources;
+
+    #[tokio::test]
+    async fn handles_resolve_to_replies() {
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new());
+        let task = task(["echo", "hello"]);
+
+        let handle = runner.submit(task);
+        runner.run().await;
+
+        let reply = handle.await.unwrap();
+        assert_eq!(reply.backend, "local");
+        assert_eq!(reply.executions.unwrap()[0].stdout, "hello\n");
+    }
+
+    #[tokio::test]
+    async fn failed_tasks_are_retried() {
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new());
+        let task = Task::builder()
+            .extend_executions([execution(["false"])])
+            .max_retries(2)
+            .try_build()
+            .unwrap();
+
+        let handle = runner.submit(task);
+        runner.run().await;
+
+        let reply = handle.await.unwrap();
+        assert_eq!(reply.attempts, 3);
+        assert_ne!(reply.executions.unwrap()[0].status, 0);
+    }
 
-        Handle { callback: rx }
+    #[tokio::test]
+    async fn successful_tasks_are_not_retried() {
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new());
+        let task = Task::builder()
+            .extend_executions([execution(["true"])])
+            .max_retries(2)
+            .try_build()
+            .unwrap();
+
+        let handle = runner.submit(task);
+        runner.run().await;
+
+        assert_eq!(handle.await.unwrap().attempts, 1);
     }
 
-    /// Gets the tasks from the runner.
-    pub fn tasks(self) -> impl Iterator<Item = BoxFuture<'static, ()>> {
-        self.tasks.into_iter()
+    #[tokio::test]
+    async fn preemptible_tasks_are_retried() {
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new());
+        let task = Task::builder()
+            .extend_executions([execution(["false"])])
+            .resources(Resources::builder().preemptible(true).build())
+            .try_build()
+            .unwrap();
+
+        let handle = runner.submit(task);
+        runner.run().await;
+
+        assert_eq!(handle.await.unwrap().attempts, PREEMPTIBLE_MAX_RETRIES + 1);
     }
 
-    /// Runs all of the tasks scheduled in the [`Runner`].
-    pub async fn run(self) {
-        join_all(self.tasks).await;
+    #[tokio::test]
+    async fn cached_replies_are_reused() {
+        let cache = Arc::new(InMemoryCache::new());
+        let task = task(["date", "+%N"]);
+
+        let mut runner =
+            Runner::new(String::from("local"), LocalBackend::new()).with_cache(cache.clone());
+        let first = runner.submit(task.clone());
+        runner.run().await;
+        let first = first.await.unwrap();
+
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new()).with_cache(cache);
+        let second = runner.submit(task);
+        assert!(runner.tasks.is_empty());
+        runner.run().await;
+        let second = second.await.unwrap();
+
+        assert_eq!(
+            first.executions.unwrap()[0].stdout,
+            second.executions.unwrap()[0].stdout
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_replies_are_not_cached() {
+        let cache = Arc::new(InMemoryCache::new());
+        let task = task(["false"]);
+
+        let mut runner =
+            Runner::new(String::from("local"), LocalBackend::new()).with_cache(cache.clone());
+        let handle = runner.submit(task.clone());
+        runner.run().await;
+        handle.await.unwrap();
+
+        assert!(cache.get(&cache::key("local", &task)).is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_cancels_submitted_tasks() {
+        let mut runner = Runner::new(String::from("local"), LocalBackend::new());
+        let task = task(["echo", "hello"]);
+
+        let handle = runner.submit(task);
+        runner.shutdown().await.unwrap();
+
+        assert!(runner.tasks.is_empty());
+        let reply = handle.await.unwrap();
+        assert_eq!(reply.outcome, Outcome::Cancelled);
+        assert!(reply.executions.is_none());
+        assert_eq!(
+            runner.stats(),
+            RunnerStats {
+                failed: 1,
+                ..Default::default()
+            }
+        );
     }
 }
@@ -1,17 +1,24 @@
 //! Supported backends.
 
+use std::time::Duration;
+use std::time::SystemTime;
+
 use async_trait::async_trait;
 use futures::future::BoxFuture;
+use futures::FutureExt;
 use nonempty::NonEmpty;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
 
 pub mod config;
 pub mod docker;
 pub mod generic;
+pub mod local;
 pub mod tes;
 
 pub use config::Config;
 
+pub use bollard::container::LogOutput;
 pub use std::fmt::Debug;
 
 use crate::engine::Task;
@@ -21,34 +28,264 @@ use crate::BoxedError;
 pub type Result<T> = std::result::Result<T, BoxedError>;
 
 /// A result of a single execution.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ExecutionResult {
     /// The exit code.
-    pub status: u64,
+    ///
+    /// The exit code is signed so that backends can faithfully report codes
+    /// that are negative (e.g., `-1` when no exit code is available, or the
+    /// negated signal number when a local process is terminated by a signal).
+    pub status: i64,
+
+    /// How the execution exited.
+    ///
+    /// Unlike [`status`](Self::status), this distinguishes an exit code from
+    /// termination by a signal and from an exit that is not known.
+    pub exit: Exit,
 
     /// The contents of standard out.
     pub stdout: String,
 
     /// The contents of standard error.
     pub stderr: String,
+
+    /// Whether the execution was killed for running out of memory.
+    ///
+    /// Backends that cannot detect this always report `false`.
+    pub oom_killed: bool,
+
+    /// When the execution started (if known).
+    pub started_at: Option<SystemTime>,
+
+    /// When the execution ended (if known).
+    pub ended_at: Option<SystemTime>,
+
+    /// The identifier of whatever ran the execution on the backend (if
+    /// known), for correlating a result with the backend's own tooling.
+    ///
+    /// This is the name of the container for the Docker backend, the id of
+    /// the TES task for the TES backend, the id of the job for the generic
+    /// backend, and the id of the process for the local backend.
+    pub id: Option<String>,
+}
+
+impl ExecutionResult {
+    /// Gets how long the execution took (if both its start and end are known).
+    ///
+    /// If the clock went backwards while the execution ran, a zero duration is
+    /// returned.
+    pub fn duration(&self) -> Option<Duration> {
+        let (started_at, ended_at) = self.started_at.zip(self.ended_at)?;
+        Some(ended_at.duration_since(started_at).unwrap_or_default())
+    }
+}
+
+/// How an execution exited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// The execution exited with an exit code.
+    Code(i64),
+
+    /// The execution was terminated by a signal.
+    Signal(i32),
+
+    /// The execution had not exited when it was inspected.
+    Running,
+
+    /// How the execution exited is not known (e.g., because it never ran or
+    /// the backend did not report it).
+    Unknown,
+}
+
+impl Exit {
+    /// Gets the exit code (if the execution exited with one).
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            Exit::Code(code) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Gets the number of the signal that terminated the execution (if it
+    /// was terminated by one).
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            Exit::Signal(signal) => Some(*signal),
+            _ => None,
+        }
+    }
+
+    /// Gets whether the execution exited successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Exit::Code(0))
+    }
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exit::Code(code) => write!(f, "exit code {code}"),
+            Exit::Signal(signal) => write!(f, "signal {signal}"),
+            Exit::Running => write!(f, "still running"),
+            Exit::Unknown => write!(f, "unknown exit"),
+        }
+    }
+}
+
+/// A log of an output produced by a task.
+///
+/// This is analogous to the `OutputFileLog` of the TES specification.
+#[derive(Clone, Debug)]
+pub struct OutputLog {
+    /// The URL the output was copied to.
+    pub url: String,
+
+    /// The path of the output within the container.
+    pub path: String,
+
+    /// The size of the output in bytes.
+    ///
+    /// For a directory, this is the total size of the files within it.
+    pub size_bytes: u64,
+}
+
+/// The outcome of a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every execution of the task exited successfully.
+    Completed,
+
+    /// An execution of the task exited with a nonzero status.
+    ///
+    /// If more than one execution failed, this describes the first of them.
+    Failed {
+        /// The index of the execution that failed.
+        execution_index: usize,
+
+        /// The status the execution exited with.
+        status: i64,
+    },
+
+    /// The backend was unable to run the task (e.g., because a container
+    /// could not be created or the host lacks the requested resources).
+    BackendError(String),
+
+    /// The task was cancelled before it completed.
+    Cancelled,
+
+    /// The task did not complete within its time limit.
+    TimedOut,
+}
+
+impl Outcome {
+    /// Gets the outcome of a task whose executions all ran.
+    ///
+    /// The task is [`Completed`](Outcome::Completed) if every execution
+    /// exited with a status of zero; otherwise, it
+    /// [`Failed`](Outcome::Failed) with the first nonzero status.
+    pub fn from_executions<'a>(executions: impl IntoIterator<Item = &'a ExecutionResult>) -> Self {
+        executions
+            .into_iter()
+            .enumerate()
+            .find(|(_, execution)| execution.status != 0)
+            .map(|(execution_index, execution)| Outcome::Failed {
+                execution_index,
+                status: execution.status,
+            })
+            .unwrap_or(Outcome::Completed)
+    }
+
+    /// Gets whether the task completed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Outcome::Completed)
+    }
 }
 
 /// A reply from a backend when a task is completed.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Reply {
     /// The name of the backend that ran this.
     pub backend: String,
 
+    /// The outcome of the task.
+    pub outcome: Outcome,
+
     /// The results from each execution.
     pub executions: Option<NonEmpty<ExecutionResult>>,
+
+    /// The logs of the outputs produced by the task.
+    ///
+    /// Backends that do not retrieve outputs (e.g., the generic backend)
+    /// always report [`None`].
+    pub outputs: Option<NonEmpty<OutputLog>>,
+
+    /// The number of times the task was attempted.
+    ///
+    /// Backends always report a single attempt; retries are handled by the
+    /// [`Runner`](crate::engine::service::runner::Runner).
+    pub attempts: u32,
 }
 
 /// An execution backend.
 #[async_trait]
-pub trait Backend: Debug + Send + 'static {
+pub trait Backend: Debug + Send + Sync + 'static {
     /// Gets the default name for the backend.
     fn default_name(&self) -> &'static str;
 
-    /// Runs a task in a backend;
-    fn run(&self, name: String, task: Task, cb: Sender<Reply>) -> BoxFuture<'static, ()>;
+    /// Runs a task in a backend.
+    ///
+    /// If a `logs` channel is provided, backends that support it will send
+    /// chunks of the standard output and standard error streams through it as
+    /// they are produced. The aggregated output is always included in the
+    /// final [`Reply`] regardless.
+    fn run(
+        &self,
+        name: String,
+        task: Task,
+        cb: Sender<Reply>,
+        logs: Option<UnboundedSender<LogOutput>>,
+    ) -> BoxFuture<'static, ()>;
+
+    /// Checks whether the backend is reachable and able to accept tasks.
+    ///
+    /// By default, a backend is always considered healthy.
+    fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        async { true }.boxed()
+    }
+
+    /// Cleans up any resources the backend has left behind.
+    ///
+    /// This is intended to reclaim resources that were leaked by tasks that
+    /// never finished (e.g., because the process was killed mid-run). By
+    /// default, there is nothing to clean up.
+    fn shutdown(&self) -> BoxFuture<'static, Result<()>> {
+        async { Ok(()) }.boxed()
+    }
+}
+
+/// A boxed backend, such as one created from a [`Config`] (see
+/// [`Config::to_backend()`]).
+#[async_trait]
+impl Backend for Box<dyn Backend> {
+    fn default_name(&self) -> &'static str {
+        self.as_ref().default_name()
+    }
+
+    fn run(
+        &self,
+        name: String,
+        task: Task,
+        cb: Sender<Reply>,
+        logs: Option<UnboundedSender<LogOutput>>,
+    ) -> BoxFuture<'static, ()> {
+        self.as_ref().run(name, task, cb, logs)
+    }
+
+    fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        self.as_ref().healthcheck()
+    }
+
+    fn shutdown(&self) -> BoxFuture<'static, Result<()>> {
+        self.as_ref().shutdown()
+    }
 }
@@ -1,28 +1,109 @@
 //! Configuration for different types of backends
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[cfg(test)]
 use std::process::{Command, Output};
 
+use bollard::auth::DockerCredentials;
 use serde::Deserialize;
 use serde::Serialize;
 
-/// The left placeholder for the backend config
-const LEFT_PLACEHOLDER: &str = "~{";
-/// The right placeholder for the backend config
-const RIGHT_PLACEHOLDER: &str = "}";
+use crate::engine::service::runner::backend::docker::DockerBackend;
+use crate::engine::service::runner::backend::generic::GenericBackend;
+use crate::engine::service::runner::backend::tes::Auth;
+use crate::engine::service::runner::backend::tes::TesBackend;
+use crate::engine::service::runner::backend::Backend;
+use crate::engine::service::runner::backend::Result;
 
-/// Substitutes placeholders in a string with values from a hashmap
-pub(crate) fn substitute_placeholders(s: &str, substitutions: &HashMap<String, String>) -> String {
+/// The default left placeholder delimiter for the backend config
+pub const DEFAULT_LEFT_PLACEHOLDER: &str = "~{";
+/// The default right placeholder delimiter for the backend config
+pub const DEFAULT_RIGHT_PLACEHOLDER: &str = "}";
+
+/// Substitutes placeholders delimited by `left` and `right` in a string with
+/// values from a hashmap
+pub(crate) fn substitute_placeholders(
+    s: &str,
+    substitutions: &HashMap<String, String>,
+    left: &str,
+    right: &str,
+) -> String {
     let mut result = s.to_string();
     for (key, value) in substitutions {
-        let placeholder_key = format!("{}{}{}", LEFT_PLACEHOLDER, key, RIGHT_PLACEHOLDER);
+        let placeholder_key = format!("{}{}{}", left, key, right);
         result = result.replace(&placeholder_key, value);
     }
     result
 }
 
+/// Expands environment variables (`$VAR` or `${VAR}`) and the home directory
+/// (a leading `~/`) in a string.
+///
+/// Placeholders (delimited by `left` and `right`) are left untouched, as are
+/// references to environment variables that are not set.
+pub(crate) fn expand_environment(s: &str, left: &str, right: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(c) = rest.chars().next() {
+        // Copy placeholders through verbatim
+        if rest.starts_with(left) {
+            let end = rest[left.len()..]
+                .find(right)
+                .map(|i| left.len() + i + right.len())
+                .unwrap_or(rest.len());
+            result.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+
+        // Expand a home directory at the start of a word
+        if c == '~'
+            && rest[1..].starts_with('/')
+            && result.chars().last().is_none_or(char::is_whitespace)
+        {
+            if let Some(home) = dirs::home_dir() {
+                result.push_str(&home.to_string_lossy());
+                rest = &rest[1..];
+                continue;
+            }
+        }
+
+        if c == '$' {
+            let (name, len) = match rest[1..].strip_prefix('{') {
+                Some(braced) => match braced.find('}') {
+                    Some(end) => (&braced[..end], end + 3),
+                    None => ("", 0),
+                },
+                None => {
+                    let end = rest[1..]
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len() - 1);
+                    (&rest[1..end + 1], end + 1)
+                }
+            };
+
+            let valid = name
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+            if let Some(value) = valid.then(|| std::env::var(name).ok()).flatten() {
+                result.push_str(&value);
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    result
+}
+
 /// Configuration for an arbitrary backend
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
@@ -42,8 +123,122 @@ pub struct Config {
 }
 
 impl Config {
-    /// Submits a backend based on its config. Likely this method will be removed and the branch for Generic will be moved to GenericBackend's submit method.
-    /// Instead of this method we should have a to_backend() method or something similar that creates a Box<dyn Backend> based on config.
+    /// Creates the [`Backend`] described by this config.
+    ///
+    /// The backend should be registered with an engine under the configured
+    /// [`name`](Config::name).
+    pub fn to_backend(&self) -> Result<Box<dyn Backend>> {
+        match &self.kind {
+            BackendType::Generic(_) => {
+                let backend = GenericBackend::try_from(self.clone())
+                    .map_err(|_| format!("invalid generic backend `{}`", self.name))?;
+                Ok(Box::new(backend.to_runner()))
+            }
+            BackendType::Docker(docker) => {
+                let mut backend = match &docker.host {
+                    Some(host) => DockerBackend::try_new_with_host(docker.cleanup, host)?,
+                    None => DockerBackend::try_new(docker.cleanup)?,
+                };
+
+                if let Some(credentials) = &docker.credentials {
+                    backend = backend.with_credentials(credentials.into());
+                }
+
+                if let Some(user) = &docker.user {
+                    backend = backend.with_user(user);
+                } else if docker.current_user {
+                    backend = backend.with_current_user();
+                }
+
+                Ok(Box::new(
+                    backend.with_shared_container(docker.shared_container),
+                ))
+            }
+            BackendType::Tes(tes) => {
+                let mut backend = TesBackend::new_with_auth(&tes.url, tes.auth())?;
+                if let Some(ms) = tes.poll_interval_ms {
+                    backend = backend.with_poll_interval(Duration::from_millis(ms));
+                }
+
+                if let Some(ms) = tes.max_poll_duration_ms {
+                    backend = backend.with_max_poll_duration(Duration::from_millis(ms));
+                }
+
+                Ok(Box::new(backend))
+            }
+        }
+    }
+
+    /// Checks the invariants of the config that cannot be expressed by its
+    /// type, returning a description of each problem found.
+    pub(crate) fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.default_cpu == Some(0) {
+            problems.push(String::from("`default-cpu` must be greater than zero"));
+        }
+
+        if self.default_ram == Some(0) {
+            problems.push(String::from("`default-ram` must be greater than zero"));
+        }
+
+        match &self.kind {
+            BackendType::Generic(generic) => {
+                match &generic.job_id_regex {
+                    Some(regex) => match regex::Regex::new(regex) {
+                        Ok(regex) if regex.captures_len() < 2 => problems.push(String::from(
+                            "`job_id_regex` must have a capture group for the job id",
+                        )),
+                        Ok(_) => {}
+                        Err(e) => problems.push(format!("`job_id_regex` is invalid: {e}")),
+                    },
+                    None => {
+                        if generic.monitor.is_some() {
+                            problems.push(String::from("`monitor` requires a `job_id_regex`"));
+                        }
+
+                        if generic.kill.is_some() {
+                            problems.push(String::from("`kill` requires a `job_id_regex`"));
+                        }
+                    }
+                }
+
+                if generic.left_placeholder.is_empty() || generic.right_placeholder.is_empty() {
+                    problems.push(String::from("placeholder delimiters must not be empty"));
+                }
+
+                if generic.monitor_frequency == Some(0) {
+                    problems.push(String::from(
+                        "`monitor_frequency` must be greater than zero",
+                    ));
+                }
+            }
+            BackendType::Docker(docker) => {
+                if docker.user.is_some() && docker.current_user {
+                    problems.push(String::from(
+                        "only one of `user` and `current_user` may be specified",
+                    ));
+                }
+            }
+            BackendType::Tes(tes) => {
+                if let Err(e) = url::Url::parse(&tes.url) {
+                    problems.push(format!("`url` is invalid: {e}"));
+                }
+
+                if tes.token.is_some() && tes.auth.is_some() {
+                    problems.push(String::from(
+                        "only one of `token` and `auth` may be specified",
+                    ));
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Submits a backend based on its config. Likely this method will be
+    /// removed in favor of running tasks on the backend created by
+    /// [`to_backend()`](Config::to_backend).
     #[cfg(test)]
     pub fn submit(&self, substitutions: &mut HashMap<String, String>) -> Output {
         // Replace default flags only if it isn't already set
@@ -62,7 +257,12 @@ impl Config {
 
         match &self.kind {
             BackendType::Generic(generic) => {
-                let command = substitute_placeholders(&generic.submit, substitutions);
+                let command = substitute_placeholders(
+                    &generic.submit,
+                    substitutions,
+                    &generic.left_placeholder,
+                    &generic.right_placeholder,
+                );
 
                 Command::new("sh")
                     .arg("-c")
@@ -87,6 +287,9 @@ pub enum BackendType {
     Generic(GenericBackendConfig),
     /// Docker config details
     Docker(DockerBackendConfig),
+    /// Task Execution Service (TES) config details
+    #[serde(rename = "TES")]
+    Tes(TesBackendConfig),
 }
 
 /// Extra attributes for Generic Backends
@@ -102,11 +305,114 @@ pub struct GenericBackendConfig {
     pub monitor_frequency: Option<u32>,
     /// The script command that will run on kill
     pub kill: Option<String>,
+    /// The script command that will run to check that the backend is able to
+    /// accept jobs
+    pub healthcheck: Option<String>,
+    /// Whether to expand environment variables (`$VAR` or `${VAR}`) and the
+    /// home directory (`~/`) in the command strings
+    #[serde(default)]
+    pub expand_env: bool,
+    /// The delimiter that starts a placeholder in the command strings
+    /// (defaults to `~{`)
+    #[serde(default = "default_left_placeholder")]
+    pub left_placeholder: String,
+    /// The delimiter that ends a placeholder in the command strings (defaults
+    /// to `}`)
+    #[serde(default = "default_right_placeholder")]
+    pub right_placeholder: String,
+}
+
+/// Gets the default left placeholder delimiter for generic backends
+fn default_left_placeholder() -> String {
+    DEFAULT_LEFT_PLACEHOLDER.to_string()
+}
+
+/// Gets the default right placeholder delimiter for generic backends
+fn default_right_placeholder() -> String {
+    DEFAULT_RIGHT_PLACEHOLDER.to_string()
 }
 
 /// Extra attributes for Docker backends
 #[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct DockerBackendConfig;
+pub struct DockerBackendConfig {
+    /// Whether to remove containers once their executions complete (defaults
+    /// to `true`)
+    #[serde(default = "default_cleanup")]
+    pub cleanup: bool,
+    /// The Docker host to connect to instead of the default (e.g.,
+    /// `unix:///var/run/docker.sock` or `tcp://localhost:2375`)
+    pub host: Option<String>,
+    /// The credentials used when pulling images
+    pub credentials: Option<RegistryCredentials>,
+    /// Whether the executions of a task share a single container (defaults
+    /// to `false`)
+    #[serde(default)]
+    pub shared_container: bool,
+    /// The user that commands run as within containers, in any form accepted
+    /// by `docker run --user` (e.g., `1000:1000`)
+    pub user: Option<String>,
+    /// Whether commands run as the `uid:gid` of the current process within
+    /// containers (defaults to `false`)
+    #[serde(default)]
+    pub current_user: bool,
+}
+
+/// Gets the default cleanup setting for Docker backends
+fn default_cleanup() -> bool {
+    true
+}
+
+/// Credentials for a container image registry
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RegistryCredentials {
+    /// The username to log in with
+    pub username: String,
+    /// The password to log in with
+    pub password: String,
+    /// The address of the registry (defaults to Docker Hub)
+    pub server: Option<String>,
+}
+
+impl From<&RegistryCredentials> for DockerCredentials {
+    fn from(credentials: &RegistryCredentials) -> Self {
+        Self {
+            username: Some(credentials.username.clone()),
+            password: Some(credentials.password.clone()),
+            serveraddress: credentials.server.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Extra attributes for TES backends
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TesBackendConfig {
+    /// The URL of the TES server
+    pub url: String,
+    /// The token used to authenticate with the TES server using HTTP basic
+    /// authentication (deprecated in favor of `auth`)
+    pub token: Option<String>,
+    /// The authentication used with the TES server (e.g.,
+    /// `auth = { bearer = "..." }`)
+    pub auth: Option<Auth>,
+    /// The interval at which submitted tasks are polled in milliseconds
+    pub poll_interval_ms: Option<u64>,
+    /// How long a submitted task is polled in milliseconds before it is
+    /// cancelled and reported as timed out (unbounded by default)
+    pub max_poll_duration_ms: Option<u64>,
+}
+
+impl TesBackendConfig {
+    /// Gets the authentication used with the TES server, falling back to HTTP
+    /// basic authentication with the `token` if no `auth` is specified.
+    pub fn auth(&self) -> Auth {
+        match (&self.auth, &self.token) {
+            (Some(auth), _) => auth.clone(),
+            (None, Some(token)) => Auth::Basic(token.clone()),
+            (None, None) => Auth::None,
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -159,10 +465,82 @@ mod tests {
         match &backend.kind {
             super::BackendType::Generic(generic) => {
                 let command_str = generic.submit.clone();
-                let subbed = super::substitute_placeholders(&command_str, &substitutions);
+                let subbed = super::substitute_placeholders(
+                    &command_str,
+                    &substitutions,
+                    &generic.left_placeholder,
+                    &generic.right_placeholder,
+                );
                 assert_eq!(subbed, "    bsub -q compbio -n 1 -cwd ~{cwd} -o ~{cwd}/stdout.lsf -e ~{cwd}/stderr.lsf -R \"rusage[mem=~{memory_mb}] span[hosts=1]\" ~{script}\n");
             }
             _ => panic!("expected generic backend"),
         }
     }
+
+    #[test]
+    fn docker_and_tes_configs_load() {
+        let config = Config::fixture("docker_and_tes.toml").unwrap();
+
+        match &config.backend("docker").unwrap().kind {
+            super::BackendType::Docker(docker) => {
+                assert!(!docker.cleanup);
+                assert_eq!(docker.host.as_deref(), Some("unix:///var/run/docker.sock"));
+                assert_eq!(docker.credentials.as_ref().unwrap().username, "kids24");
+                assert!(docker.shared_container);
+                assert_eq!(docker.user.as_deref(), Some("1000:1000"));
+                assert!(!docker.current_user);
+            }
+            _ => panic!("expected docker backend"),
+        }
+
+        match &config.backend("docker-defaults").unwrap().kind {
+            super::BackendType::Docker(docker) => {
+                assert!(docker.cleanup);
+                assert!(docker.host.is_none());
+                assert!(docker.credentials.is_none());
+                assert!(!docker.shared_container);
+                assert!(docker.user.is_none());
+                assert!(!docker.current_user);
+            }
+            _ => panic!("expected docker backend"),
+        }
+
+        match &config.backend("tes").unwrap().kind {
+            super::BackendType::Tes(tes) => {
+                assert_eq!(tes.url, "http://localhost:8000");
+                assert_eq!(tes.token.as_deref(), Some("secret"));
+                assert!(matches!(tes.auth(), super::Auth::Basic(token) if token == "secret"));
+                assert_eq!(tes.poll_interval_ms, Some(1000));
+                assert_eq!(tes.max_poll_duration_ms, Some(3_600_000));
+            }
+            _ => panic!("expected TES backend"),
+        }
+
+        match &config.backend("tes-bearer").unwrap().kind {
+            super::BackendType::Tes(tes) => {
+                assert!(matches!(tes.auth(), super::Auth::Bearer(token) if token == "secret"));
+            }
+            _ => panic!("expected TES backend"),
+        }
+    }
+
+    #[test]
+    fn environment_expansion_skips_placeholders() {
+        let path = std::env::var("PATH").unwrap();
+
+        assert_eq!(
+            super::expand_environment("$PATH:${PATH} ~{cwd}", "~{", "}"),
+            format!("{path}:{path} ~{{cwd}}")
+        );
+        assert_eq!(
+            super::expand_environment("$CRANKSHAFT_TEST_UNSET $1 $ ${ ~{$PATH}", "~{", "}"),
+            "$CRANKSHAFT_TEST_UNSET $1 $ ${ ~{$PATH}"
+        );
+
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(
+            super::expand_environment("ls ~/data a~/b", "~{", "}"),
+            format!("ls {}/data a~/b", home.display())
+        );
+    }
 }
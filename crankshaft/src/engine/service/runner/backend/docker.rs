@@ -1,39 +1,75 @@
 //! A docker runner service.
 
+use std::collections::HashMap;
+use std::fs;
 use std::io::Cursor;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use async_trait::async_trait;
+use bollard::auth::DockerCredentials;
 use bollard::container::Config;
 use bollard::container::CreateContainerOptions;
+use bollard::container::DownloadFromContainerOptions;
+use bollard::container::InspectContainerOptions;
 use bollard::container::KillContainerOptions;
+use bollard::container::ListContainersOptions;
 use bollard::container::LogOutput;
+use bollard::container::RemoveContainerOptions;
 use bollard::container::StartContainerOptions;
 use bollard::container::UploadToContainerOptions;
 use bollard::errors::Error;
 use bollard::exec::CreateExecOptions;
 use bollard::exec::StartExecResults;
+use bollard::image::CreateImageOptions;
 use bollard::models::HostConfig;
 use bollard::models::Mount;
+use bollard::models::SystemInfo;
 use bollard::Docker;
+use bollard::API_DEFAULT_VERSION;
 use futures::future::BoxFuture;
 use futures::FutureExt;
+use futures::Stream;
+use futures::StreamExt;
 use futures::TryStreamExt;
 use nonempty::NonEmpty;
 use random_word::Lang;
-use tmp_mount::TmpMount;
+use regex::RegexSet;
+use retry::retry;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
+use tracing::error;
+use tracing::info;
+use tracing::info_span;
+use tracing::instrument;
+use tracing::warn;
+use tracing::Instrument as _;
+use tracing::Span;
+use url::Url;
+use volume::Volume;
 
 use crate::engine::service::runner::backend::Backend;
 use crate::engine::service::runner::backend::ExecutionResult;
+use crate::engine::service::runner::backend::Exit;
+use crate::engine::service::runner::backend::Outcome;
+use crate::engine::service::runner::backend::OutputLog;
 use crate::engine::service::runner::backend::Reply;
+use crate::engine::service::runner::backend::Result as BackendResult;
+use crate::engine::task::input;
+use crate::engine::task::output;
 use crate::engine::task::Execution;
 use crate::engine::task::Input;
+use crate::engine::task::Output;
 use crate::engine::task::Resources;
 use crate::engine::Task;
+use crate::BoxedError;
 
+pub mod retry;
 pub mod tmp_mount;
+pub mod volume;
 
 /// The number of parts in the random name of each Docker container.
 pub const NAME_PARTS: usize = 4;
@@ -44,9 +80,74 @@ pub const NAME_SEPARATOR: &str = "-";
 /// The working dir name inside the docker container
 pub const WORKDIR: &str = "/workdir";
 
+/// The label attached to every container created by crankshaft.
+///
+/// Together with the [`RUN_LABEL`], this allows the containers leaked by a
+/// backend to be found and removed when the engine is [shut
+/// down](crate::engine::Engine::shutdown).
+pub const MANAGED_LABEL: &str = "crankshaft.managed";
+
+/// The label holding the name of the task a container is running (if the task
+/// is named).
+pub const TASK_LABEL: &str = "crankshaft.task";
+
+/// The label holding the index of the execution a container is running within
+/// its task.
+pub const EXECUTION_LABEL: &str = "crankshaft.execution";
+
+/// The label holding the run id of the backend that created a container.
+///
+/// A backend only removes the containers with its own run id when it is shut
+/// down, so that engines sharing a Docker host do not remove each other's
+/// containers. To clean up after a process that was killed mid-run, create a
+/// backend with the same run id (see [`DockerBackend::with_run_id()`]).
+pub const RUN_LABEL: &str = "crankshaft.run";
+
+/// The maximum number of names tried when creating a container.
+///
+/// Container names are random, so a name is only ever in use if it collides
+/// with that of another container; a new name is generated each time.
+pub const MAX_NAME_ATTEMPTS: u32 = 5;
+
+/// The read/write timeout (in seconds) when connecting to an explicit Docker
+/// host.
+const CONNECT_TIMEOUT: u64 = 120;
+
 /// A [`Result`](std::result::Result) with an [`Error`]
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// A resource limit requested by a task that exceeds the capacity of the
+/// Docker host.
+///
+/// The Docker daemon rejects such containers, so the task is failed before any
+/// container is created, with this error as the standard error of the
+/// execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourcesUnavailable {
+    /// The name of the resource (`cpu` or `memory`).
+    pub resource: &'static str,
+
+    /// The amount requested (in cores for `cpu` and in bytes for `memory`).
+    pub requested: i64,
+
+    /// The amount available on the Docker host.
+    pub available: i64,
+}
+
+impl std::fmt::Display for ResourcesUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "requested {requested} {resource} but the Docker host only has {available}",
+            requested = self.requested,
+            resource = self.resource,
+            available = self.available
+        )
+    }
+}
+
+impl std::error::Error for ResourcesUnavailable {}
+
 /// A local execution backend.
 #[derive(Debug)]
 pub struct DockerBackend {
@@ -55,18 +156,143 @@ pub struct DockerBackend {
 
     /// Whether or not to clean up containers.
     cleanup: bool,
+
+    /// The policy for retrying transient Docker daemon errors.
+    retry_policy: retry::Policy,
+
+    /// The id attached to every container created by this backend.
+    run_id: String,
+
+    /// The credentials used when pulling images (if any).
+    credentials: Option<DockerCredentials>,
+
+    /// Whether the executions of a task share a single container.
+    shared_container: bool,
+
+    /// The user (and optionally group) that commands run as within
+    /// containers (if not the image's default).
+    user: Option<String>,
 }
 
 impl DockerBackend {
     /// Attempts to create a new [`Docker`].
     ///
     /// Note that, currently, we connect [using defaults](Docker::connect_with_defaults).
+    /// Transient Docker daemon errors are retried using the default
+    /// [`retry::Policy`] (see [`with_retry_policy()`](Self::with_retry_policy)).
+    ///
+    /// A random run id is generated for the backend and attached to each
+    /// container it creates (see [`RUN_LABEL`]).
     pub fn try_new(cleanup: bool) -> Result<Self> {
-        let inner = Docker::connect_with_defaults().map(Arc::new)?;
-        Ok(Self {
-            client: inner,
+        Docker::connect_with_defaults().map(|client| Self::with_client(client, cleanup))
+    }
+
+    /// Attempts to create a new [`Docker`] connected to a specific host.
+    ///
+    /// Hosts with a `tcp://` or `http://` scheme are connected to over HTTP;
+    /// any other host is treated as a Unix socket (or a Windows named pipe).
+    pub fn try_new_with_host(cleanup: bool, host: &str) -> Result<Self> {
+        let client = if host.starts_with("tcp://") || host.starts_with("http://") {
+            Docker::connect_with_http(host, CONNECT_TIMEOUT, API_DEFAULT_VERSION)?
+        } else {
+            Docker::connect_with_socket(host, CONNECT_TIMEOUT, API_DEFAULT_VERSION)?
+        };
+
+        Ok(Self::with_client(client, cleanup))
+    }
+
+    /// Creates a new [`DockerBackend`] from a connected client.
+    fn with_client(client: Docker, cleanup: bool) -> Self {
+        Self {
+            client: Arc::new(client),
             cleanup,
-        })
+            retry_policy: Default::default(),
+            run_id: random_name(),
+            credentials: None,
+            shared_container: false,
+            user: None,
+        }
+    }
+
+    /// Gets the id attached to every container created by this backend.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Sets the id attached to every container created by this backend.
+    ///
+    /// # Notes
+    ///
+    /// This overwrites the randomly generated run id.
+    pub fn with_run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.run_id = run_id.into();
+        self
+    }
+
+    /// Sets the credentials used when pulling images.
+    ///
+    /// Images that are not present on the Docker host are pulled before their
+    /// containers are created.
+    pub fn with_credentials(mut self, credentials: DockerCredentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Sets whether the executions of a task share a single container.
+    ///
+    /// By default, each execution is run in a fresh container, so nothing
+    /// written outside of the task's volumes survives from one execution to
+    /// the next. When the container is shared, it is created (and the inputs
+    /// are inserted) once for the first execution and each execution is then
+    /// run within it in turn, so intermediate files persist between them.
+    ///
+    /// # Notes
+    ///
+    /// A container can only be shared by executions that use the same image;
+    /// the executions of a task that use different images are each run in
+    /// their own container regardless. A shared container is created with the
+    /// resources of the first execution, so the
+    /// [resources](crate::engine::task::Execution::resources) of any later
+    /// execution are not applied.
+    pub fn with_shared_container(mut self, shared: bool) -> Self {
+        self.shared_container = shared;
+        self
+    }
+
+    /// Sets the user that commands run as within containers.
+    ///
+    /// The user is given in any form accepted by `docker run --user`, such
+    /// as a name or a `uid:gid` pair (e.g. `1000:1000`). By default, commands
+    /// run as the image's default user, which is usually `root`, so files
+    /// written to mounted volumes end up owned by `root` on the host.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any user previously provided to the
+    /// backend.
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Sets the user that commands run as within containers to the `uid:gid`
+    /// of the current process, so files written to mounted volumes are owned
+    /// by the user running crankshaft.
+    ///
+    /// See [`with_user()`](Self::with_user). On platforms without user ids,
+    /// this has no effect.
+    pub fn with_current_user(mut self) -> Self {
+        if let Some(user) = current_user() {
+            self.user = Some(user);
+        }
+        self
+    }
+
+    /// Sets the policy for retrying transient Docker daemon errors when
+    /// creating and starting containers.
+    pub fn with_retry_policy(mut self, policy: retry::Policy) -> Self {
+        self.retry_policy = policy;
+        self
     }
 }
 
@@ -76,58 +302,289 @@ impl Backend for DockerBackend {
         "docker"
     }
 
-    fn run(&self, name: String, task: Task, cb: Sender<Reply>) -> BoxFuture<'static, ()> {
-        let mut client = self.client.clone();
+    fn run(
+        &self,
+        name: String,
+        task: Task,
+        cb: Sender<Reply>,
+        logs: Option<UnboundedSender<LogOutput>>,
+    ) -> BoxFuture<'static, ()> {
+        let client = self.client.clone();
         let cleanup = self.cleanup;
+        let retry_policy = self.retry_policy;
+        let run_id = self.run_id.clone();
+        let credentials = self.credentials.clone();
+        let shared_container = self.shared_container;
+        let user = self.user.clone();
+        let span = info_span!("run", backend = %name, task = task.name(), run_id = %run_id);
 
         async move {
-            let mut results: Option<NonEmpty<ExecutionResult>> = None;
+            let mut results = Vec::new();
+            let mut outputs = Vec::new();
 
             // Generate mounts to be shared among tasks
-            let tmp_mounts: Vec<TmpMount> = task
+            let volumes = task
                 .volumes()
                 .into_iter()
                 .flatten()
-                .map(|s| TmpMount::from_str(s).unwrap())
-                .collect();
+                .map(|s| Volume::from_str(s))
+                .collect::<std::result::Result<Vec<_>, _>>();
+            let volumes = match volumes {
+                Ok(volumes) => volumes,
+                Err(e) => {
+                    error!(error = %e, "task has an invalid volume");
+                    let _ = cb.send(Reply {
+                        backend: name,
+                        outcome: Outcome::BackendError(e.to_string()),
+                        executions: Some(NonEmpty::new(failure(e.to_string()))),
+                        outputs: None,
+                        attempts: 1,
+                    });
+                    return;
+                }
+            };
+
+            // Fail the task up front if any of its inputs cannot be inserted
+            // into a container
+            if let Some(message) = unsupported_input(&task) {
+                warn!(message, "task has an unsupported input");
+                let _ = cb.send(Reply {
+                    backend: name,
+                    outcome: Outcome::BackendError(message.clone()),
+                    executions: Some(NonEmpty::new(failure(message))),
+                    outputs: None,
+                    attempts: 1,
+                });
+                return;
+            }
+
+            // Executions can only share a container if they share an image
+            let mut images = task.executions().map(Execution::image);
+            let first_image = images.next();
+            let shared = shared_container && images.all(|image| Some(image) == first_image);
+
+            if shared_container && !shared {
+                warn!(
+                    "the executions of the task use different images, so each is run in its own \
+                     container"
+                );
+            }
+
+            // Fail the task up front if it has a volume that cannot be shared
+            // by its executions
+            let executions = task.executions().count();
+            if let Some(message) = unshareable_volume(&volumes, shared, executions) {
+                warn!(message, "task has a volume that cannot be shared");
+                let _ = cb.send(Reply {
+                    backend: name,
+                    outcome: Outcome::BackendError(message.clone()),
+                    executions: Some(NonEmpty::new(failure(message))),
+                    outputs: None,
+                    attempts: 1,
+                });
+                return;
+            }
+
+            let mounts: Vec<Mount> = volumes.iter().map(|v| v.into()).collect();
 
-            let mounts: Vec<Mount> = tmp_mounts.iter().map(|tm| tm.into()).collect();
+            // The capacity of the host is only needed when resources are requested
+            let requested = task
+                .executions()
+                .filter_map(|execution| task.resources_for(execution))
+                .collect::<Vec<_>>();
+            let info = match requested.is_empty() {
+                true => None,
+                false => client.info().await.ok(),
+            };
 
-            for execution in task.executions() {
-                let name = random_name();
+            // Fail the task up front if the host cannot satisfy the limits of
+            // any of its executions
+            let unavailable = info.as_ref().and_then(|info| {
+                requested.iter().find_map(|resources| {
+                    check_resources(&HostConfig::from(*resources), info).err()
+                })
+            });
+
+            if let Some(e) = unavailable {
+                warn!(error = %e, "task requests more resources than are available");
+                let _ = cb.send(Reply {
+                    backend: name,
+                    outcome: Outcome::BackendError(e.to_string()),
+                    executions: Some(NonEmpty::new(failure(e.to_string()))),
+                    outputs: None,
+                    attempts: 1,
+                });
+                return;
+            }
+
+            let last = executions - 1;
+            let mut backend_error = None;
+            let mut output_error = None;
+
+            let mut container = String::new();
+
+            for (index, execution) in task.executions().enumerate() {
+                let mut started_at = SystemTime::now();
 
-                // Create the container
-                container_create(&name, execution, task.resources(), &mut client, &mounts[..])
-                    .await;
+                if index == 0 || !shared {
+                    container = random_name();
 
-                // Start the container
-                container_start(&name, &mut client).await;
+                    // Label the container so it can be correlated with its task
+                    let execution_index = index.to_string();
+                    let mut labels = HashMap::from([
+                        (MANAGED_LABEL, "true"),
+                        (EXECUTION_LABEL, execution_index.as_str()),
+                        (RUN_LABEL, run_id.as_str()),
+                    ]);
 
-                // Insert inputs
-                if let Some(inputs) = task.inputs() {
-                    for input in inputs {
-                        insert_input(&name, &mut client, input).await;
+                    if let Some(task_name) = task.name() {
+                        labels.insert(TASK_LABEL, task_name);
                     }
-                };
 
-                // Run a command
-                let exec_result = container_exec(&name, execution, &mut client).await;
+                    // Pull the image if it is not already present
+                    let pulled = image_pull(execution.image(), &client, credentials.as_ref()).await;
+                    if let Err(e) = pulled {
+                        error!(error = %e, "failed to pull image");
+                        let message = format!("failed to pull image `{}`: {e}", execution.image());
+                        results.push(failure(message.clone()));
+                        backend_error = Some(message);
+                        break;
+                    }
+
+                    // Create the container, choosing a new name if the random
+                    // one is already in use
+                    let resources = task.resources_for(execution);
+                    let host_config = HostConfig {
+                        mounts: Some(mounts.clone()),
+                        storage_opt: storage_opt(resources, info.as_ref()),
+                        ..resources.map(HostConfig::from).unwrap_or_default()
+                    };
+                    started_at = SystemTime::now();
+                    let mut name_attempts = 1;
+                    let created = loop {
+                        let created = retry(&retry_policy, || {
+                            container_create(
+                                &container,
+                                execution,
+                                &client,
+                                &host_config,
+                                &labels,
+                                user.as_deref(),
+                            )
+                        })
+                        .await;
+
+                        match created {
+                            Err(e) if is_name_conflict(&e) && name_attempts < MAX_NAME_ATTEMPTS => {
+                                warn!(container, "container name is already in use; renaming");
+                                container = random_name();
+                                name_attempts += 1;
+                            }
+                            created => break created,
+                        }
+                    };
 
-                if cleanup {
-                    client
-                        .kill_container(&name, None::<KillContainerOptions<String>>)
+                    if let Err(e) = created {
+                        error!(error = %e, "failed to create container");
+                        let message = format!("failed to create container: {e}");
+                        results.push(failure(message.clone()));
+                        backend_error = Some(message);
+                        break;
+                    }
+
+                    // Start the container
+                    let started =
+                        retry(&retry_policy, || container_start(&container, &client)).await;
+                    if let Err(e) = started {
+                        error!(error = %e, "failed to start container");
+                        if cleanup {
+                            let _ = container_remove(&container, &client).await;
+                        }
+
+                        let message = format!("failed to start container: {e}");
+                        results.push(failure(message.clone()));
+                        backend_error = Some(message);
+                        break;
+                    }
+
+                    // Insert inputs
+                    let mut inserted = Ok(());
+                    for input in task.inputs().into_iter().flatten() {
+                        inserted = insert_input(&container, &client, input)
+                            .await
+                            .map_err(|e| format!("failed to insert input `{}`: {e}", input.path()));
+                        if inserted.is_err() {
+                            break;
+                        }
+                    }
+
+                    if let Err(message) = inserted {
+                        error!(error = message, "failed to insert input");
+                        if cleanup {
+                            let _ = container_remove(&container, &client).await;
+                        }
+
+                        results.push(failure(message.clone()));
+                        backend_error = Some(message);
+                        break;
+                    }
+                }
+
+                // Run a command
+                let executed =
+                    container_exec(&container, execution, &client, logs.as_ref(), started_at)
                         .await
-                        .unwrap();
-                    client.remove_container(&name, None).await.unwrap();
+                        .map_err(|e| format!("failed to run execution: {e}"));
+                let exec_result = match executed {
+                    Ok(result) => result,
+                    Err(message) => {
+                        error!(error = message, "failed to run execution");
+                        if cleanup {
+                            let _ = container_remove(&container, &client).await;
+                        }
+
+                        results.push(failure(message.clone()));
+                        backend_error = Some(message);
+                        break;
+                    }
+                };
+
+                // Retrieve outputs from the container of the final execution
+                if index == last {
+                    for output in task.outputs().into_iter().flatten() {
+                        let retrieved = retrieve_output(&container, &client, output).await;
+                        match retrieved {
+                            Ok(size_bytes) => outputs.push(OutputLog {
+                                url: output.url().to_string(),
+                                path: output.path().to_string(),
+                                size_bytes,
+                            }),
+                            Err(e) => {
+                                error!(
+                                    error = %e,
+                                    output = output.path(),
+                                    "failed to retrieve output"
+                                );
+                                output_error = Some(format!(
+                                    "failed to retrieve output `{path}`: {e}",
+                                    path = output.path()
+                                ));
+                                break;
+                            }
+                        }
+                    }
                 }
 
-                results = match results {
-                    Some(mut results) => {
-                        results.push(exec_result);
-                        Some(results)
+                // NOTE: the execution has already finished, so a container
+                // that cannot be removed does not change the reply.
+                if cleanup && (index == last || !shared) {
+                    let removed = container_remove(&container, &client).await;
+                    if let Err(e) = removed {
+                        warn!(error = %e, container, "failed to remove container");
                     }
-                    None => Some(NonEmpty::new(exec_result)),
                 }
+
+                results.push(exec_result);
             }
 
             // NOTE: this will return an error if the receiver has already hung
@@ -136,13 +593,80 @@ impl Backend for DockerBackend {
             // this error.
             let _ = cb.send(Reply {
                 backend: name,
-                executions: Some(results.expect("at least one execution to be run")),
+                // NOTE: a failed execution is a better explanation of a
+                // missing output than the missing output itself.
+                outcome: match (backend_error, Outcome::from_executions(&results)) {
+                    (Some(message), _) => Outcome::BackendError(message),
+                    (None, Outcome::Completed) => match output_error {
+                        Some(message) => Outcome::BackendError(message),
+                        None => Outcome::Completed,
+                    },
+                    (None, outcome) => outcome,
+                },
+                executions: Some(
+                    NonEmpty::from_vec(results).expect("at least one execution to be run"),
+                ),
+                outputs: NonEmpty::from_vec(outputs),
+                attempts: 1,
             });
         }
+        .instrument(span)
+        .boxed()
+    }
+
+    fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        let client = self.client.clone();
+        async move { client.ping().await.is_ok() }.boxed()
+    }
+
+    fn shutdown(&self) -> BoxFuture<'static, BackendResult<()>> {
+        let client = self.client.clone();
+        let filters = leaked_filters(&self.run_id);
+
+        async move {
+            let options = ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            };
+
+            for container in client.list_containers(Some(options)).await? {
+                let Some(id) = container.id else {
+                    continue;
+                };
+
+                info!(container = id, "removing leaked container");
+                client
+                    .remove_container(
+                        &id,
+                        Some(RemoveContainerOptions {
+                            force: true,
+                            ..Default::default()
+                        }),
+                    )
+                    .await?;
+            }
+
+            Ok(())
+        }
         .boxed()
     }
 }
 
+/// Gets the filters that match the containers created by the backend with the
+/// given run id.
+///
+/// Docker only matches containers that have every one of the labels.
+fn leaked_filters(run_id: &str) -> HashMap<String, Vec<String>> {
+    HashMap::from([(
+        String::from("label"),
+        vec![
+            format!("{MANAGED_LABEL}=true"),
+            format!("{RUN_LABEL}={run_id}"),
+        ],
+    )])
+}
+
 /// Generates a random name for a Docker container.
 fn random_name() -> String {
     (1..=NAME_PARTS)
@@ -151,20 +675,71 @@ fn random_name() -> String {
         .join(NAME_SEPARATOR)
 }
 
+/// Gets the `uid:gid` of the current process.
+///
+/// Returns [`None`] on platforms without user ids.
+fn current_user() -> Option<String> {
+    #[cfg(unix)]
+    {
+        // SAFETY: `getuid` and `getgid` always succeed and have no side
+        // effects.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Some(format!("{uid}:{gid}"))
+    }
+
+    #[cfg(not(unix))]
+    None
+}
+
+/// Gets the storage options of a container that honor its requested disk
+/// size.
+///
+/// Disk requests can only be honored by some storage drivers, so the
+/// requested size is ignored (with a warning) if the host does not report one
+/// that supports it.
+fn storage_opt(
+    resources: Option<&Resources>,
+    info: Option<&SystemInfo>,
+) -> Option<HashMap<String, String>> {
+    let disk_gb = resources.and_then(Resources::disk_gb)?;
+
+    match info {
+        Some(info) if supports_storage_opt(info) => Some(HashMap::from([(
+            String::from("size"),
+            format!("{disk_gb}G"),
+        )])),
+        _ => {
+            warn!(
+                "ignoring the requested disk size as the storage driver of the Docker daemon \
+                 does not support it"
+            );
+            None
+        }
+    }
+}
+
+/// Returns whether an error from the Docker daemon means that the name of a
+/// container being created is already in use.
+fn is_name_conflict(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::DockerResponseServerError {
+            status_code: 409,
+            ..
+        }
+    )
+}
+
 /// Creates a container using the Docker client.
+#[instrument(level = "debug", skip_all, fields(container = name, image = execution.image()))]
 async fn container_create(
     name: &str,
     execution: &Execution,
-    resources: Option<&Resources>,
-    client: &mut Arc<Docker>,
-    mounts: &[Mount],
-) {
-    // Configure Docker to use all mounts
-    let host_config = HostConfig {
-        mounts: Some(mounts.to_vec()),
-        ..resources.map(HostConfig::from).unwrap_or_default()
-    };
-
+    client: &Arc<Docker>,
+    host_config: &HostConfig,
+    labels: &HashMap<&str, &str>,
+    user: Option<&str>,
+) -> Result<()> {
     let options = Some(CreateContainerOptions {
         name,
         ..Default::default()
@@ -173,42 +748,162 @@ async fn container_create(
     let config = Config {
         image: Some(execution.image()),
         tty: Some(true),
-        host_config: Some(host_config),
+        host_config: Some(host_config.clone()),
         working_dir: execution.workdir().map(String::as_str),
+        labels: Some(labels.clone()),
+        user,
         ..Default::default()
     };
 
-    client.create_container(options, config).await.unwrap();
+    client.create_container(options, config).await?;
+    Ok(())
+}
+
+/// Checks the resource limits of a container against the capacity of the
+/// Docker host.
+///
+/// Limits are only checked when the host reports its capacity.
+fn check_resources(
+    host_config: &HostConfig,
+    info: &SystemInfo,
+) -> std::result::Result<(), ResourcesUnavailable> {
+    let limits = [
+        ("cpu", host_config.cpu_count, info.ncpu),
+        ("memory", host_config.memory, info.mem_total),
+    ];
+
+    for (resource, requested, available) in limits {
+        if let (Some(requested), Some(available)) = (requested, available) {
+            if requested > available {
+                return Err(ResourcesUnavailable {
+                    resource,
+                    requested,
+                    available,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a failed result for an execution whose container never ran.
+fn failure(stderr: String) -> ExecutionResult {
+    ExecutionResult {
+        // NOTE: like a process without an exit code, an execution that never
+        // ran is reported with a status of -1.
+        status: -1,
+        exit: Exit::Unknown,
+        stdout: String::new(),
+        stderr,
+        oom_killed: false,
+        started_at: None,
+        ended_at: None,
+        id: None,
+    }
+}
+
+/// Determines whether the storage driver of the Docker daemon supports limiting
+/// the size of a container's writable layer.
+///
+/// The `overlay2` driver only supports this when backed by XFS mounted with the
+/// `pquota` option (which cannot be detected here).
+fn supports_storage_opt(info: &SystemInfo) -> bool {
+    match info.driver.as_deref() {
+        Some("btrfs" | "devicemapper" | "windowsfilter" | "zfs") => true,
+        Some("overlay2") => info
+            .driver_status
+            .iter()
+            .flatten()
+            .any(|status| matches!(&status[..], [label, value] if label == "Backing Filesystem" && value == "xfs")),
+        _ => false,
+    }
+}
+
+/// Pulls an image using the Docker client (unless it is already present).
+#[instrument(level = "debug", skip_all, fields(image = image))]
+async fn image_pull(
+    image: &str,
+    client: &Arc<Docker>,
+    credentials: Option<&DockerCredentials>,
+) -> Result<()> {
+    let present = client.inspect_image(image).await;
+    if present.is_ok() {
+        return Ok(());
+    }
+
+    info!(image, "pulling image");
+    client
+        .create_image(
+            Some(CreateImageOptions {
+                from_image: image,
+                ..Default::default()
+            }),
+            None,
+            credentials.cloned(),
+        )
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    Ok(())
 }
 
 /// Starts a container using the Docker client.
-async fn container_start(name: &str, client: &mut Arc<Docker>) {
+#[instrument(level = "debug", skip_all, fields(container = name))]
+async fn container_start(name: &str, client: &Arc<Docker>) -> Result<()> {
     client
         .start_container(name, None::<StartContainerOptions<String>>)
         .await
-        .unwrap();
+}
+
+/// Kills and removes a container using the Docker client.
+#[instrument(level = "debug", skip_all, fields(container = name))]
+async fn container_remove(name: &str, client: &Arc<Docker>) -> Result<()> {
+    client
+        .kill_container(name, None::<KillContainerOptions<String>>)
+        .await?;
+    client.remove_container(name, None).await
 }
 
 /// Puts input files into the container
-async fn insert_input(name: &str, client: &mut Arc<Docker>, input: &Input) {
-    let mut tar = tar::Builder::new(Vec::new());
+///
+/// Directory inputs are uploaded recursively, leaving out their
+/// [exclusions](Input::exclusions).
+#[instrument(level = "debug", skip_all, fields(container = name, path = input.path()))]
+async fn insert_input(
+    name: &str,
+    client: &Arc<Docker>,
+    input: &Input,
+) -> std::result::Result<(), BoxedError> {
+    let tar_path = input.path().trim_start_matches('/');
 
-    let content = input.fetch().await.unwrap();
+    let tar_contents = match input.r#type() {
+        input::Type::File => {
+            let mut tar = tar::Builder::new(Vec::new());
 
-    let tar_path = input.path().trim_start_matches('/');
+            let content = input.fetch().await?;
+
+            // Create a header with the full path
+            let mut header = tar::Header::new_gnu();
+            header.set_path(tar_path)?;
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644); // Set appropriate permissions
+            header.set_cksum();
 
-    // Create a header with the full path
-    let mut header = tar::Header::new_gnu();
-    header.set_path(tar_path).unwrap();
-    header.set_size(content.len() as u64);
-    header.set_mode(0o644); // Set appropriate permissions
-    header.set_cksum();
+            // Append the file to the tar archive
+            tar.append_data(&mut header, tar_path, Cursor::new(content))?;
 
-    // Append the file to the tar archive
-    tar.append_data(&mut header, tar_path, Cursor::new(content))
-        .unwrap();
+            tar.into_inner()?
+        }
+        input::Type::Directory => {
+            let root = directory_root(input)?;
+            let exclusions = input.exclusion_set()?;
+            let tar_path = PathBuf::from(tar_path);
 
-    let tar_contents = tar.into_inner().unwrap();
+            tokio::task::spawn_blocking(move || directory_archive(&root, &tar_path, &exclusions))
+                .await??
+        }
+    };
 
     // Upload to the root of the container
     client
@@ -220,16 +915,333 @@ async fn insert_input(name: &str, client: &mut Arc<Docker>, input: &Input) {
             }),
             tar_contents.into(),
         )
-        .await
-        .unwrap();
+        .await?;
+
+    Ok(())
+}
+
+/// Gets the path on the host of the directory of a directory input.
+///
+/// Only directories on the local filesystem (i.e., `file://` URLs) can be
+/// uploaded into a container.
+fn directory_root(input: &Input) -> std::result::Result<PathBuf, String> {
+    match input.contents() {
+        input::Contents::URL(url) if url.scheme() == "file" => url
+            .to_file_path()
+            .map_err(|_| format!("invalid directory input path: {url}")),
+        input::Contents::URL(url) => Err(format!(
+            "directory inputs must be local `file://` URLs, but `{path}` is sourced from `{url}`",
+            path = input.path()
+        )),
+        input::Contents::Literal(_) => Err(format!(
+            "directory inputs must be local `file://` URLs, but `{path}` is a literal",
+            path = input.path()
+        )),
+    }
+}
+
+/// Checks that every input of a task can be inserted into a container,
+/// returning a description of the first that cannot.
+fn unsupported_input(task: &Task) -> Option<String> {
+    task.inputs()
+        .into_iter()
+        .flatten()
+        .filter(|input| matches!(input.r#type(), input::Type::Directory))
+        .find_map(|input| directory_root(input).err())
+}
+
+/// Checks that every volume of a task can be shared by its executions,
+/// returning a description of the first that cannot.
+///
+/// A size-limited temporary mount is a separate `tmpfs` in each container, so
+/// it is only shared when every execution runs in the same container.
+fn unshareable_volume(volumes: &[Volume], shared: bool, executions: usize) -> Option<String> {
+    if shared || executions < 2 {
+        return None;
+    }
+
+    volumes.iter().find_map(|volume| match volume {
+        Volume::Tmp(tmp) if tmp.size_bytes().is_some() => Some(format!(
+            "the size-limited volume `{path}` cannot be shared by executions that run in \
+             separate containers",
+            path = tmp.container_path()
+        )),
+        _ => None,
+    })
+}
+
+/// Builds a tar archive of a directory, placing its contents at `tar_path`
+/// and leaving out the paths (relative to the directory) that match any of
+/// the exclusions.
+fn directory_archive(
+    root: &Path,
+    tar_path: &Path,
+    exclusions: &RegexSet,
+) -> std::io::Result<Vec<u8>> {
+    let mut tar = tar::Builder::new(Vec::new());
+    tar.append_dir(tar_path, root)?;
+
+    let mut pending = vec![PathBuf::new()];
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(root.join(&directory))? {
+            let entry = entry?;
+            let relative = directory.join(entry.file_name());
+
+            // NOTE: exclusions are always matched against `/`-separated paths.
+            let key = relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if exclusions.is_match(&key) {
+                continue;
+            }
+
+            let archived = tar_path.join(&relative);
+            if entry.file_type()?.is_dir() {
+                tar.append_dir(&archived, entry.path())?;
+                pending.push(relative);
+            } else {
+                tar.append_path_with_name(entry.path(), &archived)?;
+            }
+        }
+    }
+
+    tar.into_inner()
+}
+
+/// Copies an output out of the container to its destination, returning the
+/// size of the output in bytes.
+///
+/// Currently, only `file://` destination URLs are supported.
+#[instrument(level = "debug", skip_all, fields(container = name, path = output.path()))]
+async fn retrieve_output(
+    name: &str,
+    client: &Arc<Docker>,
+    output: &Output,
+) -> std::result::Result<u64, BoxedError> {
+    let url = Url::parse(output.url())?;
+    let destination = match url.scheme() {
+        "file" => url
+            .to_file_path()
+            .map_err(|_| format!("invalid output file path: {url}"))?,
+        scheme => return Err(format!("unsupported output URL scheme: {scheme}").into()),
+    };
+
+    // Docker hands back the contents of the path as a tar archive
+    let archive = client
+        .download_from_container(
+            name,
+            Some(DownloadFromContainerOptions {
+                path: output.path(),
+            }),
+        )
+        .try_fold(Vec::new(), |mut archive, chunk| async move {
+            archive.extend_from_slice(&chunk);
+            Ok(archive)
+        })
+        .await?;
+
+    let mut archive = tar::Archive::new(Cursor::new(archive));
+
+    match output.r#type() {
+        output::Type::File => {
+            let mut entry = archive
+                .entries()?
+                .next()
+                .ok_or_else(|| format!("output `{}` was not found", output.path()))??;
+
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            entry.unpack(&destination)?;
+            Ok(entry.header().size()?)
+        }
+        output::Type::Directory => {
+            fs::create_dir_all(&destination)?;
+            let mut size = 0;
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+
+                // Entries are rooted at the name of the directory itself, so
+                // that component is stripped before unpacking
+                let path = entry.path()?.components().skip(1).collect::<PathBuf>();
+                if path.as_os_str().is_empty() {
+                    continue;
+                }
+
+                if entry.header().entry_type().is_file() {
+                    size += entry.header().size()?;
+                }
+
+                entry.unpack(destination.join(path))?;
+            }
+
+            Ok(size)
+        }
+    }
+}
+
+/// Gets the number of bytes at the end of `bytes` that form the start of a
+/// UTF-8 encoded character whose remaining bytes have yet to arrive.
+fn incomplete_char_len(bytes: &[u8]) -> usize {
+    for (i, byte) in bytes.iter().rev().take(4).enumerate() {
+        let len = match byte {
+            // A continuation byte; keep looking for the leading byte
+            0x80..=0xBF => continue,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return 0,
+        };
+
+        return if i + 1 < len { i + 1 } else { 0 };
+    }
+
+    0
+}
+
+/// Text decoded from a stream of UTF-8 encoded chunks.
+///
+/// Docker splits output at arbitrary byte boundaries, so the bytes of a
+/// character split across chunks are held back until the rest arrive rather
+/// than being decoded as replacement characters.
+#[derive(Debug, Default)]
+struct Utf8Buffer {
+    /// The text decoded so far.
+    text: String,
+    /// The bytes of an incomplete character at the end of the last chunk.
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    /// Decodes a chunk of bytes.
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        let complete = self.pending.len() - incomplete_char_len(&self.pending);
+        self.text
+            .push_str(&String::from_utf8_lossy(&self.pending[..complete]));
+        self.pending.drain(..complete);
+    }
+
+    /// Gets the decoded text, including any trailing incomplete character.
+    fn finish(mut self) -> String {
+        self.text.push_str(&String::from_utf8_lossy(&self.pending));
+        self.text
+    }
+}
+
+/// The standard output and standard error collected from an execution.
+#[derive(Debug, Default)]
+struct Logs {
+    /// The standard output.
+    stdout: Utf8Buffer,
+    /// The standard error.
+    stderr: Utf8Buffer,
+}
+
+impl Logs {
+    /// Collects a log message.
+    fn push(&mut self, log: &LogOutput) {
+        match log {
+            LogOutput::StdOut { message } => self.stdout.push(message),
+            LogOutput::StdErr { message } => self.stderr.push(message),
+            _ => {}
+        }
+    }
+
+    /// Gets the decoded standard output and standard error.
+    fn finish(self) -> (String, String) {
+        (self.stdout.finish(), self.stderr.finish())
+    }
+}
+
+/// Gets how an exec exited from its inspection.
+///
+/// # Notes
+///
+/// Docker reports a process terminated by a signal with an exit code of 128
+/// plus the signal number, so such codes are reported as signals. A process
+/// that genuinely exits with such a code (e.g., `exit 137`) cannot be told
+/// apart.
+///
+/// An exec without an exit code is reported as [`Exit::Unknown`], even if
+/// Docker still considers it to be running.
+fn exec_exit(running: Option<bool>, exit_code: Option<i64>) -> Exit {
+    /// The largest signal number on Linux.
+    const MAX_SIGNAL: i64 = 64;
+
+    match (running, exit_code) {
+        (_, None) => Exit::Unknown,
+        (Some(true), _) => Exit::Running,
+        (_, Some(code)) if (129..=128 + MAX_SIGNAL).contains(&code) => {
+            Exit::Signal((code - 128) as i32)
+        }
+        (_, Some(code)) => Exit::Code(code),
+    }
+}
+
+/// Collects the output of an execution from its log stream.
+///
+/// If a `logs` channel is provided, each chunk of output is also forwarded
+/// through it as it arrives.
+///
+/// # Notes
+///
+/// The output of an exec is not part of the logs of its container, so the
+/// stream cannot be resumed (e.g., with the `since` option of the container
+/// logs) once it fails. The output collected up to the failure is kept.
+async fn collect_logs(
+    mut stream: impl Stream<Item = std::result::Result<LogOutput, Error>> + Unpin,
+    logs: Option<&UnboundedSender<LogOutput>>,
+) -> Logs {
+    let mut output = Logs::default();
+    loop {
+        let log = match stream.next().await {
+            Some(Ok(log)) => log,
+            None => break,
+            Some(Err(e)) => {
+                error!(
+                    error = %e,
+                    "failed to collect the remaining logs of an execution; keeping the partial \
+                     output"
+                );
+                break;
+            }
+        };
+
+        if let Some(logs) = logs {
+            // NOTE: a send error only means that the receiver has hung up, in
+            // which case the caller is no longer interested in the output.
+            let _ = logs.send(log.clone());
+        }
+
+        output.push(&log);
+    }
+
+    output
 }
 
 /// Execute a command in container, returning an ExecutionResult
+///
+/// Returns an error if the daemon fails to create, start, or inspect the
+/// exec.
+///
+/// If a `logs` channel is provided, each chunk of output is also forwarded
+/// through it as it arrives. The execution is considered to have started at
+/// `started_at` (i.e., when its container was created or, in a shared
+/// container, when the previous execution ended) and to have ended once the
+/// command has exited.
+#[instrument(level = "debug", skip_all, fields(container = name, status))]
 async fn container_exec(
     name: &str,
     execution: &Execution,
-    client: &mut Arc<Docker>,
-) -> ExecutionResult {
+    client: &Arc<Docker>,
+    logs: Option<&UnboundedSender<LogOutput>>,
+    started_at: SystemTime,
+) -> std::result::Result<ExecutionResult, BoxedError> {
     let exec_id = client
         .create_exec(
             name,
@@ -237,52 +1249,490 @@ async fn container_exec(
                 attach_stdout: Some(true),
                 attach_stderr: Some(true),
                 cmd: Some(execution.args().into_iter().map(|s| s.as_str()).collect()),
+                // NOTE: a shared container is created with the working
+                // directory of the first execution, so each execution sets its
+                // own.
+                working_dir: execution.workdir().map(String::as_str),
                 ..Default::default()
             },
         )
-        .await
-        .unwrap()
+        .await?
         .id;
 
-    let log_stream = if let StartExecResults::Attached { output, .. } =
-        client.start_exec(&exec_id, None).await.unwrap()
-    {
-        output
-    } else {
-        unreachable!();
+    let started = client.start_exec(&exec_id, None).await?;
+    let log_stream = match started {
+        StartExecResults::Attached { output, .. } => output,
+        StartExecResults::Detached => return Err("the exec was started detached".into()),
     };
 
-    // Process logs
-    let (stdout, stderr) = log_stream
-        .try_fold(
-            (String::with_capacity(1 << 8), String::with_capacity(1 << 8)),
-            |(mut stdout, mut stderr), log| async move {
-                match log {
-                    LogOutput::StdOut { message } => {
-                        stdout.push_str(&String::from_utf8_lossy(&message));
-                    }
-                    LogOutput::StdErr { message } => {
-                        stderr.push_str(&String::from_utf8_lossy(&message));
-                    }
-                    _ => {}
-                }
-                Ok((stdout, stderr))
-            },
-        )
-        .await
-        .unwrap_or_else(|e| {
-            eprintln!("Error collecting logs: {:?}", e);
-            (String::new(), String::new())
-        });
+    let (stdout, stderr) = collect_logs(log_stream, logs).await.finish();
 
-    // Get return code
     // Get the exit code
-    let exec_inspect = client.inspect_exec(&exec_id).await.unwrap();
-    let status = exec_inspect.exit_code.unwrap_or(-1) as u64;
+    let exec_inspect = client.inspect_exec(&exec_id).await?;
+    let exit = exec_exit(exec_inspect.running, exec_inspect.exit_code);
+    let status = exec_inspect.exit_code.unwrap_or(-1);
+    let ended_at = SystemTime::now();
+    Span::current().record("status", status);
 
-    ExecutionResult {
+    // Check whether the out-of-memory killer was invoked within the container
+    let oom_killed = client
+        .inspect_container(name, None::<InspectContainerOptions>)
+        .await
+        .ok()
+        .and_then(|container| container.state)
+        .and_then(|state| state.oom_killed)
+        .unwrap_or(false);
+
+    Ok(ExecutionResult {
         status,
+        exit,
         stdout,
         stderr,
+        oom_killed,
+        started_at: Some(started_at),
+        ended_at: Some(ended_at),
+        id: Some(name.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::task::testing::execution;
+    use crate::engine::task::testing::task;
+
+    #[test]
+    fn name_conflicts_are_detected() {
+        let conflict = Error::DockerResponseServerError {
+            status_code: 409,
+            message: String::from("the container name is already in use"),
+        };
+        assert!(is_name_conflict(&conflict));
+
+        let missing = Error::DockerResponseServerError {
+            status_code: 404,
+            message: String::from("no such image"),
+        };
+        assert!(!is_name_conflict(&missing));
+    }
+
+    /// Creates a [`SystemInfo`] with a storage driver and backing filesystem.
+    fn info(driver: &str, filesystem: &str) -> SystemInfo {
+        SystemInfo {
+            driver: Some(driver.to_string()),
+            driver_status: Some(vec![vec![
+                String::from("Backing Filesystem"),
+                filesystem.to_string(),
+            ]]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn split_characters_are_decoded() {
+        let mut logs = Logs::default();
+        let text = "crème brûlée 🦀";
+        let bytes = text.as_bytes();
+
+        // Split within `è` (two bytes) and within the crab (four bytes)
+        let crab = bytes.len() - 2;
+        for chunk in [&bytes[..3], &bytes[3..crab], &bytes[crab..]] {
+            logs.push(&LogOutput::StdOut {
+                message: chunk.to_vec().into(),
+            });
+        }
+
+        logs.push(&LogOutput::StdErr {
+            message: b"\xF0\x9F".to_vec().into(),
+        });
+
+        let (stdout, stderr) = logs.finish();
+        assert_eq!(stdout, text);
+        assert_eq!(stderr, "\u{FFFD}");
+    }
+
+    #[tokio::test]
+    async fn partial_logs_are_kept_when_the_stream_fails() {
+        let stream = futures::stream::iter([
+            Ok(LogOutput::StdOut {
+                message: b"first\n".to_vec().into(),
+            }),
+            Ok(LogOutput::StdErr {
+                message: b"warning\n".to_vec().into(),
+            }),
+            Err(Error::DockerResponseServerError {
+                status_code: 500,
+                message: String::from("connection reset"),
+            }),
+            Ok(LogOutput::StdOut {
+                message: b"never seen\n".to_vec().into(),
+            }),
+        ]);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (stdout, stderr) = collect_logs(stream, Some(&tx)).await.finish();
+        assert_eq!(stdout, "first\n");
+        assert_eq!(stderr, "warning\n");
+
+        drop(tx);
+        let mut forwarded = 0;
+        while rx.recv().await.is_some() {
+            forwarded += 1;
+        }
+        assert_eq!(forwarded, 2);
+    }
+
+    #[test]
+    fn exec_exits_are_interpreted() {
+        assert_eq!(exec_exit(Some(false), Some(0)), Exit::Code(0));
+        assert_eq!(exec_exit(Some(false), Some(3)), Exit::Code(3));
+        assert_eq!(exec_exit(Some(false), Some(137)), Exit::Signal(9));
+        assert_eq!(exec_exit(Some(false), Some(255)), Exit::Code(255));
+        assert_eq!(exec_exit(Some(true), Some(0)), Exit::Running);
+        assert_eq!(exec_exit(Some(true), None), Exit::Unknown);
+        assert_eq!(exec_exit(None, None), Exit::Unknown);
+    }
+
+    #[test]
+    fn resource_limits_are_checked_against_the_host() {
+        let info = SystemInfo {
+            ncpu: Some(4),
+            mem_total: Some(8 << 30),
+            ..Default::default()
+        };
+
+        let resources = Resources::builder().cpu_cores(4u64).ram_gb(8.0).build();
+        assert_eq!(
+            check_resources(&HostConfig::from(&resources), &info),
+            Ok(())
+        );
+
+        let resources = Resources::builder().cpu_cores(16u64).build();
+        let err = check_resources(&HostConfig::from(&resources), &info).unwrap_err();
+        assert_eq!(
+            err,
+            ResourcesUnavailable {
+                resource: "cpu",
+                requested: 16,
+                available: 4,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "requested 16 cpu but the Docker host only has 4"
+        );
+
+        let resources = Resources::builder().ram_gb(16.0).build();
+        let err = check_resources(&HostConfig::from(&resources), &info).unwrap_err();
+        assert_eq!(err.resource, "memory");
+
+        // Hosts that don't report their capacity aren't checked
+        let resources = Resources::builder().cpu_cores(16u64).build();
+        let info = SystemInfo::default();
+        assert_eq!(
+            check_resources(&HostConfig::from(&resources), &info),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn shutdown_only_matches_containers_of_its_run() {
+        let filters = leaked_filters("bright-blue-fast-fox");
+        assert_eq!(
+            filters["label"],
+            [
+                "crankshaft.managed=true",
+                "crankshaft.run=bright-blue-fast-fox"
+            ]
+        );
+    }
+
+    #[test]
+    fn storage_opt_support() {
+        assert!(supports_storage_opt(&info("overlay2", "xfs")));
+        assert!(supports_storage_opt(&info("btrfs", "btrfs")));
+        assert!(!supports_storage_opt(&info("overlay2", "extfs")));
+        assert!(!supports_storage_opt(&info("vfs", "xfs")));
+        assert!(!supports_storage_opt(&SystemInfo::default()));
+    }
+
+    #[test]
+    fn size_limited_volumes_require_a_shared_container() {
+        let volumes = ["/scratch:size=1m", "/tmp"].map(|s| Volume::from_str(s).unwrap());
+
+        assert_eq!(
+            unshareable_volume(&volumes, false, 2).unwrap(),
+            "the size-limited volume `/scratch` cannot be shared by executions that run in \
+             separate containers"
+        );
+        assert!(unshareable_volume(&volumes, true, 2).is_none());
+        assert!(unshareable_volume(&volumes, false, 1).is_none());
+
+        let volumes = [Volume::from_str("/tmp").unwrap()];
+        assert!(unshareable_volume(&volumes, false, 2).is_none());
+    }
+
+    #[test]
+    fn directory_archives_leave_out_exclusions() {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, contents) in [
+            ("a.txt", "a"),
+            (".git/config", "config"),
+            ("cache/x.tmp", "x"),
+            ("sub/b.txt", "b"),
+            ("sub/y.tmp", "y"),
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        let exclusions = Input::builder()
+            .contents(dir.path().to_path_buf())
+            .path("/inputs/dir")
+            .r#type(input::Type::Directory)
+            .extend_exclusions([".git", "**/*.tmp"])
+            .try_build()
+            .unwrap()
+            .exclusion_set()
+            .unwrap();
+        let archive = directory_archive(dir.path(), Path::new("inputs/dir"), &exclusions).unwrap();
+
+        let paths = tar::Archive::new(Cursor::new(archive))
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect::<std::collections::BTreeSet<_>>();
+        let expected = [
+            "inputs/dir",
+            "inputs/dir/a.txt",
+            "inputs/dir/cache",
+            "inputs/dir/sub",
+            "inputs/dir/sub/b.txt",
+        ]
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn remote_directory_inputs_are_unsupported() {
+        let task = |contents: input::Contents| {
+            Task::builder()
+                .extend_inputs([Input::builder()
+                    .contents(contents)
+                    .path("/inputs/dir")
+                    .r#type(input::Type::Directory)
+                    .try_build()
+                    .unwrap()])
+                .extend_executions([execution(["ls", "/inputs/dir"])])
+                .try_build()
+                .unwrap()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            unsupported_input(&task(dir.path().to_path_buf().into())),
+            None
+        );
+
+        let url = Url::parse("s3://bucket/dir").unwrap();
+        assert_eq!(
+            unsupported_input(&task(input::Contents::URL(url))).unwrap(),
+            "directory inputs must be local `file://` URLs, but `/inputs/dir` is sourced from \
+             `s3://bucket/dir`"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn files_are_owned_by_the_configured_user() {
+        let task = task(["sh", "-c", "touch /tmp/file && stat -c %u:%g /tmp/file"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .with_user("1000:1000")
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(executions[0].stdout, "1000:1000\n");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn invalid_volumes_fail_the_task() {
+        let task = Task::builder()
+            .extend_executions([execution(["true"])])
+            .extend_volumes([String::from("/refs:/data/refs:rx")])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert!(matches!(
+            reply.outcome,
+            Outcome::BackendError(message) if message.starts_with("invalid volume `/refs:/data/refs:rx`")
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn missing_outputs_fail_the_task() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = Task::builder()
+            .extend_executions([execution(["true"])])
+            .extend_outputs([Output::builder()
+                .url(Url::from_file_path(dir.path().join("missing.txt")).unwrap())
+                .path("/outputs/missing.txt")
+                .r#type(output::Type::File)
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert!(matches!(
+            reply.outcome,
+            Outcome::BackendError(message)
+                if message.starts_with("failed to retrieve output `/outputs/missing.txt`")
+        ));
+        assert!(reply.outputs.is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn outputs_are_reported_with_their_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let task = Task::builder()
+            .extend_executions([execution([
+                "sh",
+                "-c",
+                "mkdir -p /outputs/dir && printf hello > /outputs/result.txt && printf abc \
+                     > /outputs/dir/a && printf de > /outputs/dir/b",
+            ])])
+            .extend_outputs([
+                Output::builder()
+                    .url(Url::from_file_path(dir.path().join("result.txt")).unwrap())
+                    .path("/outputs/result.txt")
+                    .r#type(output::Type::File)
+                    .try_build()
+                    .unwrap(),
+                Output::builder()
+                    .url(Url::from_file_path(dir.path().join("dir")).unwrap())
+                    .path("/outputs/dir")
+                    .r#type(output::Type::Directory)
+                    .try_build()
+                    .unwrap(),
+            ])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert_eq!(reply.outcome, Outcome::Completed);
+
+        let outputs = reply.outputs.unwrap();
+        assert_eq!(outputs[0].path, "/outputs/result.txt");
+        assert_eq!(outputs[0].size_bytes, 5);
+        assert_eq!(outputs[1].path, "/outputs/dir");
+        assert_eq!(outputs[1].size_bytes, 5);
+        assert_eq!(fs::read_to_string(dir.path().join("dir/b")).unwrap(), "de");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn executions_past_the_memory_limit_are_oom_killed() {
+        // NOTE: `tail` buffers its input until it sees a newline, so it reads
+        // all of it into memory (well beyond the limit and any swap).
+        let task = Task::builder()
+            .resources(Resources::builder().ram_gb(0.25).build())
+            .extend_executions([execution(["sh", "-c", "head -c 2G /dev/zero | tail"])])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert!(matches!(
+            reply.outcome,
+            Outcome::Failed {
+                execution_index: 0,
+                ..
+            }
+        ));
+
+        let executions = reply.executions.unwrap();
+        assert!(executions[0].oom_killed);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn shared_containers_keep_files_between_executions() {
+        let task = Task::builder()
+            .extend_executions([
+                execution(["sh", "-c", "echo hello > /tmp/shared.txt"]),
+                execution(["ls", "/tmp"]),
+                execution(["cat", "/tmp/shared.txt"]),
+            ])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .with_shared_container(true)
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert_eq!(reply.outcome, Outcome::Completed);
+
+        let executions = reply.executions.unwrap();
+        assert!(executions[1]
+            .stdout
+            .lines()
+            .any(|line| line == "shared.txt"));
+        assert_eq!(executions[2].stdout, "hello\n");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn killed_executions_report_the_signal() {
+        let task = task(["sh", "-c", "kill -9 $$"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        // NOTE: Docker reports a process terminated by a signal with an exit
+        // code of 128 plus the signal number.
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(executions[0].status, 137);
+        assert_eq!(executions[0].exit, Exit::Signal(9));
     }
 }
@@ -0,0 +1,240 @@
+//! Retrying Docker daemon calls that fail for transient reasons.
+
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use bollard::errors::Error;
+use tracing::warn;
+
+/// The default number of times a failed call is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// The default upper bound on the delay between retries.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// A policy for retrying transient Docker daemon errors.
+///
+/// The delay between attempts starts at the initial backoff and doubles after
+/// each failed attempt, up to the maximum backoff.
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    /// The maximum number of retries after the first attempt.
+    max_retries: u32,
+
+    /// The delay before the first retry.
+    initial_backoff: Duration,
+
+    /// The upper bound on the delay between retries.
+    max_backoff: Duration,
+}
+
+impl Policy {
+    /// Creates a new retry [`Policy`].
+    pub fn new(max_retries: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// Creates a [`Policy`] that never retries.
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// The maximum number of retries after the first attempt.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// The delay before the first retry.
+    pub fn initial_backoff(&self) -> Duration {
+        self.initial_backoff
+    }
+
+    /// The upper bound on the delay between retries.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Gets the delay to wait before the provided retry (starting at zero).
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_RETRIES,
+            DEFAULT_INITIAL_BACKOFF,
+            DEFAULT_MAX_BACKOFF,
+        )
+    }
+}
+
+/// Returns whether an error from the Docker daemon is transient (i.e., the same
+/// call may succeed if it is tried again).
+///
+/// Connection failures, timeouts, and server errors (`5xx`) are considered
+/// transient. Deterministic failures, such as a missing image or an invalid
+/// container configuration, are not.
+pub fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::DockerResponseServerError { status_code, .. } => *status_code >= 500,
+        Error::RequestTimeoutError
+        | Error::HyperResponseError { .. }
+        | Error::HyperLegacyError { .. } => true,
+        Error::IOError { err } => matches!(
+            err.kind(),
+            ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Runs a fallible Docker call, retrying it according to the [`Policy`] for as
+/// long as it fails with a [transient](is_transient) error.
+pub async fn retry<T, F, Fut>(policy: &Policy, mut call: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut retries = 0;
+
+    loop {
+        let result = call().await;
+
+        match result {
+            Err(e) if retries < policy.max_retries && is_transient(&e) => {
+                let backoff = policy.backoff(retries);
+                warn!(error = %e, retry = retries + 1, ?backoff, "retrying docker call");
+
+                tokio::time::sleep(backoff).await;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    /// A stand-in for a Docker client that fails a fixed number of times with
+    /// the provided error before succeeding.
+    struct FlakyClient {
+        /// The number of calls made so far.
+        calls: AtomicU32,
+
+        /// The number of calls that fail before one succeeds.
+        failures: u32,
+
+        /// Creates the error returned by failing calls.
+        error: fn() -> Error,
+    }
+
+    impl FlakyClient {
+        /// Creates a new [`FlakyClient`].
+        fn new(failures: u32, error: fn() -> Error) -> Self {
+            Self {
+                calls: AtomicU32::new(0),
+                failures,
+                error,
+            }
+        }
+
+        /// Simulates a call to the Docker daemon.
+        async fn create_container(&self) -> Result<&'static str, Error> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+
+            if call < self.failures {
+                Err((self.error)())
+            } else {
+                Ok("created")
+            }
+        }
+    }
+
+    /// A policy that retries without waiting.
+    fn immediate(max_retries: u32) -> Policy {
+        Policy::new(max_retries, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// A transient error.
+    fn connection_reset() -> Error {
+        Error::IOError {
+            err: std::io::Error::from(ErrorKind::ConnectionReset),
+        }
+    }
+
+    /// A deterministic error.
+    fn image_not_found() -> Error {
+        Error::DockerResponseServerError {
+            status_code: 404,
+            message: String::from("No such image: ubuntu"),
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let client = FlakyClient::new(2, connection_reset);
+        let result = retry(&immediate(3), || client.create_container()).await;
+
+        assert_eq!(result.unwrap(), "created");
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn deterministic_failures_are_not_retried() {
+        let client = FlakyClient::new(2, image_not_found);
+        let result = retry(&immediate(3), || client.create_container()).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_are_bounded() {
+        let client = FlakyClient::new(5, connection_reset);
+        let result = retry(&immediate(2), || client.create_container()).await;
+
+        assert!(result.is_err());
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = Policy::new(5, Duration::from_millis(100), Duration::from_millis(350));
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn server_errors_are_transient() {
+        assert!(is_transient(&Error::DockerResponseServerError {
+            status_code: 503,
+            message: String::from("service unavailable"),
+        }));
+        assert!(!is_transient(&image_not_found()));
+    }
+}
@@ -1,38 +1,207 @@
 //! Create local temporary folders to mount in docker containers
 
+use std::io::Error;
+use std::io::ErrorKind;
 use std::str::FromStr;
 
 use bollard::models::Mount;
+use bollard::models::MountTmpfsOptions;
 use bollard::models::MountTypeEnum;
 use tempfile::TempDir;
 
+/// The option marking a temporary mount as read-only.
+const READ_ONLY: &str = "ro";
+
+/// The option marking a temporary mount as writable.
+const READ_WRITE: &str = "rw";
+
+/// The prefix of the option limiting the size of a temporary mount.
+const SIZE: &str = "size=";
+
 /// Mount a local TempDir to a mount shared amongst the executors in a tast
+///
+/// Temporary mounts are specified as `<container path>[:<option>,...]`, where
+/// the options are any of:
+///
+/// * `ro` or `rw` to make the mount read-only or writable (the default).
+/// * `size=<size>` to limit the size of the mount, where the size is a number
+///   of bytes optionally suffixed with `k`, `m`, or `g` (powers of 1024).
+///
+/// A size-limited mount is backed by a `tmpfs` within the container rather
+/// than a temporary directory on the host, as Docker cannot limit the size of
+/// a bind mount. Each container gets its own `tmpfs`, so a size-limited mount
+/// is only shared amongst executions that run in a
+/// [shared container](super::DockerBackend::with_shared_container); the
+/// backend rejects one for a task whose executions each run in their own
+/// container.
 pub struct TmpMount {
-    /// local temp directory mounted as a volume
-    local_path: TempDir,
+    /// local temp directory mounted as a volume (if the mount is not backed by
+    /// a `tmpfs`)
+    local_path: Option<TempDir>,
 
     /// path used to mount in the container
     container_path: String,
+
+    /// the size limit of the mount in bytes (if any)
+    size_bytes: Option<i64>,
+
+    /// whether the mount is read-only
+    read_only: bool,
+}
+
+impl TmpMount {
+    /// Gets the path of the mount within the container.
+    pub fn container_path(&self) -> &str {
+        &self.container_path
+    }
+
+    /// Gets the size limit of the mount in bytes (if any).
+    pub fn size_bytes(&self) -> Option<i64> {
+        self.size_bytes
+    }
+
+    /// Gets whether the mount is read-only.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+/// Parses a size (e.g., `512m`) into bytes.
+fn parse_size(s: &str) -> Option<i64> {
+    let (number, multiplier) = match s.char_indices().last()? {
+        (i, 'k' | 'K') => (&s[..i], 1024),
+        (i, 'm' | 'M') => (&s[..i], 1024 * 1024),
+        (i, 'g' | 'G') => (&s[..i], 1024 * 1024 * 1024),
+        _ => (s, 1),
+    };
+
+    number
+        .parse::<i64>()
+        .ok()
+        .filter(|number| *number > 0)?
+        .checked_mul(multiplier)
 }
 
 impl FromStr for TmpMount {
-    type Err = std::io::Error;
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (container_path, options) = s.split_once(':').unwrap_or((s, ""));
+
+        if container_path.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid volume `{s}`: the container path must not be empty"),
+            ));
+        }
+
+        let mut size_bytes = None;
+        let mut read_only = false;
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            match option {
+                READ_ONLY => read_only = true,
+                READ_WRITE => read_only = false,
+                _ => match option.strip_prefix(SIZE) {
+                    Some(size) => {
+                        size_bytes = Some(parse_size(size).ok_or_else(|| {
+                            Error::new(
+                                ErrorKind::InvalidInput,
+                                format!("invalid volume `{s}`: invalid size `{size}`"),
+                            )
+                        })?);
+                    }
+                    None => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("invalid volume `{s}`: unknown option `{option}`"),
+                        ))
+                    }
+                },
+            }
+        }
+
         Ok(TmpMount {
-            local_path: TempDir::new()?,
-            container_path: s.to_string(),
+            local_path: match size_bytes {
+                Some(_) => None,
+                None => Some(TempDir::new()?),
+            },
+            container_path: container_path.to_string(),
+            size_bytes,
+            read_only,
         })
     }
 }
 
 impl From<&TmpMount> for Mount {
     fn from(val: &TmpMount) -> Self {
-        Mount {
-            target: Some(val.container_path.clone()),
-            source: Some(val.local_path.path().to_str().unwrap().to_string()),
-            typ: Some(MountTypeEnum::BIND),
-            ..Default::default()
+        match &val.local_path {
+            Some(local_path) => Mount {
+                target: Some(val.container_path.clone()),
+                source: Some(local_path.path().to_str().unwrap().to_string()),
+                typ: Some(MountTypeEnum::BIND),
+                read_only: Some(val.read_only),
+                ..Default::default()
+            },
+            None => Mount {
+                target: Some(val.container_path.clone()),
+                typ: Some(MountTypeEnum::TMPFS),
+                read_only: Some(val.read_only),
+                tmpfs_options: Some(MountTmpfsOptions {
+                    size_bytes: val.size_bytes,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::service::runner::backend::docker::DockerBackend;
+    use crate::engine::service::runner::backend::Backend;
+    use crate::engine::task::testing::execution;
+    use crate::engine::Task;
+
+    #[test]
+    fn sizes_are_parsed_in_powers_of_1024() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("2k"), Some(2 * 1024));
+        assert_eq!(parse_size("64M"), Some(64 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Some(1024 * 1024 * 1024));
+
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("0"), None);
+        assert_eq!(parse_size("-1k"), None);
+        assert_eq!(parse_size("1t"), None);
+        assert_eq!(parse_size("9223372036854775807g"), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn writes_past_the_size_limit_fail() {
+        let task = Task::builder()
+            .extend_executions([execution([
+                "dd",
+                "if=/dev/zero",
+                "of=/scratch/big",
+                "bs=1M",
+                "count=2",
+            ])])
+            .extend_volumes([String::from("/scratch:size=1m")])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_ne!(executions[0].status, 0);
+        assert!(executions[0].stderr.contains("No space left on device"));
+    }
+}
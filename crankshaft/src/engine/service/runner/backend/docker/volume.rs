@@ -0,0 +1,187 @@
+//! Volumes that are mounted in docker containers.
+
+use std::io::Error;
+use std::io::ErrorKind;
+use std::str::FromStr;
+
+use bollard::models::Mount;
+use bollard::models::MountTypeEnum;
+
+use crate::engine::service::runner::backend::docker::tmp_mount::TmpMount;
+
+/// The suffix marking a host mount as read-only.
+const READ_ONLY: &str = "ro";
+
+/// The suffix marking a host mount as writable.
+const READ_WRITE: &str = "rw";
+
+/// A volume shared amongst the executions in a task.
+///
+/// Volumes are specified as strings in one of two forms:
+///
+/// * `<container path>[:<option>,...]` creates a temporary mount at the
+///   container path that is discarded afterwards. The options can limit its
+///   size or make it read-only (see [`TmpMount`]).
+/// * `<host path>:<container path>[:ro|:rw]` bind mounts an existing host path
+///   at the container path. The mount is writable unless it is suffixed with
+///   `:ro`.
+pub enum Volume {
+    /// A temporary directory that is discarded once the task completes.
+    Tmp(TmpMount),
+
+    /// A path on the host.
+    Host {
+        /// The path on the host.
+        source: String,
+
+        /// The path at which the host path is mounted in the container.
+        target: String,
+
+        /// Whether the mount is read-only.
+        read_only: bool,
+    },
+}
+
+impl FromStr for Volume {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+
+        let (source, target, read_only) = match parts[..] {
+            [_] => return TmpMount::from_str(s).map(Volume::Tmp),
+            // NOTE: container paths are always absolute, so a second part that
+            // is not a path holds the options of a temporary mount.
+            [_, options] if !options.starts_with('/') => {
+                return TmpMount::from_str(s).map(Volume::Tmp)
+            }
+            [source, target] => (source, target, false),
+            [source, target, READ_ONLY] => (source, target, true),
+            [source, target, READ_WRITE] => (source, target, false),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid volume `{s}`: expected `host:container[:ro|:rw]`"),
+                ))
+            }
+        };
+
+        if source.is_empty() || target.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid volume `{s}`: the host and container paths must not be empty"),
+            ));
+        }
+
+        Ok(Volume::Host {
+            source: source.to_string(),
+            target: target.to_string(),
+            read_only,
+        })
+    }
+}
+
+impl From<&Volume> for Mount {
+    fn from(val: &Volume) -> Self {
+        match val {
+            Volume::Tmp(tmp) => tmp.into(),
+            Volume::Host {
+                source,
+                target,
+                read_only,
+            } => Mount {
+                target: Some(target.clone()),
+                source: Some(source.clone()),
+                typ: Some(MountTypeEnum::BIND),
+                read_only: Some(*read_only),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::engine::service::runner::backend::docker::DockerBackend;
+    use crate::engine::service::runner::backend::Backend;
+    use crate::engine::task::testing::execution;
+    use crate::engine::Task;
+
+    #[test]
+    fn plain_paths_are_tmp_mounts() {
+        let volume = "/volA".parse::<Volume>().unwrap();
+        assert!(matches!(volume, Volume::Tmp(_)));
+
+        let mount = Mount::from(&volume);
+        assert_eq!(mount.target.as_deref(), Some("/volA"));
+        assert_eq!(mount.typ, Some(MountTypeEnum::BIND));
+    }
+
+    #[test]
+    fn tmp_mounts_accept_options() {
+        let Volume::Tmp(tmp) = "/scratch:size=64m,ro".parse::<Volume>().unwrap() else {
+            panic!("expected a tmp mount");
+        };
+        assert_eq!(tmp.size_bytes(), Some(64 * 1024 * 1024));
+        assert!(tmp.read_only());
+
+        let mount = Mount::from(&tmp);
+        assert_eq!(mount.target.as_deref(), Some("/scratch"));
+        assert_eq!(mount.typ, Some(MountTypeEnum::TMPFS));
+        assert_eq!(mount.read_only, Some(true));
+        assert_eq!(
+            mount.tmpfs_options.unwrap().size_bytes,
+            Some(64 * 1024 * 1024)
+        );
+
+        assert!("/scratch:size=lots".parse::<Volume>().is_err());
+        assert!("/scratch:size=0".parse::<Volume>().is_err());
+        assert!("/scratch:noexec".parse::<Volume>().is_err());
+    }
+
+    #[test]
+    fn host_paths_are_bind_mounts() {
+        let mount = Mount::from(&"/refs:/data/refs:ro".parse::<Volume>().unwrap());
+        assert_eq!(mount.source.as_deref(), Some("/refs"));
+        assert_eq!(mount.target.as_deref(), Some("/data/refs"));
+        assert_eq!(mount.read_only, Some(true));
+
+        let mount = Mount::from(&"/scratch:/scratch".parse::<Volume>().unwrap());
+        assert_eq!(mount.read_only, Some(false));
+    }
+
+    #[test]
+    fn invalid_volumes_are_rejected() {
+        assert!("/refs:/data/refs:rx".parse::<Volume>().is_err());
+        assert!("/a:/b:ro:extra".parse::<Volume>().is_err());
+        assert!(":/data".parse::<Volume>().is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires a Docker daemon"]
+    async fn host_paths_are_readable_in_the_container() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("genome.fa"), ">chr1").unwrap();
+
+        let task = Task::builder()
+            .extend_executions([execution(["cat", "/refs/genome.fa"])])
+            .extend_volumes([format!("{}:/refs:ro", dir.path().display())])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        DockerBackend::try_new(true)
+            .unwrap()
+            .run(String::from("docker"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(executions[0].status, 0);
+        assert_eq!(executions[0].stdout, ">chr1");
+    }
+}
@@ -1,22 +1,48 @@
 //! Generic backend implementation
 
-use std::{collections::HashMap, process::Command, sync::Arc};
+use std::{collections::HashMap, process::Command, sync::Arc, time::SystemTime};
 
 use async_trait::async_trait;
+use futures::future::BoxFuture;
 use futures::FutureExt;
 use nonempty::NonEmpty;
 use regex;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
+use tracing::info_span;
+use tracing::Instrument as _;
 
+use crate::engine::service::runner::backend::config::expand_environment;
 use crate::engine::service::runner::backend::config::substitute_placeholders;
 use crate::engine::service::runner::backend::config::BackendType;
 use crate::engine::service::runner::backend::Backend;
 use crate::engine::service::runner::backend::Config;
 use crate::engine::service::runner::backend::ExecutionResult;
+use crate::engine::service::runner::backend::Exit;
+use crate::engine::service::runner::backend::LogOutput;
+use crate::engine::service::runner::backend::Outcome;
 use crate::engine::service::runner::backend::Reply;
+use crate::engine::service::runner::backend::Result as BackendResult;
+use crate::engine::task::Execution;
+use crate::engine::task::Resources;
 use crate::engine::Task;
 
 /// A generic backend.
+///
+/// Alongside any configured runtime attributes, the following substitutions
+/// are made available to the command templates for each execution:
+///
+/// * `~{script}`—the arguments of the execution joined into a command (each
+///   argument is shell-quoted as needed).
+/// * `~{cwd}`—the working directory of the execution (if one is set). The
+///   submit command itself is also run from this directory.
+/// * `~{cpu}` and `~{memory_mb}`—the requested resources (if any).
+/// * `~{job_id}`—the job id extracted from the output of the submit command
+///   (monitor and kill commands only).
+///
+/// Placeholders are delimited by `~{` and `}` by default, but the delimiters
+/// can be changed (e.g., to `%{` and `}`) to avoid escaping them in templates
+/// that embed shell parameter expansions or WDL.
 #[derive(Debug)]
 pub struct GenericBackend {
     /// All runtime attributes
@@ -31,21 +57,183 @@ pub struct GenericBackend {
     ///
     /// This is used to extract the job id from the STDOUT of the submit command.
     /// It should have exactly one capture group.
+    ///
+    /// Without a regex, the submit command is expected to block until the job
+    /// has finished (and the job is never monitored).
     pub job_id_regex: Option<String>,
     /// monitor command for checking alive-ness
+    ///
+    /// Without a monitor command, the job is considered finished as soon as
+    /// the submit command exits.
     pub monitor: Option<String>,
     /// frequency to monitor the job in seconds
     pub monitor_frequency: Option<u32>,
     /// kill command for killing a job
     pub kill: Option<String>,
+    /// whether to expand environment variables and the home directory in the
+    /// command strings
+    ///
+    /// Environment variables are expanded before placeholders are substituted,
+    /// so the substituted values (e.g., `~{script}`) are never expanded.
+    pub expand_env: bool,
+    /// the delimiter that starts a placeholder in the command strings
+    pub left_placeholder: String,
+    /// the delimiter that ends a placeholder in the command strings
+    pub right_placeholder: String,
+    /// command for checking whether the backend is able to accept jobs
+    ///
+    /// The backend is healthy if the command exits successfully; without a
+    /// command, the backend is always considered healthy.
+    pub healthcheck: Option<String>,
+    /// whether to only render the submit command of each execution
+    ///
+    /// When set, nothing is submitted; instead, each execution succeeds with
+    /// its rendered submit command (see
+    /// [`render_submit()`](GenericBackend::render_submit)) as its standard
+    /// output.
+    pub dry_run: bool,
 }
 
 impl GenericBackend {
     /// Generates a process result from an incoming task
+    ///
+    /// If a `cwd` substitution is present, the submit command is run from that
+    /// directory.
+    ///
+    /// An error is returned (and nothing is run) if the submit or monitor
+    /// command references a placeholder that has no substitution. An error is
+    /// also returned if the job id cannot be found in the output of the submit
+    /// command.
+    ///
+    /// In a [dry run](GenericBackend::dry_run), nothing is run and the result
+    /// succeeds with the rendered submit command as its standard output.
     pub async fn process_command(
         &self,
         substitutions: &mut HashMap<String, String>,
-    ) -> Option<ExecutionResult> {
+    ) -> BackendResult<ExecutionResult> {
+        let submit_command = self.render(substitutions)?;
+        if self.dry_run {
+            return Ok(ExecutionResult {
+                status: 0,
+                exit: Exit::Code(0),
+                stdout: submit_command,
+                stderr: String::new(),
+                oom_killed: false,
+                started_at: None,
+                ended_at: None,
+                id: None,
+            });
+        }
+
+        let mut submit = Command::new("sh");
+        submit.arg("-c").arg(submit_command);
+
+        if let Some(cwd) = substitutions.get("cwd") {
+            submit.current_dir(cwd);
+        }
+
+        let started_at = SystemTime::now();
+        let submit_output = submit.output()?;
+        let submit_stdout = String::from_utf8(submit_output.stdout)?;
+
+        let job_id = match &self.job_id_regex {
+            Some(job_id_regex) => {
+                let job_id = regex::Regex::new(job_id_regex)?
+                    .captures(&submit_stdout)
+                    .and_then(|captures| captures.get(1))
+                    .ok_or("the job id was not found in the output of the submit command")?
+                    .as_str()
+                    .to_string();
+                substitutions.insert("job_id".to_string(), job_id.clone());
+                Some(job_id)
+            }
+            None => None,
+        };
+
+        if let Some(monitor) = &self.monitor {
+            let monitor_command = self.substitute(monitor, substitutions)?;
+
+            // loop while job is running.
+            // monitor_command should return a non-zero exit code when the job is done
+            loop {
+                let monitor_output = Command::new("sh")
+                    .arg("-c")
+                    .arg(monitor_command.clone())
+                    .output()?;
+
+                if monitor_output
+                    .status
+                    .code()
+                    .ok_or("monitor command was terminated by a signal")?
+                    != 0
+                {
+                    break;
+                }
+                // sleep for monitor_frequency seconds
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    self.monitor_frequency.unwrap_or(5).into(),
+                ))
+                .await;
+            }
+        }
+
+        // TODO: collect job output. In meantime, just return the status code
+        // and the stdout/stderr of the submit command
+        let status: i64 = submit_output
+            .status
+            .code()
+            .ok_or("submit command was terminated by a signal")?
+            .into();
+        Ok(ExecutionResult {
+            status,
+            exit: Exit::Code(status),
+            stdout: submit_stdout,
+            stderr: String::from_utf8(submit_output.stderr)?,
+            oom_killed: false,
+            started_at: Some(started_at),
+            ended_at: Some(SystemTime::now()),
+            id: job_id,
+        })
+    }
+
+    /// Renders the submit command for each execution of a task.
+    ///
+    /// This performs the same placeholder substitution as running the task
+    /// would, but does not run anything. Note that the `~{job_id}` placeholder
+    /// is not available, as it is only known once a job is submitted.
+    ///
+    /// An error is returned if the submit command references a placeholder
+    /// that has no substitution.
+    pub fn render_submit(&self, task: &Task) -> BackendResult<Vec<String>> {
+        task.executions()
+            .map(|execution| self.render(&mut self.substitutions(task, execution)))
+            .collect()
+    }
+
+    /// Gets the substitutions for an execution within a task.
+    fn substitutions(&self, task: &Task, execution: &Execution) -> HashMap<String, String> {
+        let mut substitutions = self.runtime_attributes.clone().unwrap_or_default();
+        substitutions.insert("script".to_string(), script(execution.args()));
+
+        if let Some(cwd) = execution.workdir() {
+            substitutions.insert("cwd".to_string(), cwd.to_string());
+        }
+
+        if let Some(gb) = task.resources_for(execution).and_then(Resources::ram_gb) {
+            substitutions.insert(
+                "memory_mb".to_string(),
+                ((gb * 1000f64) as usize).to_string(),
+            );
+        }
+
+        substitutions
+    }
+
+    /// Renders the submit command from a set of substitutions.
+    ///
+    /// The default resources are first inserted into the substitutions (unless
+    /// they are already present).
+    fn render(&self, substitutions: &mut HashMap<String, String>) -> BackendResult<String> {
         if let Some(cpu) = self.default_cpu {
             substitutions
                 .entry("cpu".to_string())
@@ -57,53 +245,64 @@ impl GenericBackend {
                 .or_insert(ram.to_string());
         }
 
-        let submit_command = substitute_placeholders(&self.submit, substitutions);
-        let submit_output = Command::new("sh")
-            .arg("-c")
-            .arg(submit_command)
-            .output()
-            .expect("Failed to run command");
-
-        let job_id_regex_str = self.job_id_regex.clone().unwrap();
-        let submit_stdout = String::from_utf8(submit_output.stdout).ok().unwrap();
-        let job_id = regex::Regex::new(job_id_regex_str.as_str())
-            .ok()
-            .unwrap()
-            .captures(&submit_stdout)
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str();
-        substitutions.insert("job_id".to_string(), job_id.to_string());
+        self.substitute(&self.submit, substitutions)
+    }
 
-        let monitor_command =
-            substitute_placeholders(&self.monitor.clone().unwrap(), substitutions);
+    /// Substitutes the placeholders in a command string.
+    ///
+    /// If enabled, environment variables are expanded first. An error listing
+    /// the unresolved placeholders is returned if the command references any
+    /// placeholder that has no substitution.
+    fn substitute(
+        &self,
+        command: &str,
+        substitutions: &HashMap<String, String>,
+    ) -> BackendResult<String> {
+        let (left, right) = (&self.left_placeholder, &self.right_placeholder);
 
-        // loop while job is running.
-        // monitor_command should return a non-zero exit code when the job is done
-        loop {
-            let monitor_output = Command::new("sh")
-                .arg("-c")
-                .arg(monitor_command.clone())
-                .output()
-                .expect("Failed to run command");
+        let command = if self.expand_env {
+            expand_environment(command, left, right)
+        } else {
+            command.to_string()
+        };
 
-            if monitor_output.status.code()? != 0 {
-                break;
-            }
-            // sleep for monitor_frequency seconds
-            tokio::time::sleep(std::time::Duration::from_secs(
-                self.monitor_frequency.unwrap_or(5).into(),
-            ))
-            .await;
+        // NOTE: the placeholders are checked before substitution so that
+        // substituted values (e.g., a script that itself contains the
+        // delimiters) are never mistaken for unresolved placeholders.
+        let unresolved = self
+            .placeholders(&command)
+            .filter(|key| !substitutions.contains_key(*key))
+            .map(|key| format!("`{left}{key}{right}`"))
+            .collect::<Vec<_>>();
+
+        if !unresolved.is_empty() {
+            return Err(format!(
+                "unresolved placeholders in command `{command}`: {unresolved}",
+                command = command.trim(),
+                unresolved = unresolved.join(", ")
+            )
+            .into());
         }
 
-        // TODO: collect job output. In meantime, just return the status code
-        // and the stdout/stderr of the submit command
-        Some(ExecutionResult {
-            status: submit_output.status.code()? as u64,
-            stdout: submit_stdout,
-            stderr: String::from_utf8(submit_output.stderr).ok()?,
+        Ok(substitute_placeholders(
+            &command,
+            substitutions,
+            left,
+            right,
+        ))
+    }
+
+    /// Gets the keys of the placeholders referenced by a command string.
+    fn placeholders<'a>(&'a self, command: &'a str) -> impl Iterator<Item = &'a str> {
+        let (left, right) = (&self.left_placeholder, &self.right_placeholder);
+        let mut rest = command;
+
+        std::iter::from_fn(move || {
+            let start = rest.find(left.as_str())? + left.len();
+            let end = rest[start..].find(right.as_str())?;
+            let key = &rest[start..start + end];
+            rest = &rest[start + end + right.len()..];
+            Some(key)
         })
     }
 
@@ -129,6 +328,11 @@ impl TryFrom<Config> for GenericBackend {
                 monitor: generic_backend.monitor,
                 monitor_frequency: generic_backend.monitor_frequency,
                 kill: generic_backend.kill,
+                healthcheck: generic_backend.healthcheck,
+                expand_env: generic_backend.expand_env,
+                left_placeholder: generic_backend.left_placeholder,
+                right_placeholder: generic_backend.right_placeholder,
+                dry_run: false,
             })
         } else {
             Err(())
@@ -155,7 +359,7 @@ impl Runner {
 #[async_trait]
 impl Backend for Runner {
     fn default_name(&self) -> &'static str {
-        unimplemented!("you must provide a backend name for a generic runner!")
+        "generic"
     }
 
     fn run(
@@ -163,40 +367,31 @@ impl Backend for Runner {
         name: String,
         task: Task,
         cb: Sender<super::Reply>,
+        // NOTE: the output of a generic backend is only available once the
+        // job has completed, so it is never streamed.
+        _: Option<UnboundedSender<LogOutput>>,
     ) -> futures::future::BoxFuture<'static, ()> {
         let client = self.client.clone();
+        let span = info_span!("run", backend = %name, task = task.name());
 
         async move {
             let mut results: Option<NonEmpty<ExecutionResult>> = None;
             for exec in task.executions() {
-                let mut substitutions = match &client.runtime_attributes {
-                    Some(attributes) => attributes.clone(),
-                    None => HashMap::new(),
-                };
-
-                let command = exec
-                    .args()
-                    .into_iter()
-                    .map(|cmd| cmd.to_string())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-
-                substitutions.insert("script".to_string(), command);
-
-                if let Some(cwd) = exec.workdir() {
-                    substitutions.insert("cwd".to_string(), cwd.to_string());
-                }
-
-                if let Some(resources) = task.resources() {
-                    if let Some(gb) = resources.ram_gb() {
-                        substitutions.insert(
-                            "memory_mb".to_string(),
-                            ((gb * 1000f64) as usize).to_string(),
-                        );
-                    }
-                }
+                let mut substitutions = client.substitutions(&task, exec);
+                let result = client.process_command(&mut substitutions).await;
 
-                let execution_result = client.process_command(&mut substitutions).await.unwrap();
+                let execution_result = result.unwrap_or_else(|e| ExecutionResult {
+                    // NOTE: like a process without an exit code, a command that
+                    // could not be run is reported with a status of -1.
+                    status: -1,
+                    exit: Exit::Unknown,
+                    stdout: String::new(),
+                    stderr: e.to_string(),
+                    oom_killed: false,
+                    started_at: None,
+                    ended_at: None,
+                    id: None,
+                });
 
                 results = match results {
                     Some(mut results) => {
@@ -207,11 +402,318 @@ impl Backend for Runner {
                 }
             }
 
+            let results = results.expect("at least one execution to be run");
+
             let _ = cb.send(Reply {
                 backend: name,
-                executions: Some(results.expect("at least one execution to be run")),
+                outcome: Outcome::from_executions(&results),
+                executions: Some(results),
+                outputs: None,
+                attempts: 1,
             });
         }
+        .instrument(span)
         .boxed()
     }
+
+    fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        let client = self.client.clone();
+
+        async move {
+            let Some(healthcheck) = &client.healthcheck else {
+                return true;
+            };
+
+            let command = if client.expand_env {
+                expand_environment(
+                    healthcheck,
+                    &client.left_placeholder,
+                    &client.right_placeholder,
+                )
+            } else {
+                healthcheck.clone()
+            };
+
+            tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .status()
+                .await
+                .map(|status| status.success())
+                .unwrap_or(false)
+        }
+        .boxed()
+    }
+}
+
+/// Joins the arguments of an execution into a command, quoting each argument
+/// so that it is passed to the program as-is by the shell.
+fn script<'a>(args: impl IntoIterator<Item = &'a String>) -> String {
+    shlex::try_join(args.into_iter().map(String::as_str))
+        .expect("arguments should not contain nul bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::engine::service::runner::backend::config::DEFAULT_LEFT_PLACEHOLDER;
+    use crate::engine::service::runner::backend::config::DEFAULT_RIGHT_PLACEHOLDER;
+    use crate::engine::task::testing;
+
+    #[test]
+    fn script_quotes_arguments() {
+        let args = [
+            String::from("echo"),
+            String::from("hello world"),
+            String::from("it's"),
+            String::from("$HOME"),
+        ];
+
+        assert_eq!(script(&args), r#"echo 'hello world' "it's" '$HOME'"#);
+    }
+
+    /// Creates a backend that submits jobs with an LSF-like command.
+    fn lsf(dry_run: bool) -> GenericBackend {
+        GenericBackend {
+            runtime_attributes: Some(HashMap::from([(
+                String::from("queue"),
+                String::from("compbio"),
+            )])),
+            default_cpu: Some(1),
+            default_ram_mb: None,
+            submit: String::from("bsub -q ~{queue} -n ~{cpu} -cwd ~{cwd} ~{script}"),
+            job_id_regex: None,
+            monitor: None,
+            monitor_frequency: None,
+            kill: None,
+            healthcheck: None,
+            expand_env: false,
+            left_placeholder: String::from(DEFAULT_LEFT_PLACEHOLDER),
+            right_placeholder: String::from(DEFAULT_RIGHT_PLACEHOLDER),
+            dry_run,
+        }
+    }
+
+    /// Creates a task with a single execution in a working directory.
+    fn task() -> Task {
+        Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["echo", "hello world"])
+                .working_directory("/data")
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn render_submit_substitutes_placeholders() {
+        assert_eq!(
+            lsf(false).render_submit(&task()).unwrap(),
+            ["bsub -q compbio -n 1 -cwd /data echo 'hello world'"]
+        );
+    }
+
+    #[test]
+    fn environment_variables_are_expanded() {
+        let path = std::env::var("PATH").unwrap();
+        let backend = GenericBackend {
+            submit: String::from("bsub -P ${PATH}x ~{script}"),
+            expand_env: true,
+            ..lsf(false)
+        };
+
+        let task = testing::task(["echo", "$PATH"]);
+
+        // NOTE: the substituted script is never expanded.
+        assert_eq!(
+            backend.render_submit(&task).unwrap(),
+            [format!("bsub -P {path}x echo '$PATH'")]
+        );
+    }
+
+    #[test]
+    fn executions_override_the_task_memory() {
+        let backend = GenericBackend {
+            submit: String::from("bsub -M ~{memory_mb} ~{script}"),
+            ..lsf(false)
+        };
+
+        let task = Task::builder()
+            .extend_executions([
+                testing::execution(["light"]),
+                Execution::builder()
+                    .image("ubuntu")
+                    .args(["heavy"])
+                    .resources(Resources::builder().ram_gb(16.0).build())
+                    .try_build()
+                    .unwrap(),
+            ])
+            .resources(Resources::builder().ram_gb(2.0).build())
+            .try_build()
+            .unwrap();
+
+        assert_eq!(
+            backend.render_submit(&task).unwrap(),
+            ["bsub -M 2000 light", "bsub -M 16000 heavy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn healthcheck_runs_the_configured_command() {
+        assert!(lsf(false).to_runner().healthcheck().await);
+
+        let healthy = GenericBackend {
+            healthcheck: Some(String::from("exit 0")),
+            ..lsf(false)
+        };
+        assert!(healthy.to_runner().healthcheck().await);
+
+        let unhealthy = GenericBackend {
+            healthcheck: Some(String::from("exit 1")),
+            ..lsf(false)
+        };
+        assert!(!unhealthy.to_runner().healthcheck().await);
+    }
+
+    #[test]
+    fn custom_placeholder_delimiters_are_substituted() {
+        let backend = GenericBackend {
+            submit: String::from("bsub -q <<queue>> -cwd ~{cwd} <<script>>"),
+            left_placeholder: String::from("<<"),
+            right_placeholder: String::from(">>"),
+            ..lsf(false)
+        };
+
+        assert_eq!(
+            backend.render_submit(&task()).unwrap(),
+            ["bsub -q compbio -cwd ~{cwd} echo 'hello world'"]
+        );
+    }
+
+    #[tokio::test]
+    async fn unresolved_placeholders_are_reported() {
+        let backend = GenericBackend {
+            submit: String::from("bsub -q ~{queue} -o ~{output} -e ~{error} ~{script}"),
+            ..lsf(false)
+        };
+
+        assert_eq!(
+            backend.render_submit(&task()).unwrap_err().to_string(),
+            "unresolved placeholders in command `bsub -q ~{queue} -o ~{output} -e ~{error} \
+             ~{script}`: `~{output}`, `~{error}`"
+        );
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        backend
+            .to_runner()
+            .run(String::from("lsf"), task(), tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_ne!(executions[0].status, 0);
+        assert!(executions[0].stderr.starts_with("unresolved placeholders"));
+    }
+
+    #[tokio::test]
+    async fn dry_runs_reply_with_the_submit_command() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        lsf(true)
+            .to_runner()
+            .run(String::from("lsf"), task(), tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(executions[0].status, 0);
+        assert_eq!(
+            executions[0].stdout,
+            "bsub -q compbio -n 1 -cwd /data echo 'hello world'"
+        );
+    }
+
+    #[tokio::test]
+    async fn submit_runs_in_the_working_directory() {
+        let dir = TempDir::new().unwrap();
+        let cwd = dir.path().canonicalize().unwrap();
+
+        let backend = GenericBackend {
+            runtime_attributes: None,
+            default_cpu: None,
+            default_ram_mb: None,
+            submit: String::from("echo \"Job <1> in $(pwd) (~{cwd})\""),
+            job_id_regex: Some(String::from("Job <(\\d+)>")),
+            monitor: Some(String::from("exit 1")),
+            monitor_frequency: None,
+            kill: None,
+            healthcheck: None,
+            expand_env: false,
+            left_placeholder: String::from(DEFAULT_LEFT_PLACEHOLDER),
+            right_placeholder: String::from(DEFAULT_RIGHT_PLACEHOLDER),
+            dry_run: false,
+        };
+
+        let task = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["true"])
+                .working_directory(cwd.to_str().unwrap())
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        backend
+            .to_runner()
+            .run(String::from("generic"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        let cwd = cwd.display();
+        assert_eq!(
+            reply.executions.unwrap()[0].stdout,
+            format!("Job <1> in {cwd} ({cwd})\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn jobs_without_a_job_id_regex_finish_with_the_submit_command() {
+        let backend = GenericBackend {
+            submit: String::from("echo ~{script}"),
+            ..lsf(false)
+        };
+
+        let mut substitutions = HashMap::from([(String::from("script"), String::from("done"))]);
+        let result = backend.process_command(&mut substitutions).await.unwrap();
+        assert_eq!(result.status, 0);
+        assert_eq!(result.stdout, "done\n");
+        assert_eq!(result.id, None);
+    }
+
+    #[tokio::test]
+    async fn missing_job_ids_are_reported() {
+        let backend = GenericBackend {
+            submit: String::from("echo submitted"),
+            job_id_regex: Some(String::from("Job <(\\d+)>")),
+            monitor: Some(String::from("exit 1")),
+            ..lsf(false)
+        };
+
+        let err = backend
+            .process_command(&mut HashMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the job id was not found in the output of the submit command"
+        );
+    }
+
+    #[test]
+    fn default_name_is_generic() {
+        assert_eq!(lsf(false).to_runner().default_name(), "generic");
+    }
 }
@@ -0,0 +1,435 @@
+//! A local execution backend that runs tasks as processes on the host.
+
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use nonempty::NonEmpty;
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot::Sender;
+use tracing::info_span;
+use tracing::Instrument as _;
+
+use crate::engine::service::runner::backend::Backend;
+use crate::engine::service::runner::backend::ExecutionResult;
+use crate::engine::service::runner::backend::Exit;
+use crate::engine::service::runner::backend::LogOutput;
+use crate::engine::service::runner::backend::Outcome;
+use crate::engine::service::runner::backend::OutputLog;
+use crate::engine::service::runner::backend::Reply;
+use crate::engine::task::Execution;
+use crate::engine::Task;
+
+/// A local execution backend.
+///
+/// Each [`Execution`] is run directly on the host with its first argument as
+/// the program and the remaining arguments passed to it. The working
+/// directory, environment variables, and standard input of the execution are
+/// honored, but the image is ignored entirely, so this backend does not
+/// require a Docker daemon.
+///
+/// Note that inputs, outputs, and volumes are not localized by this backend:
+/// executions see the host filesystem as-is. Outputs are reported in place,
+/// with the size of the file (or directory) at their path on the host, and the
+/// task fails if any of them is missing.
+#[derive(Debug, Default)]
+pub struct LocalBackend;
+
+impl LocalBackend {
+    /// Creates a new [`LocalBackend`].
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Backend for LocalBackend {
+    fn default_name(&self) -> &'static str {
+        "local"
+    }
+
+    fn run(
+        &self,
+        name: String,
+        task: Task,
+        cb: Sender<Reply>,
+        // NOTE: the output of each execution is collected when the process
+        // exits, so it is never streamed.
+        _: Option<UnboundedSender<LogOutput>>,
+    ) -> BoxFuture<'static, ()> {
+        let span = info_span!("run", backend = %name, task = task.name());
+
+        async move {
+            let mut results: Option<NonEmpty<ExecutionResult>> = None;
+
+            for execution in task.executions() {
+                let exec_result = process_exec(execution).await.unwrap_or_else(|e| {
+                    failure(format!(
+                        "failed to run `{program}` on the host: {e}",
+                        program = execution.args().head
+                    ))
+                });
+
+                results = match results {
+                    Some(mut results) => {
+                        results.push(exec_result);
+                        Some(results)
+                    }
+                    None => Some(NonEmpty::new(exec_result)),
+                }
+            }
+
+            let results = results.expect("at least one execution to be run");
+
+            let mut outputs = Vec::new();
+            let mut output_error = None;
+            for output in task.outputs().into_iter().flatten() {
+                match size_of(Path::new(output.path())) {
+                    Ok(size_bytes) => outputs.push(OutputLog {
+                        url: output.url().to_string(),
+                        path: output.path().to_string(),
+                        size_bytes,
+                    }),
+                    Err(e) => {
+                        output_error =
+                            Some(format!("failed to find output `{}`: {e}", output.path()));
+                        break;
+                    }
+                }
+            }
+
+            // NOTE: a missing output only fails a task whose executions all
+            // succeeded, as the failure of an execution is more informative.
+            let outcome = match Outcome::from_executions(&results) {
+                Outcome::Completed => {
+                    output_error.map_or(Outcome::Completed, Outcome::BackendError)
+                }
+                outcome => outcome,
+            };
+
+            let _ = cb.send(Reply {
+                backend: name,
+                outcome,
+                executions: Some(results),
+                outputs: NonEmpty::from_vec(outputs),
+                attempts: 1,
+            });
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+/// Runs an execution as a process on the host, returning an
+/// [`ExecutionResult`].
+async fn process_exec(execution: &Execution) -> std::io::Result<ExecutionResult> {
+    let args = execution.args();
+
+    let mut command = Command::new(&args.head);
+    command
+        .args(&args.tail)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // NOTE: a cancelled task (e.g., one that outlived the deadline of an
+        // engine run) must not leave its process running on the host.
+        .kill_on_drop(true);
+
+    if let Some(workdir) = execution.workdir() {
+        command.current_dir(workdir);
+    }
+
+    if let Some(env) = execution.env() {
+        command.envs(env);
+    }
+
+    match execution.stdin() {
+        Some(path) => command.stdin(File::open(path)?),
+        None => command.stdin(Stdio::null()),
+    };
+
+    let started_at = SystemTime::now();
+    let child = command.spawn()?;
+    let pid = child.id();
+    let output = child.wait_with_output().await?;
+
+    Ok(ExecutionResult {
+        status: status(output.status),
+        exit: exit(output.status),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        oom_killed: false,
+        started_at: Some(started_at),
+        ended_at: Some(SystemTime::now()),
+        id: pid.map(|pid| pid.to_string()),
+    })
+}
+
+/// Gets the size of the file at a path, or the total size of the files within
+/// the directory at a path.
+fn size_of(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::metadata(path)?;
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    fs::read_dir(path)?.try_fold(0, |size, entry| Ok(size + size_of(&entry?.path())?))
+}
+
+/// Creates a failed result for an execution whose process never ran (e.g.,
+/// because its program does not exist or its standard input could not be
+/// opened).
+fn failure(stderr: String) -> ExecutionResult {
+    ExecutionResult {
+        // NOTE: like a process without an exit code, an execution that never
+        // ran is reported with a status of -1.
+        status: -1,
+        exit: Exit::Unknown,
+        stdout: String::new(),
+        stderr,
+        oom_killed: false,
+        started_at: None,
+        ended_at: None,
+        id: None,
+    }
+}
+
+/// Gets the status of an exited process.
+///
+/// A process terminated by a signal has no exit code, so the negated signal
+/// number is reported instead (e.g., `-9` for `SIGKILL`).
+fn status(status: std::process::ExitStatus) -> i64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt as _;
+
+        if let Some(signal) = status.signal() {
+            return -i64::from(signal);
+        }
+    }
+
+    status.code().map(i64::from).unwrap_or(-1)
+}
+
+/// Gets how a process exited.
+fn exit(status: std::process::ExitStatus) -> Exit {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt as _;
+
+        if let Some(signal) = status.signal() {
+            return Exit::Signal(signal);
+        }
+    }
+
+    status
+        .code()
+        .map(|code| Exit::Code(code.into()))
+        .unwrap_or(Exit::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::*;
+    use crate::engine::task::output;
+    use crate::engine::task::testing::execution;
+    use crate::engine::task::testing::task;
+    use crate::engine::task::Output;
+
+    #[tokio::test]
+    async fn executions_honor_workdir_env_and_stdin() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("marker.txt"), "in the workdir").unwrap();
+
+        let stdin = dir.path().join("stdin.txt");
+        fs::write(&stdin, "from stdin").unwrap();
+
+        let task = Task::builder()
+            .extend_executions([
+                Execution::builder()
+                    .image("ignored")
+                    .args(["sh", "-c", "cat marker.txt; echo; echo $GREETING; cat"])
+                    .working_directory(dir.path().to_str().unwrap())
+                    .env("GREETING", "hello")
+                    .stdin(stdin.to_str().unwrap())
+                    .try_build()
+                    .unwrap(),
+                execution(["sh", "-c", "echo oops >&2; exit 3"]),
+            ])
+            .try_build()
+            .unwrap();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(String::from("local"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert_eq!(reply.backend, "local");
+        assert_eq!(
+            reply.outcome,
+            Outcome::Failed {
+                execution_index: 1,
+                status: 3
+            }
+        );
+
+        let executions = reply.executions.unwrap();
+        assert_eq!(executions.len(), 2);
+
+        assert_eq!(executions[0].status, 0);
+        assert_eq!(executions[0].stdout, "in the workdir\nhello\nfrom stdin");
+
+        assert_eq!(executions[1].status, 3);
+        assert_eq!(executions[1].stderr, "oops\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn executions_report_their_process_id() {
+        let task = task(["sh", "-c", "echo $$"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(String::from("local"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(
+            executions[0].id.as_deref(),
+            Some(executions[0].stdout.trim())
+        );
+    }
+
+    #[tokio::test]
+    async fn killed_executions_report_the_signal() {
+        let task = task(["sh", "-c", "kill -9 $$"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(String::from("local"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert_eq!(executions[0].status, -9);
+        assert_eq!(executions[0].exit, Exit::Signal(9));
+    }
+
+    #[tokio::test]
+    async fn missing_programs_fail_the_execution() {
+        let task = task(["crankshaft-no-such-program"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(String::from("local"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert_eq!(
+            reply.outcome,
+            Outcome::Failed {
+                execution_index: 0,
+                status: -1
+            }
+        );
+
+        let executions = reply.executions.unwrap();
+        assert_eq!(executions[0].exit, Exit::Unknown);
+        assert!(executions[0]
+            .stderr
+            .starts_with("failed to run `crankshaft-no-such-program` on the host"));
+    }
+
+    #[tokio::test]
+    async fn executions_are_timed() {
+        let task = task(["sleep", "0.1"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(String::from("local"), task, tx, None)
+            .await;
+
+        let executions = rx.await.unwrap().executions.unwrap();
+        assert!(executions[0].started_at.unwrap() <= executions[0].ended_at.unwrap());
+        assert!(executions[0].duration().unwrap() >= std::time::Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn outputs_are_reported_with_their_size() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("result.txt");
+        let directory = dir.path().join("results");
+        let missing = dir.path().join("missing.txt");
+
+        let output = |path: &Path, r#type| {
+            Output::builder()
+                .url(Url::from_file_path(path).unwrap())
+                .path(path.to_str().unwrap())
+                .r#type(r#type)
+                .try_build()
+                .unwrap()
+        };
+
+        let script = format!(
+            "printf hello > {file}; mkdir {directory}; printf abc > {directory}/a; printf de > \
+             {directory}/b",
+            file = file.display(),
+            directory = directory.display()
+        );
+        let task = |outputs: Vec<Output>| {
+            Task::builder()
+                .extend_executions([execution(["sh", "-c", &script])])
+                .extend_outputs(outputs)
+                .try_build()
+                .unwrap()
+        };
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(
+                String::from("local"),
+                task(vec![
+                    output(&file, output::Type::File),
+                    output(&directory, output::Type::Directory),
+                ]),
+                tx,
+                None,
+            )
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert_eq!(reply.outcome, Outcome::Completed);
+        let outputs = reply.outputs.unwrap();
+        assert_eq!(outputs[0].path, file.to_str().unwrap());
+        assert_eq!(outputs[0].size_bytes, 5);
+        assert_eq!(outputs[1].size_bytes, 5);
+
+        fs::remove_dir_all(&directory).unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        LocalBackend::new()
+            .run(
+                String::from("local"),
+                task(vec![output(&missing, output::Type::File)]),
+                tx,
+                None,
+            )
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert!(matches!(
+            reply.outcome,
+            Outcome::BackendError(message) if message.starts_with("failed to find output")
+        ));
+    }
+}
@@ -2,117 +2,631 @@
 
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use futures::future::BoxFuture;
 use futures::FutureExt as _;
 use nonempty::NonEmpty;
 use reqwest::header;
+use serde::Deserialize;
+use serde::Serialize;
+use tes::task::State;
+use tes::task::TaskLog;
 use tes::Client;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::Sender;
+use tracing::info_span;
+use tracing::warn;
+use tracing::Instrument as _;
 
 use crate::engine::service::runner::backend::Backend;
 use crate::engine::service::runner::backend::ExecutionResult;
+use crate::engine::service::runner::backend::Exit;
+use crate::engine::service::runner::backend::LogOutput;
+use crate::engine::service::runner::backend::Outcome;
+use crate::engine::service::runner::backend::OutputLog;
 use crate::engine::service::runner::backend::Reply;
 use crate::engine::Task;
 use crate::BoxedError;
 
+pub mod convert;
+
 /// The number of parts in the random name of each TES container.
 pub const NAME_PARTS: usize = 4;
 
 /// The separator between each random part of the TES container name.
 pub const NAME_SEPARATOR: &str = "-";
 
+/// The default interval at which the state of a submitted task is polled.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The number of consecutive times polling the state of a submitted task may
+/// fail before the task is given up on.
+pub const MAX_POLL_FAILURES: u32 = 5;
+
 /// A [`Result`](std::result::Result) with an [`BoxedError`]
 pub type Result<T> = std::result::Result<T, BoxedError>;
 
+/// The authentication used when communicating with a TES server.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    /// No authentication.
+    #[default]
+    None,
+
+    /// HTTP basic authentication with the given (already encoded) credentials.
+    Basic(String),
+
+    /// Bearer token authentication (e.g., an OAuth2 access token).
+    Bearer(String),
+}
+
+impl Auth {
+    /// Gets the value of the `Authorization` header for the authentication.
+    ///
+    /// Returns [`None`] if no authentication is used.
+    pub fn header_value(&self) -> Option<String> {
+        match self {
+            Auth::None => None,
+            Auth::Basic(credentials) => Some(format!("Basic {credentials}")),
+            Auth::Bearer(token) => Some(format!("Bearer {token}")),
+        }
+    }
+}
+
 /// A local execution backend.
 #[derive(Debug)]
 pub struct TesBackend {
     /// A handle to the inner TES client.
     client: Arc<Client>,
+
+    /// The interval at which the state of a submitted task is polled.
+    poll_interval: Duration,
+
+    /// How long a submitted task is polled before giving up (if bounded).
+    max_poll_duration: Option<Duration>,
 }
 
 impl TesBackend {
-    /// Creates a new [`TesBackend`].
-    pub fn new(url: impl Into<String>, token: Option<impl Into<String>>) -> Self {
-        let url = url.into();
+    /// Creates a new [`TesBackend`] that authenticates with HTTP basic
+    /// authentication when a token is provided.
+    #[deprecated(note = "use `TesBackend::new_with_auth` instead")]
+    pub fn new(url: impl Into<String>, token: Option<impl Into<String>>) -> Result<Self> {
+        let auth = token.map_or(Auth::None, |token| Auth::Basic(token.into()));
+        Self::new_with_auth(url, auth)
+    }
 
-        let mut headers = header::HeaderMap::new();
+    /// Creates a new [`TesBackend`] that authenticates with the given
+    /// [`Auth`].
+    pub fn new_with_auth(url: impl Into<String>, auth: Auth) -> Result<Self> {
+        Self::new_with_headers(url, auth, header::HeaderMap::new())
+    }
+
+    /// Creates a new [`TesBackend`] that authenticates with the given
+    /// [`Auth`] and sends the given extra headers with every request.
+    ///
+    /// # Notes
+    ///
+    /// The `Authorization` header set by the [`Auth`] overwrites any
+    /// `Authorization` header within the extra headers.
+    ///
+    /// An error is returned if the credentials of the [`Auth`] are not a valid
+    /// header value or if the client cannot be created.
+    pub fn new_with_headers(
+        url: impl Into<String>,
+        auth: Auth,
+        mut headers: header::HeaderMap,
+    ) -> Result<Self> {
+        let url = url.into();
 
-        if let Some(token) = token {
-            headers.insert(
-                "Authorization",
-                header::HeaderValue::from_str(&format!("Basic {}", token.into())).unwrap(),
-            );
+        if let Some(value) = auth.header_value() {
+            let value = header::HeaderValue::from_str(&value)
+                .map_err(|_| "the TES credentials are not a valid header value")?;
+            headers.insert(header::AUTHORIZATION, value);
         }
 
-        let inner = Client::new(&url, headers).unwrap();
+        let inner = Client::new(&url, headers)?;
 
-        Self {
+        Ok(Self {
             client: Arc::new(inner),
-        }
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            max_poll_duration: None,
+        })
+    }
+
+    /// Sets the interval at which the state of a submitted task is polled.
+    ///
+    /// # Notes
+    ///
+    /// This overwrites the [default poll interval](DEFAULT_POLL_INTERVAL).
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets how long a submitted task is polled before giving up.
+    ///
+    /// A task that has not finished by then is cancelled (on a best-effort
+    /// basis) and reported as [timed out](Outcome::TimedOut). By default, a
+    /// task is polled until it finishes.
+    ///
+    /// Regardless, polling gives up with a [backend
+    /// error](Outcome::BackendError) once the state of the task could not be
+    /// retrieved [`MAX_POLL_FAILURES`] times in a row.
+    pub fn with_max_poll_duration(mut self, max_poll_duration: Duration) -> Self {
+        self.max_poll_duration = Some(max_poll_duration);
+        self
     }
 }
 
 #[async_trait]
 impl Backend for TesBackend {
     fn default_name(&self) -> &'static str {
-        unimplemented!("you must provide a backend name for a TES runner!")
+        "tes"
     }
 
-    fn run(&self, name: String, task: Task, cb: Sender<Reply>) -> BoxFuture<'static, ()> {
+    fn run(
+        &self,
+        name: String,
+        task: Task,
+        cb: Sender<Reply>,
+        // NOTE: TES only reports the output of executors once the task has
+        // completed, so it is never streamed.
+        _: Option<UnboundedSender<LogOutput>>,
+    ) -> BoxFuture<'static, ()> {
         let client = self.client.clone();
+        let poll_interval = self.poll_interval;
+        let max_poll_duration = self.max_poll_duration;
+        let span = info_span!("run", backend = %name, task = task.name());
 
-        let task = tes::Task {
-            name: task.name().map(|v| v.to_owned()),
-            description: task.description().map(|v| v.to_owned()),
-            executors: task
-                .executions()
-                .map(|execution| tes::task::Executor {
-                    image: execution.image().to_owned(),
-                    command: execution.args().into_iter().cloned().collect::<Vec<_>>(),
-                    ..Default::default()
-                })
-                .collect::<Vec<_>>(),
-            ..Default::default()
-        };
+        let task = tes::Task::try_from(task);
 
         async move {
-            let task_id = client.create_task(task).await.unwrap();
+            let created = match task {
+                Ok(task) => client
+                    .create_task(task)
+                    .await
+                    .map_err(|e| format!("failed to create task: {e}")),
+                Err(e) => Err(format!("failed to convert task: {e}")),
+            };
+
+            let task_id = match created {
+                Ok(task_id) => task_id,
+                Err(message) => {
+                    let _ = cb.send(Reply {
+                        backend: name,
+                        outcome: Outcome::BackendError(message.clone()),
+                        executions: Some(NonEmpty::new(failure(message))),
+                        outputs: None,
+                        attempts: 1,
+                    });
+                    return;
+                }
+            };
+
+            let polling_since = Instant::now();
+            let mut failures = 0;
 
             loop {
-                if let Ok(task) = client.get_task(&task_id).await {
-                    if let Some(ref state) = task.state {
-                        if !state.is_executing() {
-                            let mut results = task
-                                .logs
-                                .unwrap()
-                                .into_iter()
-                                .flat_map(|task| task.logs)
-                                .map(|log| ExecutionResult {
-                                    status: log.exit_code.unwrap_or_default() as u64,
-                                    stdout: log.stdout.unwrap_or_default(),
-                                    stderr: log.stderr.unwrap_or_default(),
-                                });
-
-                            let mut executions = NonEmpty::new(results.next().unwrap());
-                            executions.extend(results);
+                let response = client.get_task(&task_id).await;
+
+                match response {
+                    Ok(task) => {
+                        failures = 0;
+
+                        if let Some(state) = task.state.as_ref().filter(|state| state.is_terminal())
+                        {
+                            let outputs = output_logs(task.logs.as_deref());
+                            let mut executions = execution_results(state, task.logs);
+                            for execution in executions.iter_mut().flat_map(|e| e.iter_mut()) {
+                                execution.id = Some(task_id.clone());
+                            }
 
                             let reply = Reply {
                                 backend: name,
-                                executions: Some(executions),
+                                outcome: outcome(state, executions.as_ref()),
+                                executions,
+                                outputs,
+                                attempts: 1,
                             };
 
                             let _ = cb.send(reply);
                             return;
                         }
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        warn!(task_id, error = %e, failures, "failed to get the state of task");
+
+                        if failures >= MAX_POLL_FAILURES {
+                            let message = format!(
+                                "failed to get the state of task `{task_id}` {failures} times in a \
+                                 row: {e}"
+                            );
+                            let mut execution = failure(message.clone());
+                            execution.id = Some(task_id);
+                            let _ = cb.send(Reply {
+                                backend: name,
+                                outcome: Outcome::BackendError(message),
+                                executions: Some(NonEmpty::new(execution)),
+                                outputs: None,
+                                attempts: 1,
+                            });
+                            return;
+                        }
+                    }
+                }
 
-                        tokio::time::sleep(Duration::from_millis(200)).await;
+                if let Some(max) = max_poll_duration.filter(|max| polling_since.elapsed() >= *max) {
+                    warn!(task_id, "task did not finish within {max:?}; cancelling it");
+                    if let Err(e) = client.cancel_task(&task_id).await {
+                        warn!(task_id, error = %e, "failed to cancel task");
                     }
+
+                    let message = format!("task `{task_id}` did not finish within {max:?}");
+                    let mut execution = failure(message);
+                    execution.id = Some(task_id);
+                    let _ = cb.send(Reply {
+                        backend: name,
+                        outcome: Outcome::TimedOut,
+                        executions: Some(NonEmpty::new(execution)),
+                        outputs: None,
+                        attempts: 1,
+                    });
+                    return;
                 }
+
+                tokio::time::sleep(poll_interval).await;
             }
         }
+        .instrument(span)
         .boxed()
     }
+
+    fn healthcheck(&self) -> BoxFuture<'static, bool> {
+        let client = self.client.clone();
+        async move { client.healthcheck().await }.boxed()
+    }
+}
+
+/// Creates a failed result for an executor that never ran.
+fn failure(stderr: String) -> ExecutionResult {
+    ExecutionResult {
+        // NOTE: like a process without an exit code, an executor that never
+        // ran is reported with a status of -1.
+        status: -1,
+        exit: Exit::Unknown,
+        stdout: String::new(),
+        stderr,
+        oom_killed: false,
+        started_at: None,
+        ended_at: None,
+        id: None,
+    }
+}
+
+/// Collects the results of the executors of a task that has stopped executing.
+///
+/// If the task errored before any of its executors produced logs (e.g., because
+/// of a system error), a single failed result describing the error is returned
+/// instead. Otherwise, [`None`] is returned if no executor produced logs (e.g.,
+/// because the task was cancelled before it started).
+fn execution_results(
+    state: &State,
+    logs: Option<Vec<TaskLog>>,
+) -> Option<NonEmpty<ExecutionResult>> {
+    let logs = logs.unwrap_or_default();
+
+    if state.is_error() && logs.iter().all(|log| log.logs.is_empty()) {
+        let mut stderr = format!("task failed with state `{state:?}`");
+        for system_logs in logs.iter().filter_map(|log| log.system_logs.as_ref()) {
+            stderr.push('\n');
+            stderr.push_str(system_logs);
+        }
+
+        return Some(NonEmpty::new(failure(stderr)));
+    }
+
+    let results = logs
+        .into_iter()
+        .flat_map(|task| task.logs)
+        .map(|log| ExecutionResult {
+            // NOTE: a missing exit code must not be mistaken for success.
+            status: log.exit_code.map_or(-1, Into::into),
+            exit: log
+                .exit_code
+                .map(|code| Exit::Code(code.into()))
+                .unwrap_or(Exit::Unknown),
+            stdout: log.stdout.unwrap_or_default(),
+            stderr: log.stderr.unwrap_or_default(),
+            oom_killed: false,
+            started_at: log.start_time.map(Into::into),
+            ended_at: log.end_time.map(Into::into),
+            id: None,
+        })
+        .collect();
+
+    NonEmpty::from_vec(results)
+}
+
+/// Gets the outcome of a task that has stopped executing.
+///
+/// Only the results of a task that completed or whose executors errored
+/// reflect the task itself; any other terminal state (e.g., a system error or
+/// preemption) is reported as a backend error.
+fn outcome(state: &State, executions: Option<&NonEmpty<ExecutionResult>>) -> Outcome {
+    match state {
+        State::Canceled => Outcome::Cancelled,
+        State::Complete | State::ExecutorError => {
+            Outcome::from_executions(executions.into_iter().flatten())
+        }
+        _ => Outcome::BackendError(format!("task failed with state `{state:?}`")),
+    }
+}
+
+/// Collects the logs of the outputs uploaded by the TES server.
+fn output_logs(logs: Option<&[TaskLog]>) -> Option<NonEmpty<OutputLog>> {
+    let outputs = logs
+        .into_iter()
+        .flatten()
+        .flat_map(|log| log.outputs.iter().flatten())
+        .map(|log| OutputLog {
+            url: log.url.clone(),
+            path: log.path.clone(),
+            // NOTE: TES reports sizes as strings (as they are 64-bit integers).
+            size_bytes: log.size_bytes.parse().unwrap_or_default(),
+        })
+        .collect();
+
+    NonEmpty::from_vec(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::task::testing::task;
+
+    #[test]
+    fn auth_sets_the_authorization_scheme() {
+        assert_eq!(Auth::None.header_value(), None);
+        assert_eq!(
+            Auth::Basic(String::from("a2lkczI0"))
+                .header_value()
+                .as_deref(),
+            Some("Basic a2lkczI0")
+        );
+        assert_eq!(
+            Auth::Bearer(String::from("secret"))
+                .header_value()
+                .as_deref(),
+            Some("Bearer secret")
+        );
+    }
+
+    #[tokio::test]
+    async fn polling_gives_up_after_the_max_poll_duration() {
+        use std::sync::Mutex;
+
+        use tokio::io::AsyncReadExt as _;
+        use tokio::io::AsyncWriteExt as _;
+        use tokio::net::TcpListener;
+
+        // A TES server whose tasks run forever, recording each request line
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+                let line = request.lines().next().unwrap_or_default().to_string();
+
+                let body = if line.starts_with("POST /tasks ") {
+                    r#"{"id":"task-1"}"#
+                } else if line.starts_with("GET /tasks/task-1") {
+                    r#"{"id":"task-1","state":"RUNNING","executors":[]}"#
+                } else {
+                    "{}"
+                };
+                recorded.lock().unwrap().push(line);
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: \
+                     {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let task = task(["sleep", "infinity"]);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        TesBackend::new_with_auth(format!("http://{addr}/"), Auth::None)
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(10))
+            .with_max_poll_duration(Duration::from_millis(50))
+            .run(String::from("tes"), task, tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        assert!(matches!(reply.outcome, Outcome::TimedOut));
+        assert_eq!(reply.executions.unwrap()[0].id.as_deref(), Some("task-1"));
+        assert!(requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|line| line.starts_with("POST /tasks/task-1:cancel ")));
+    }
+
+    #[tokio::test]
+    async fn polling_gives_up_after_repeated_failures() {
+        use tokio::io::AsyncReadExt as _;
+        use tokio::io::AsyncWriteExt as _;
+        use tokio::net::TcpListener;
+
+        // A TES server that loses track of its tasks once they are created
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let read = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..read]);
+
+                let (status, body) = if request.starts_with("POST /tasks ") {
+                    ("200 OK", r#"{"id":"task-1"}"#)
+                } else {
+                    ("404 Not Found", "{}")
+                };
+
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: \
+                     {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        TesBackend::new_with_auth(format!("http://{addr}/"), Auth::None)
+            .unwrap()
+            .with_poll_interval(Duration::from_millis(10))
+            .run(String::from("tes"), task(["true"]), tx, None)
+            .await;
+
+        let reply = rx.await.unwrap();
+        match reply.outcome {
+            Outcome::BackendError(message) => assert!(
+                message.starts_with("failed to get the state of task `task-1` 5 times in a row"),
+                "{message}"
+            ),
+            outcome => panic!("expected a backend error, found {outcome:?}"),
+        }
+        assert_eq!(reply.executions.unwrap()[0].id.as_deref(), Some("task-1"));
+    }
+
+    #[test]
+    fn invalid_credentials_are_errors() {
+        let auth = Auth::Bearer(String::from("line\nbreak"));
+        assert!(TesBackend::new_with_auth("http://localhost:8000", auth).is_err());
+    }
+
+    #[test]
+    fn default_name_is_tes() {
+        let backend = TesBackend::new_with_auth("http://localhost:8000", Auth::None).unwrap();
+        assert_eq!(backend.default_name(), "tes");
+    }
+
+    #[test]
+    fn system_errors_without_logs_are_failures() {
+        let executions = execution_results(
+            &State::SystemError,
+            Some(vec![TaskLog {
+                system_logs: Some(String::from("node was preempted")),
+                ..Default::default()
+            }]),
+        )
+        .unwrap();
+
+        assert_eq!(executions.len(), 1);
+        assert_ne!(executions[0].status, 0);
+        assert_eq!(
+            executions[0].stderr,
+            "task failed with state `SystemError`\nnode was preempted"
+        );
+
+        let executions = execution_results(&State::ExecutorError, None).unwrap();
+        assert_ne!(executions[0].status, 0);
+    }
+
+    #[test]
+    fn outcomes_follow_the_task_state() {
+        let logs = || {
+            Some(vec![TaskLog {
+                logs: vec![tes::task::executor::Log {
+                    exit_code: Some(2),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }])
+        };
+
+        let executions = execution_results(&State::ExecutorError, logs());
+        assert_eq!(
+            outcome(&State::ExecutorError, executions.as_ref()),
+            Outcome::Failed {
+                execution_index: 0,
+                status: 2
+            }
+        );
+
+        let executions = execution_results(&State::SystemError, None);
+        assert_eq!(
+            outcome(&State::SystemError, executions.as_ref()),
+            Outcome::BackendError(String::from("task failed with state `SystemError`"))
+        );
+
+        assert_eq!(outcome(&State::Canceled, None), Outcome::Cancelled);
+    }
+
+    #[test]
+    fn output_logs_are_reported() {
+        let log = |path: &str, size_bytes: &str| tes::task::OutputFileLog {
+            url: format!("s3://bucket{path}"),
+            path: path.to_string(),
+            size_bytes: size_bytes.to_string(),
+        };
+        let logs = [
+            TaskLog {
+                outputs: Some(vec![log("/outputs/result.txt", "1024")]),
+                ..Default::default()
+            },
+            TaskLog {
+                outputs: Some(vec![
+                    log("/outputs/huge.bam", "9007199254740993"),
+                    log("/outputs/unknown.txt", "unknown"),
+                ]),
+                ..Default::default()
+            },
+        ];
+
+        let outputs = output_logs(Some(&logs)).unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[0].url, "s3://bucket/outputs/result.txt");
+        assert_eq!(outputs[0].path, "/outputs/result.txt");
+        assert_eq!(outputs[0].size_bytes, 1024);
+
+        // NOTE: sizes beyond the precision of a JSON number are kept intact,
+        // and sizes that cannot be parsed are reported as zero.
+        assert_eq!(outputs[1].size_bytes, 9_007_199_254_740_993);
+        assert_eq!(outputs[2].size_bytes, 0);
+
+        assert!(output_logs(None).is_none());
+    }
+
+    #[test]
+    fn missing_exit_codes_are_not_successes() {
+        let logs = vec![TaskLog {
+            logs: vec![tes::task::executor::Log::default()],
+            ..Default::default()
+        }];
+
+        let executions = execution_results(&State::Complete, Some(logs)).unwrap();
+        assert_eq!(executions[0].status, -1);
+        assert_eq!(executions[0].exit, Exit::Unknown);
+    }
+
+    #[test]
+    fn missing_logs_are_not_results() {
+        assert!(execution_results(&State::Canceled, None).is_none());
+        assert!(execution_results(&State::Complete, Some(vec![TaskLog::default()])).is_none());
+    }
 }
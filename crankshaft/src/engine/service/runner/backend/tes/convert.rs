@@ -0,0 +1,579 @@
+//! Conversions between crankshaft tasks and TES tasks.
+//!
+//! Not everything maps cleanly between the two:
+//!
+//! * TES volumes are container paths only, so a task with a volume that mounts
+//!   a host path cannot be converted to a TES task. The options of a
+//!   temporary mount (e.g., a size limit) have no TES equivalent and are
+//!   dropped.
+//! * TES has no notion of retries, so the maximum number of retries of a task
+//!   is not carried over.
+//! * TES only requests resources for a whole task, so the resources of
+//!   individual executions are not carried over.
+
+use url::Url;
+
+use crate::engine::task::input;
+use crate::engine::task::output;
+use crate::engine::task::Execution;
+use crate::engine::task::Input;
+use crate::engine::task::Output;
+use crate::engine::task::Resources;
+use crate::engine::Task;
+
+/// An error converting between a crankshaft task and a TES task.
+#[derive(Debug)]
+pub enum Error {
+    /// A volume mounts a path from the host, which TES does not support.
+    HostVolume(String),
+
+    /// A URL could not be parsed.
+    InvalidUrl {
+        /// The URL.
+        url: String,
+
+        /// The reason the URL could not be parsed.
+        reason: url::ParseError,
+    },
+
+    /// A TES input has neither a URL nor content.
+    MissingContents {
+        /// The path of the input.
+        path: String,
+    },
+
+    /// A TES output has no URL to copy the output to.
+    MissingUrl {
+        /// The path of the output.
+        path: String,
+    },
+
+    /// A TES task requests a negative number of CPU cores.
+    NegativeCpuCores(i64),
+
+    /// The converted task could not be built (e.g., because the TES task has
+    /// no executors).
+    Build(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::HostVolume(volume) => {
+                write!(
+                    f,
+                    "volume `{volume}` mounts a host path, which TES does not support"
+                )
+            }
+            Error::InvalidUrl { url, reason } => write!(f, "invalid URL `{url}`: {reason}"),
+            Error::MissingContents { path } => {
+                write!(f, "input `{path}` has neither a URL nor content")
+            }
+            Error::MissingUrl { path } => write!(f, "output `{path}` has no URL"),
+            Error::NegativeCpuCores(cores) => {
+                write!(f, "a negative number of CPU cores ({cores}) was requested")
+            }
+            Error::Build(reason) => write!(f, "failed to build task: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A [`Result`](std::result::Result) with an [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl TryFrom<Task> for tes::Task {
+    type Error = Error;
+
+    fn try_from(task: Task) -> Result<Self> {
+        Ok(tes::Task {
+            name: task.name().map(ToOwned::to_owned),
+            description: task.description().map(ToOwned::to_owned),
+            inputs: task.inputs().map(|inputs| inputs.map(to_input).collect()),
+            outputs: task
+                .outputs()
+                .map(|outputs| outputs.map(to_output).collect()),
+            resources: task.resources().map(to_resources),
+            executors: task.executions().map(to_executor).collect(),
+            volumes: task
+                .volumes()
+                .map(|volumes| volumes.map(|volume| to_volume(volume)).collect())
+                .transpose()?,
+            tags: task.tags().cloned(),
+            ..Default::default()
+        })
+    }
+}
+
+impl TryFrom<tes::Task> for Task {
+    type Error = Error;
+
+    fn try_from(task: tes::Task) -> Result<Self> {
+        let mut builder = Task::builder();
+
+        if let Some(name) = task.name {
+            builder = builder.name(name);
+        }
+
+        if let Some(description) = task.description {
+            builder = builder.description(description);
+        }
+
+        if let Some(inputs) = task.inputs {
+            builder = builder.extend_inputs(
+                inputs
+                    .into_iter()
+                    .map(from_input)
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+
+        if let Some(outputs) = task.outputs {
+            builder = builder.extend_outputs(
+                outputs
+                    .into_iter()
+                    .map(from_output)
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+
+        if let Some(resources) = task.resources {
+            builder = builder.resources(from_resources(resources)?);
+        }
+
+        builder = builder.extend_executions(
+            task.executors
+                .into_iter()
+                .map(from_executor)
+                .collect::<Result<Vec<_>>>()?,
+        );
+
+        if let Some(volumes) = task.volumes {
+            builder = builder.extend_volumes(volumes);
+        }
+
+        if let Some(tags) = task.tags {
+            builder = builder.extend_tags(tags);
+        }
+
+        builder.try_build().map_err(|e| Error::Build(e.to_string()))
+    }
+}
+
+/// Maps an input type to a TES file type.
+fn to_input_type(ty: &input::Type) -> tes::task::file::Type {
+    match ty {
+        input::Type::File => tes::task::file::Type::File,
+        input::Type::Directory => tes::task::file::Type::Directory,
+    }
+}
+
+/// Maps an output type to a TES file type.
+fn to_output_type(ty: &output::Type) -> tes::task::file::Type {
+    match ty {
+        output::Type::File => tes::task::file::Type::File,
+        output::Type::Directory => tes::task::file::Type::Directory,
+    }
+}
+
+/// Maps an [`Input`] to a TES input.
+fn to_input(input: &Input) -> tes::task::Input {
+    let (url, content) = match input.contents() {
+        input::Contents::URL(url) => (Some(url.to_string()), None),
+        input::Contents::Literal(content) => (None, Some(content.clone())),
+    };
+
+    tes::task::Input {
+        name: input.name().map(ToOwned::to_owned),
+        description: input.description().map(ToOwned::to_owned),
+        url,
+        path: input.path().to_owned(),
+        r#type: to_input_type(input.r#type()),
+        content,
+    }
+}
+
+/// Maps an [`Output`] to a TES output.
+///
+/// The URL of an [`Output`] is required, so a TES output always has one to
+/// copy the output to, and its type becomes the TES file type.
+fn to_output(output: &Output) -> tes::task::Output {
+    tes::task::Output {
+        name: output.name().map(ToOwned::to_owned),
+        description: output.description().map(ToOwned::to_owned),
+        url: output.url().to_owned(),
+        path: output.path().to_owned(),
+        r#type: to_output_type(output.r#type()),
+    }
+}
+
+/// Maps an [`Execution`] to a TES executor.
+fn to_executor(execution: &Execution) -> tes::task::Executor {
+    tes::task::Executor {
+        image: execution.image().to_owned(),
+        command: execution.args().into_iter().cloned().collect::<Vec<_>>(),
+        workdir: execution.workdir().cloned(),
+        stdin: execution.stdin().cloned(),
+        stdout: execution.stdout().cloned(),
+        stderr: execution.stderr().cloned(),
+        env: execution.env().map(|env| {
+            env.iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect()
+        }),
+    }
+}
+
+/// Maps a set of [`Resources`] to the TES requested resources.
+fn to_resources(resources: &Resources) -> tes::task::Resources {
+    tes::task::Resources {
+        // NOTE: a request for more cores than fit in an `i64` is clamped, as
+        // no TES server could satisfy it anyway.
+        cpu_cores: resources
+            .cpu_cores()
+            .map(|cores| i64::try_from(cores).unwrap_or(i64::MAX)),
+        preemptible: resources.preemptible(),
+        ram_gb: resources.ram_gb(),
+        disk_gb: resources.disk_gb(),
+        zones: resources
+            .zones()
+            .map(|zones| zones.iter().cloned().collect()),
+    }
+}
+
+/// Maps a volume to a TES volume.
+///
+/// See [`Builder::extend_volumes`](crate::engine::task::Builder::extend_volumes)
+/// for the forms a volume may take.
+fn to_volume(volume: &str) -> Result<String> {
+    match volume.split(':').collect::<Vec<_>>()[..] {
+        [path] => Ok(path.to_owned()),
+        // NOTE: the options of a temporary mount are never absolute paths.
+        [path, options] if !options.starts_with('/') => Ok(path.to_owned()),
+        _ => Err(Error::HostVolume(volume.to_owned())),
+    }
+}
+
+/// Parses a URL in a TES task.
+fn parse_url(url: String) -> Result<Url> {
+    Url::parse(&url).map_err(|reason| Error::InvalidUrl { url, reason })
+}
+
+/// Maps a TES file type to an input type.
+fn from_input_type(ty: tes::task::file::Type) -> input::Type {
+    match ty {
+        tes::task::file::Type::File => input::Type::File,
+        tes::task::file::Type::Directory => input::Type::Directory,
+    }
+}
+
+/// Maps a TES file type to an output type.
+fn from_output_type(ty: tes::task::file::Type) -> output::Type {
+    match ty {
+        tes::task::file::Type::File => output::Type::File,
+        tes::task::file::Type::Directory => output::Type::Directory,
+    }
+}
+
+/// Maps a TES input to an [`Input`].
+///
+/// If a TES input has both content and a URL, the content takes precedence
+/// (as specified by TES).
+fn from_input(input: tes::task::Input) -> Result<Input> {
+    let contents = match (input.content, input.url) {
+        (Some(content), _) => input::Contents::Literal(content),
+        (None, Some(url)) => input::Contents::URL(parse_url(url)?),
+        (None, None) => return Err(Error::MissingContents { path: input.path }),
+    };
+
+    let mut builder = Input::builder()
+        .contents(contents)
+        .path(input.path)
+        .r#type(from_input_type(input.r#type));
+
+    if let Some(name) = input.name {
+        builder = builder.name(name);
+    }
+
+    if let Some(description) = input.description {
+        builder = builder.description(description);
+    }
+
+    builder.try_build().map_err(|e| Error::Build(e.to_string()))
+}
+
+/// Maps a TES output to an [`Output`].
+///
+/// An output must have a URL to copy it to, so an output with an empty URL is
+/// rejected (as a TES server would).
+fn from_output(output: tes::task::Output) -> Result<Output> {
+    if output.url.is_empty() {
+        return Err(Error::MissingUrl { path: output.path });
+    }
+
+    let mut builder = Output::builder()
+        .url(parse_url(output.url)?)
+        .path(output.path)
+        .r#type(from_output_type(output.r#type));
+
+    if let Some(name) = output.name {
+        builder = builder.name(name);
+    }
+
+    if let Some(description) = output.description {
+        builder = builder.description(description);
+    }
+
+    builder.try_build().map_err(|e| Error::Build(e.to_string()))
+}
+
+/// Maps TES requested resources to a set of [`Resources`].
+fn from_resources(resources: tes::task::Resources) -> Result<Resources> {
+    let mut builder = Resources::builder();
+
+    if let Some(cores) = resources.cpu_cores {
+        let cores = u64::try_from(cores).map_err(|_| Error::NegativeCpuCores(cores))?;
+        builder = builder.cpu_cores(cores);
+    }
+
+    if let Some(preemptible) = resources.preemptible {
+        builder = builder.preemptible(preemptible);
+    }
+
+    if let Some(ram_gb) = resources.ram_gb {
+        builder = builder.ram_gb(ram_gb);
+    }
+
+    if let Some(disk_gb) = resources.disk_gb {
+        builder = builder.disk_gb(disk_gb);
+    }
+
+    if let Some(zones) = resources.zones.filter(|zones| !zones.is_empty()) {
+        builder = builder.zones(zones.into_iter());
+    }
+
+    Ok(builder.build())
+}
+
+/// Maps a TES executor to an [`Execution`].
+fn from_executor(executor: tes::task::Executor) -> Result<Execution> {
+    let mut builder = Execution::builder()
+        .image(executor.image)
+        .args(executor.command);
+
+    if let Some(workdir) = executor.workdir {
+        builder = builder.working_directory(workdir);
+    }
+
+    if let Some(stdin) = executor.stdin {
+        builder = builder.stdin(stdin);
+    }
+
+    if let Some(stdout) = executor.stdout {
+        builder = builder.stdout(stdout);
+    }
+
+    if let Some(stderr) = executor.stderr {
+        builder = builder.stderr(stderr);
+    }
+
+    for (name, value) in executor.env.into_iter().flatten() {
+        builder = builder.env(name, value);
+    }
+
+    builder.try_build().map_err(|e| Error::Build(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a task that uses every field that maps cleanly to TES.
+    fn task() -> Task {
+        Task::builder()
+            .name("hello")
+            .description("says hello")
+            .extend_inputs([
+                Input::builder()
+                    .contents(input::Contents::Literal(String::from("hello")))
+                    .path("/inputs/greeting.txt")
+                    .r#type(input::Type::File)
+                    .try_build()
+                    .unwrap(),
+                Input::builder()
+                    .contents(input::Contents::URL(
+                        Url::parse("s3://bucket/reference").unwrap(),
+                    ))
+                    .path("/inputs/reference")
+                    .r#type(input::Type::Directory)
+                    .name("reference")
+                    .try_build()
+                    .unwrap(),
+            ])
+            .extend_outputs([
+                Output::builder()
+                    .url(Url::parse("s3://bucket/result.txt").unwrap())
+                    .path("/outputs/result.txt")
+                    .r#type(output::Type::File)
+                    .try_build()
+                    .unwrap(),
+                Output::builder()
+                    .url(Url::parse("s3://bucket/results/").unwrap())
+                    .path("/outputs/results")
+                    .r#type(output::Type::Directory)
+                    .try_build()
+                    .unwrap(),
+            ])
+            .resources(
+                Resources::builder()
+                    .cpu_cores(2u64)
+                    .ram_gb(4.0)
+                    .zones(["us-east-1a"].into_iter())
+                    .build(),
+            )
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["cat", "/inputs/greeting.txt"])
+                .working_directory("/inputs")
+                .stdout("/outputs/result.txt")
+                .env("NAME", "value")
+                .try_build()
+                .unwrap()])
+            .extend_volumes([String::from("/scratch")])
+            .extend_tags([("workflow", "hello-world"), ("sample", "SJ001")])
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn executor_forwards_redirection_and_environment() {
+        let execution = Execution::builder()
+            .image("ubuntu")
+            .args(["cat"])
+            .stdin("/inputs/stdin.txt")
+            .stdout("/outputs/stdout.txt")
+            .stderr("/outputs/stderr.txt")
+            .env("NAME", "value")
+            .try_build()
+            .unwrap();
+
+        let executor = to_executor(&execution);
+        assert_eq!(executor.stdin.as_deref(), Some("/inputs/stdin.txt"));
+        assert_eq!(executor.stdout.as_deref(), Some("/outputs/stdout.txt"));
+        assert_eq!(executor.stderr.as_deref(), Some("/outputs/stderr.txt"));
+
+        let env = executor.env.unwrap();
+        assert_eq!(env.len(), 1);
+        assert_eq!(env["NAME"], "value");
+    }
+
+    #[test]
+    fn executor_honors_the_working_directory() {
+        let execution = Execution::builder()
+            .image("ubuntu")
+            .args(["pwd"])
+            .working_directory("/data")
+            .try_build()
+            .unwrap();
+
+        let executor = to_executor(&execution);
+        assert_eq!(executor.image, "ubuntu");
+        assert_eq!(executor.command, ["pwd"]);
+        assert_eq!(executor.workdir.as_deref(), Some("/data"));
+    }
+
+    #[test]
+    fn resources_are_requested() {
+        let resources = to_resources(
+            &Resources::builder()
+                .cpu_cores(2u64)
+                .ram_gb(4.0)
+                .disk_gb(100.0)
+                .build(),
+        );
+
+        assert_eq!(resources.cpu_cores, Some(2));
+        assert_eq!(resources.ram_gb, Some(4.0));
+        assert_eq!(resources.disk_gb, Some(100.0));
+        assert_eq!(resources.preemptible, None);
+    }
+
+    #[test]
+    fn tasks_round_trip() {
+        let tes = tes::Task::try_from(task()).unwrap();
+
+        let inputs = tes.inputs.as_ref().unwrap();
+        assert_eq!(inputs[0].content.as_deref(), Some("hello"));
+        assert_eq!(inputs[0].url, None);
+        assert_eq!(inputs[1].url.as_deref(), Some("s3://bucket/reference"));
+        assert!(matches!(inputs[1].r#type, tes::task::file::Type::Directory));
+        let outputs = tes.outputs.as_ref().unwrap();
+        assert_eq!(outputs[0].url, "s3://bucket/result.txt");
+        assert!(matches!(outputs[0].r#type, tes::task::file::Type::File));
+        assert!(matches!(
+            outputs[1].r#type,
+            tes::task::file::Type::Directory
+        ));
+        assert_eq!(tes.resources.as_ref().unwrap().cpu_cores, Some(2));
+        assert_eq!(tes.executors[0].workdir.as_deref(), Some("/inputs"));
+        assert_eq!(
+            tes.volumes.as_deref(),
+            Some(&[String::from("/scratch")][..])
+        );
+
+        assert_eq!(Task::try_from(tes).unwrap(), task());
+    }
+
+    #[test]
+    fn temporary_mount_options_are_dropped() {
+        assert_eq!(to_volume("/scratch:size=1g,ro").unwrap(), "/scratch");
+        assert!(matches!(
+            to_volume("/data:/data:ro"),
+            Err(Error::HostVolume(volume)) if volume == "/data:/data:ro"
+        ));
+    }
+
+    #[test]
+    fn invalid_tes_tasks_are_rejected() {
+        assert!(matches!(
+            Task::try_from(tes::Task::default()),
+            Err(Error::Build(_))
+        ));
+
+        let task = tes::Task {
+            inputs: Some(vec![tes::task::Input {
+                path: String::from("/inputs/missing.txt"),
+                ..Default::default()
+            }]),
+            executors: vec![tes::task::Executor {
+                image: String::from("ubuntu"),
+                command: vec![String::from("true")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(matches!(
+            Task::try_from(task),
+            Err(Error::MissingContents { path }) if path == "/inputs/missing.txt"
+        ));
+
+        let output = tes::task::Output {
+            path: String::from("/outputs/result.txt"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            from_output(output),
+            Err(Error::MissingUrl { path }) if path == "/outputs/result.txt"
+        ));
+
+        let resources = tes::task::Resources {
+            cpu_cores: Some(-1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            from_resources(resources),
+            Err(Error::NegativeCpuCores(-1))
+        ));
+    }
+}
@@ -1,6 +1,14 @@
 //! Tasks that can be run by execution runners.
 
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::hash::Hasher;
+
 use nonempty::NonEmpty;
+use serde::Deserialize;
+use serde::Serialize;
 
 mod builder;
 pub mod execution;
@@ -14,30 +22,54 @@ pub use input::Input;
 pub use output::Output;
 pub use resources::Resources;
 
-/// A task intended for execution.
-#[derive(Clone, Debug)]
+use crate::engine::task::resources::PREEMPTIBLE_MAX_RETRIES;
 
+/// A task intended for execution.
+///
+/// Tasks are compared and hashed by their content, so identical tasks can be
+/// deduplicated or used to key a cache of results.
+///
+/// Tasks can be serialized to and deserialized from formats like JSON or YAML
+/// (e.g., to store task templates on disk). Note that a deserialized task has
+/// not been checked by a [`Builder`], so it should be
+/// [validated](Task::validate) before it is run.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Task {
     /// An optional name.
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 
     /// An optional description.
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
 
     /// An optional list of [`Input`]s.
+    #[serde(skip_serializing_if = "Option::is_none")]
     inputs: Option<NonEmpty<Input>>,
 
     /// An optional list of [`Output`]s.
+    #[serde(skip_serializing_if = "Option::is_none")]
     outputs: Option<NonEmpty<Output>>,
 
     /// An optional set of requested [`Resources`].
+    #[serde(skip_serializing_if = "Option::is_none")]
     resources: Option<Resources>,
 
     /// The list of [`Execution`]s.
     executions: NonEmpty<Execution>,
 
     /// The list of volumes shared across executions in the task
+    #[serde(skip_serializing_if = "Option::is_none")]
     volumes: Option<NonEmpty<String>>,
+
+    /// The maximum number of times to retry the task if an execution fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_retries: Option<u32>,
+
+    /// An optional map of tags (e.g., a cost center or the ID of the workflow
+    /// that spawned the task).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<HashMap<String, String>>,
 }
 
 impl Task {
@@ -71,6 +103,14 @@ impl Task {
         self.resources.as_ref()
     }
 
+    /// Gets the resources requested for an execution of the task.
+    ///
+    /// These are the [resources of the execution](Execution::resources) if it
+    /// has its own; otherwise, they are the resources of the task.
+    pub fn resources_for<'a>(&'a self, execution: &'a Execution) -> Option<&'a Resources> {
+        execution.resources().or(self.resources())
+    }
+
     /// Gets the executions for this task.
     pub fn executions(&self) -> impl Iterator<Item = &Execution> {
         self.executions.iter()
@@ -80,4 +120,409 @@ impl Task {
     pub fn volumes(&self) -> Option<impl Iterator<Item = &String>> {
         self.volumes.as_ref().map(|volumes| volumes.iter())
     }
+
+    /// Gets the maximum number of times the task is retried if an execution
+    /// fails (if specified).
+    pub fn max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    /// Gets the number of times the task is retried if an execution fails.
+    ///
+    /// When no [maximum](Task::max_retries) is specified, a task with
+    /// preemptible resources is retried up to [`PREEMPTIBLE_MAX_RETRIES`]
+    /// times, and any other task is not retried.
+    pub fn retries(&self) -> u32 {
+        self.max_retries.unwrap_or_else(|| {
+            match self.resources().and_then(Resources::preemptible) {
+                Some(true) => PREEMPTIBLE_MAX_RETRIES,
+                _ => 0,
+            }
+        })
+    }
+
+    /// Gets the tags for the task (if any exist).
+    ///
+    /// Tags are arbitrary metadata that backends may attach to the task (e.g.,
+    /// the TES backend forwards them as the tags of the TES task).
+    pub fn tags(&self) -> Option<&HashMap<String, String>> {
+        self.tags.as_ref()
+    }
+
+    /// Checks that the task is well-formed before it is submitted to a
+    /// backend, returning a description of each problem found.
+    ///
+    /// A task is well-formed when every execution has a non-empty image and
+    /// program, every input path is absolute and unique, every directory input
+    /// is sourced from a URL, and no two outputs share a path.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        for (i, execution) in self.executions().enumerate() {
+            if execution.image().trim().is_empty() {
+                problems.push(format!("execution #{i} has an empty image"));
+            }
+
+            if execution.args().head.trim().is_empty() {
+                problems.push(format!("execution #{i} has an empty program"));
+            }
+        }
+
+        for input in self.inputs().into_iter().flatten() {
+            if !input.path().starts_with('/') {
+                problems.push(format!("input path `{}` is not absolute", input.path()));
+            }
+
+            if matches!(input.r#type(), input::Type::Directory)
+                && matches!(input.contents(), input::Contents::Literal(_))
+            {
+                problems.push(format!(
+                    "directory input `{}` has literal contents",
+                    input.path()
+                ));
+            }
+        }
+
+        for path in duplicate_paths(self.inputs().into_iter().flatten().map(Input::path)) {
+            problems.push(format!("input path `{path}` is used more than once"));
+        }
+
+        for path in duplicate_paths(self.outputs().into_iter().flatten().map(Output::path)) {
+            problems.push(format!("output path `{path}` is used more than once"));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Resolves the `~{name}` placeholders within the arguments of the
+    /// executions against the named inputs of the task.
+    ///
+    /// Each placeholder that names an input is replaced by the path of that
+    /// input within the container; any other placeholder is left untouched.
+    /// Runners resolve the placeholders of every task when it is submitted.
+    pub fn resolve_placeholders(mut self) -> Self {
+        let substitutions = self
+            .inputs()
+            .into_iter()
+            .flatten()
+            .filter_map(|input| Some((input.name()?.to_string(), input.path().to_string())))
+            .collect::<HashMap<_, _>>();
+
+        if substitutions.is_empty() {
+            return self;
+        }
+
+        for execution in self.executions.iter_mut() {
+            execution.substitute_placeholders(&substitutions);
+        }
+
+        self
+    }
+}
+
+impl Hash for Task {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.description.hash(state);
+        self.inputs.hash(state);
+        self.outputs.hash(state);
+        self.resources.hash(state);
+        self.executions.hash(state);
+        self.volumes.hash(state);
+        self.max_retries.hash(state);
+
+        // NOTE: tags are unordered, so they are hashed in sorted order.
+        self.tags
+            .as_ref()
+            .map(|tags| tags.iter().collect::<BTreeMap<_, _>>())
+            .hash(state);
+    }
+}
+
+/// Gets each path that occurs more than once (in the order of its second
+/// occurrence).
+pub(crate) fn duplicate_paths<'a>(paths: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    paths
+        .into_iter()
+        .filter(|path| !seen.insert(*path))
+        .collect()
+}
+
+/// Helpers for building tasks in tests.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// Creates an execution of the given arguments in the `ubuntu` image.
+    pub(crate) fn execution(args: impl IntoIterator<Item: Into<String>>) -> Execution {
+        Execution::builder()
+            .image("ubuntu")
+            .args(args)
+            .try_build()
+            .unwrap()
+    }
+
+    /// Creates a task with a single [execution](execution()) of the given
+    /// arguments.
+    pub(crate) fn task(args: impl IntoIterator<Item: Into<String>>) -> Task {
+        Task::builder()
+            .extend_executions([execution(args)])
+            .try_build()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasher as _;
+    use std::hash::RandomState;
+
+    use super::*;
+    use crate::engine::task::testing::execution;
+
+    /// Creates an input with literal contents at the given path.
+    fn input(path: &str) -> Input {
+        Input::builder()
+            .contents(input::Contents::Literal(String::from("hello")))
+            .path(path)
+            .r#type(input::Type::File)
+            .try_build()
+            .unwrap()
+    }
+
+    /// Creates a file output at the given path.
+    fn output(path: &str) -> Output {
+        Output::builder()
+            .url(url::Url::parse("file:///outputs/result.txt").unwrap())
+            .path(path)
+            .r#type(output::Type::File)
+            .try_build()
+            .unwrap()
+    }
+
+    /// Creates a task with the given image, inputs, and outputs.
+    fn task(
+        image: &str,
+        inputs: impl IntoIterator<Item = Input>,
+        outputs: impl IntoIterator<Item = Output>,
+    ) -> Task {
+        Task::builder()
+            .extend_inputs(inputs)
+            .extend_outputs(outputs)
+            .extend_executions([Execution::builder()
+                .image(image)
+                .args(["cat", "/inputs/a.txt"])
+                .try_build()
+                .unwrap()])
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn placeholders_resolve_to_input_paths() {
+        let task = Task::builder()
+            .extend_inputs([Input::builder()
+                .name("reads")
+                .contents(input::Contents::Literal(String::from("ACGT")))
+                .path("/inputs/reads.fq")
+                .r#type(input::Type::File)
+                .try_build()
+                .unwrap()])
+            .extend_executions([execution([
+                "wc",
+                "-l",
+                "~{reads}",
+                "~{missing}",
+                "--in=~{reads}",
+            ])])
+            .try_build()
+            .unwrap()
+            .resolve_placeholders();
+
+        let execution = task.executions().next().unwrap();
+        assert_eq!(
+            execution.args().iter().collect::<Vec<_>>(),
+            [
+                "wc",
+                "-l",
+                "/inputs/reads.fq",
+                "~{missing}",
+                "--in=/inputs/reads.fq"
+            ]
+        );
+    }
+
+    #[test]
+    fn identical_tasks_are_equal() {
+        let a = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["env"])
+                .env("A", "1")
+                .env("B", "2")
+                .try_build()
+                .unwrap()])
+            .resources(Resources::builder().cpu_cores(2u64).ram_gb(4.0).build())
+            .try_build()
+            .unwrap();
+
+        let b = Task::builder()
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["env"])
+                .env("B", "2")
+                .env("A", "1")
+                .try_build()
+                .unwrap()])
+            .resources(Resources::builder().cpu_cores(2u64).ram_gb(4.0).build())
+            .try_build()
+            .unwrap();
+
+        assert_eq!(a, b);
+
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&b));
+
+        let c = Task::builder()
+            .extend_executions(a.executions().cloned())
+            .resources(Resources::builder().cpu_cores(2u64).ram_gb(8.0).build())
+            .try_build()
+            .unwrap();
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn tags_are_extended_and_hashed_in_any_order() {
+        let tagged = |tags: &[(&str, &str)]| {
+            Task::builder()
+                .extend_executions([execution(["env"])])
+                .extend_tags(tags.iter().copied())
+                .try_build()
+                .unwrap()
+        };
+
+        let a = tagged(&[("workflow", "hello"), ("sample", "SJ001")]);
+        let b = tagged(&[("sample", "SJ001"), ("workflow", "hello")]);
+        assert_eq!(a.tags().unwrap().len(), 2);
+        assert_eq!(a, b);
+
+        let hasher = RandomState::new();
+        assert_eq!(hasher.hash_one(&a), hasher.hash_one(&b));
+
+        let c = tagged(&[("workflow", "hello"), ("workflow", "goodbye")]);
+        assert_eq!(c.tags().unwrap()["workflow"], "goodbye");
+        assert_ne!(a, c);
+
+        assert!(tagged(&[]).tags().is_none());
+    }
+
+    #[test]
+    fn tasks_round_trip_through_json_and_yaml() {
+        let task = Task::builder()
+            .name("hello")
+            .extend_inputs([input("/inputs/a.txt")])
+            .extend_outputs([output("/outputs/result.txt")])
+            .extend_executions([Execution::builder()
+                .image("ubuntu")
+                .args(["cat", "/inputs/a.txt"])
+                .env("A", "1")
+                .try_build()
+                .unwrap()])
+            .resources(Resources::builder().cpu_cores(2u64).ram_gb(4.0).build())
+            .try_build()
+            .unwrap();
+
+        let json = serde_json::to_value(&task).unwrap();
+        assert_eq!(json["inputs"][0]["contents"]["literal"], "hello");
+        assert_eq!(json["inputs"][0]["type"], "file");
+        assert_eq!(json["outputs"][0]["url"], "file:///outputs/result.txt");
+        assert_eq!(json["executions"][0]["args"][1], "/inputs/a.txt");
+        assert!(json.get("description").is_none());
+        assert_eq!(serde_json::from_value::<Task>(json).unwrap(), task);
+
+        let yaml = serde_yaml::to_string(&task).unwrap();
+        assert_eq!(serde_yaml::from_str::<Task>(&yaml).unwrap(), task);
+    }
+
+    #[test]
+    fn tasks_without_executions_are_not_deserialized() {
+        assert!(serde_json::from_str::<Task>(r#"{"executions": []}"#).is_err());
+    }
+
+    #[test]
+    fn well_formed_tasks_are_valid() {
+        let task = task(
+            "ubuntu",
+            [input("/inputs/a.txt"), input("/inputs/b.txt")],
+            [output("/outputs/a.txt"), output("/outputs/b.txt")],
+        );
+        assert!(task.validate().is_ok());
+    }
+
+    #[test]
+    fn malformed_tasks_are_reported() {
+        let directory = Input::builder()
+            .contents(input::Contents::Literal(String::from("hello")))
+            .path("/inputs/dir")
+            .r#type(input::Type::Directory)
+            .try_build()
+            .unwrap();
+        let task = task(
+            " ",
+            [input("inputs/a.txt"), input("/inputs/b.txt"), directory],
+            [output("/outputs/a.txt"), output("/outputs/a.txt")],
+        );
+
+        assert_eq!(
+            task.validate().unwrap_err(),
+            [
+                "execution #0 has an empty image",
+                "input path `inputs/a.txt` is not absolute",
+                "directory input `/inputs/dir` has literal contents",
+                "output path `/outputs/a.txt` is used more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_input_paths_are_rejected() {
+        let err = Task::builder()
+            .extend_inputs([input("/inputs/a.txt"), input("/inputs/b.txt")])
+            .extend_inputs([input("/inputs/a.txt")])
+            .extend_executions([execution(["cat", "/inputs/a.txt"])])
+            .try_build()
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            builder::Error::Invalid {
+                field: "inputs",
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid value for 'inputs' in task builder: multiple inputs map to path \
+             '/inputs/a.txt'"
+        );
+    }
+
+    #[test]
+    fn deserialized_tasks_with_duplicate_input_paths_are_reported() {
+        let task = task("ubuntu", [input("/inputs/a.txt")], []);
+        let mut value = serde_json::to_value(&task).unwrap();
+        let inputs = value["inputs"].as_array_mut().unwrap();
+        inputs.push(inputs[0].clone());
+
+        let task: Task = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            task.validate().unwrap_err(),
+            ["input path `/inputs/a.txt` is used more than once"]
+        );
+    }
 }
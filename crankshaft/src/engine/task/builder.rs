@@ -1,7 +1,10 @@
 //! A builder for a [`Task`].
 
+use std::collections::HashMap;
+
 use nonempty::NonEmpty;
 
+use crate::engine::task::duplicate_paths;
 use crate::engine::task::execution::Execution;
 use crate::engine::task::resources::Resources;
 use crate::engine::task::Input;
@@ -16,6 +19,15 @@ pub enum Error {
 
     /// Multiple values were provided for a singular builder field.
     Multiple(&'static str),
+
+    /// A value provided for a builder field was invalid.
+    Invalid {
+        /// The name of the field.
+        field: &'static str,
+
+        /// Why the value is invalid.
+        reason: String,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -27,6 +39,9 @@ impl std::fmt::Display for Error {
             Error::Multiple(field) => {
                 write!(f, "multiple value provided for '{field}' in task builder")
             }
+            Error::Invalid { field, reason } => {
+                write!(f, "invalid value for '{field}' in task builder: {reason}")
+            }
         }
     }
 }
@@ -59,6 +74,12 @@ pub struct Builder {
 
     /// The list of volumes shared among executions
     volumes: Option<NonEmpty<String>>,
+
+    /// An optional maximum number of retries.
+    max_retries: Option<u32>,
+
+    /// An optional map of tags.
+    tags: Option<HashMap<String, String>>,
 }
 
 impl Builder {
@@ -174,6 +195,10 @@ impl Builder {
     }
 
     /// Extends the set of volumes within the [`Builder`].
+    ///
+    /// A volume is either a container path, which is backed by a temporary
+    /// directory, or a `host:container[:ro]` pair, which mounts an existing
+    /// host path into the container (read-only if suffixed with `:ro`).
     pub fn extend_volumes<Iter>(mut self, volumes: Iter) -> Self
     where
         Iter: IntoIterator<Item = String>,
@@ -199,13 +224,62 @@ impl Builder {
         self
     }
 
+    /// Sets the maximum number of times the task is retried if an execution
+    /// fails.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous max retries declarations
+    /// provided to the builder.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Extends the set of tags within the [`Builder`].
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite the values of any tags with the same names
+    /// that were previously provided to the builder.
+    pub fn extend_tags<Iter, K, V>(mut self, tags: Iter) -> Self
+    where
+        Iter: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut new = tags
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .peekable();
+
+        if new.peek().is_some() {
+            self.tags.get_or_insert_with(HashMap::new).extend(new);
+        }
+
+        self
+    }
+
     /// Consumes `self` and attempts to return a built [`Task`].
+    ///
+    /// # Notes
+    ///
+    /// Building fails if two inputs are mapped to the same path within the
+    /// container, as the latter would silently overwrite the former.
     pub fn try_build(self) -> Result<Task> {
         let executors = self
             .executors
             .map(Ok)
             .unwrap_or(Err(Error::Missing("executors")))?;
 
+        let inputs = self.inputs.iter().flatten().map(Input::path);
+        if let Some(path) = duplicate_paths(inputs).first() {
+            return Err(Error::Invalid {
+                field: "inputs",
+                reason: format!("multiple inputs map to path '{path}'"),
+            });
+        }
+
         Ok(Task {
             name: self.name,
             description: self.description,
@@ -214,6 +288,8 @@ impl Builder {
             resources: self.resources,
             executions: executors,
             volumes: self.volumes,
+            max_retries: self.max_retries,
+            tags: self.tags,
         })
     }
 }
@@ -2,15 +2,26 @@
 
 mod builder;
 
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::hash::RandomState;
 
 pub use builder::Builder;
 
 use indexmap::IndexMap;
 use nonempty::NonEmpty;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::engine::service::runner::backend::config::substitute_placeholders;
+use crate::engine::service::runner::backend::config::DEFAULT_LEFT_PLACEHOLDER;
+use crate::engine::service::runner::backend::config::DEFAULT_RIGHT_PLACEHOLDER;
+use crate::engine::task::Resources;
 
 /// An execution.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Execution {
     /// The container image.
     image: String,
@@ -19,22 +30,31 @@ pub struct Execution {
     args: NonEmpty<String>,
 
     /// The working directory, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
     workdir: Option<String>,
 
     /// The path inside the container to a file whose contents will be piped to
     /// the standard input, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
     stdin: Option<String>,
 
     /// The path inside the container to a file where the contents of the
     /// standard output stream will be written, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
     stdout: Option<String>,
 
     /// The path inside the container to a file where the contents of the
     /// standard error stream will be written, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
     stderr: Option<String>,
 
     /// A map of environment variables, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
     env: Option<IndexMap<String, String>>,
+
+    /// The requested resources, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<Resources>,
 }
 
 impl Execution {
@@ -49,10 +69,34 @@ impl Execution {
     }
 
     /// The arguments to the execution.
+    ///
+    /// The first argument is the program to run and the remaining arguments
+    /// are passed to it. Note that backends hand these to the underlying
+    /// system differently: the Docker and TES backends pass them along as a
+    /// command vector, whereas the generic backend joins them into a single
+    /// command string that is substituted into its submit script.
+    ///
+    /// Arguments may reference the named inputs of their task with `~{name}`
+    /// placeholders (see [`Task::resolve_placeholders()`]).
+    ///
+    /// [`Task::resolve_placeholders()`]: crate::engine::Task::resolve_placeholders
     pub fn args(&self) -> &NonEmpty<String> {
         &self.args
     }
 
+    /// Replaces the `~{name}` placeholders within the arguments that have a
+    /// substitution.
+    pub(crate) fn substitute_placeholders(&mut self, substitutions: &HashMap<String, String>) {
+        for arg in self.args.iter_mut() {
+            *arg = substitute_placeholders(
+                arg,
+                substitutions,
+                DEFAULT_LEFT_PLACEHOLDER,
+                DEFAULT_RIGHT_PLACEHOLDER,
+            );
+        }
+    }
+
     /// The working directory.
     pub fn workdir(&self) -> Option<&String> {
         self.workdir.as_ref()
@@ -77,4 +121,32 @@ impl Execution {
     pub fn env(&self) -> Option<&IndexMap<String, String, RandomState>> {
         self.env.as_ref()
     }
+
+    /// The resources requested for the execution (if configured).
+    ///
+    /// These override the [resources of the task](crate::engine::Task::resources)
+    /// for this execution alone; an execution without its own resources uses
+    /// those of the task (see [`Task::resources_for()`](crate::engine::Task::resources_for)).
+    pub fn resources(&self) -> Option<&Resources> {
+        self.resources.as_ref()
+    }
+}
+
+impl Hash for Execution {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.image.hash(state);
+        self.args.hash(state);
+        self.workdir.hash(state);
+        self.stdin.hash(state);
+        self.stdout.hash(state);
+        self.stderr.hash(state);
+
+        // NOTE: the equality of environment variables does not depend on the
+        // order in which they were added, so neither can their hash.
+        self.env
+            .as_ref()
+            .map(|env| env.iter().collect::<BTreeMap<_, _>>())
+            .hash(state);
+        self.resources.hash(state);
+    }
 }
@@ -4,6 +4,7 @@ use indexmap::IndexMap;
 use nonempty::NonEmpty;
 
 use crate::engine::task::execution::Execution;
+use crate::engine::task::Resources;
 
 /// An error related to a [`Builder`].
 #[derive(Debug)]
@@ -54,6 +55,9 @@ pub struct Builder {
 
     /// A map of environment variables, if configured.
     env: Option<IndexMap<String, String>>,
+
+    /// The requested resources, if configured.
+    resources: Option<Resources>,
 }
 
 impl Builder {
@@ -76,6 +80,9 @@ impl Builder {
 
     /// Adds args to the [`Builder`].
     ///
+    /// The first arg is the program to run and the remaining args are passed
+    /// to it (see [`Execution::args()`](crate::engine::task::Execution::args)).
+    ///
     /// # Notes
     ///
     /// This will append to any previously assigned args (use
@@ -105,6 +112,10 @@ impl Builder {
 
     /// Adds a working directory to the [`Builder`].
     ///
+    /// The working directory is exposed by
+    /// [`Execution::workdir()`](crate::engine::task::Execution::workdir) and is
+    /// honored by every backend.
+    ///
     /// # Notes
     ///
     /// This will silently overwrite any previous working directories provided
@@ -114,6 +125,16 @@ impl Builder {
         self
     }
 
+    /// Adds a working directory to the [`Builder`].
+    ///
+    /// This is an alias for [`working_directory()`](Self::working_directory)
+    /// that matches the name of the
+    /// [`Execution::workdir()`](crate::engine::task::Execution::workdir)
+    /// getter.
+    pub fn workdir(self, value: impl Into<String>) -> Self {
+        self.working_directory(value)
+    }
+
     /// Adds a file with which to stream standard in from.
     ///
     /// # Notes
@@ -160,10 +181,30 @@ impl Builder {
         self
     }
 
+    /// Adds a set of requested resources to the [`Builder`].
+    ///
+    /// These override the resources of the task for this execution (see
+    /// [`Execution::resources()`](crate::engine::task::Execution::resources)).
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any previous resources declarations
+    /// provided to the builder.
+    pub fn resources(mut self, resources: impl Into<Resources>) -> Self {
+        self.resources = Some(resources.into());
+        self
+    }
+
     /// Consumes `self` and attempts to return a built [`Execution`].
     pub fn try_build(self) -> Result<Execution> {
         let image = self.image.map(Ok).unwrap_or(Err(Error::Missing("image")))?;
-        let args = self.args.map(Ok).unwrap_or(Err(Error::Missing("args")))?;
+        let args = self
+            .args
+            // NOTE: the first arg is the program to run, so an empty (or
+            // blank) program is treated as if no args were provided at all.
+            .filter(|args| !args.head.trim().is_empty())
+            .map(Ok)
+            .unwrap_or(Err(Error::Missing("args")))?;
 
         Ok(Execution {
             image,
@@ -173,6 +214,37 @@ impl Builder {
             stdout: self.stdout,
             stderr: self.stderr,
             env: self.env,
+            resources: self.resources,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_programs_are_rejected() {
+        let result = Builder::default()
+            .image("ubuntu")
+            .args(["", "hello"])
+            .try_build();
+        assert!(matches!(result, Err(Error::Missing("args"))));
+
+        let result = Builder::default().image("ubuntu").args([" "]).try_build();
+        assert!(matches!(result, Err(Error::Missing("args"))));
+    }
+
+    #[test]
+    fn workdir_is_an_alias_for_working_directory() {
+        let execution = Builder::default()
+            .image("ubuntu")
+            .args(["pwd"])
+            .working_directory("/first")
+            .workdir("/second")
+            .try_build()
+            .unwrap();
+
+        assert_eq!(execution.workdir().map(String::as_str), Some("/second"));
+    }
+}
@@ -6,11 +6,17 @@ use std::path::PathBuf;
 
 pub use builder::Builder;
 
+use regex::RegexSet;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde::Serialize;
 use tokio::{fs::File, io::AsyncReadExt};
 use url::Url;
 
 /// A type of input.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Type {
     /// A file.
     File,
@@ -20,7 +26,11 @@ pub enum Type {
 }
 
 /// The source of an input.
-#[derive(Clone, Debug)]
+///
+/// When serialized, the contents are keyed by their source (i.e., `url` or
+/// `literal`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Contents {
     /// Contents sourced from a URL.
     URL(Url),
@@ -37,12 +47,14 @@ impl From<PathBuf> for Contents {
 }
 
 /// An input to a task.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Input {
     /// A name.
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 
     /// A description.
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
 
     /// The contents.
@@ -53,6 +65,10 @@ pub struct Input {
 
     /// The type of the input.
     r#type: Type,
+
+    /// The glob patterns of the paths to leave out of a directory input.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exclusions: Vec<String>,
 }
 
 impl Input {
@@ -86,7 +102,31 @@ impl Input {
         &self.r#type
     }
 
+    /// The glob patterns of the paths to leave out of a directory input.
+    ///
+    /// Patterns are matched against paths relative to the root of the
+    /// directory, in the style of a `.dockerignore` file: `*` matches any
+    /// sequence of characters other than `/`, `?` matches any single
+    /// character other than `/`, and `**` matches any number of directories.
+    /// An excluded directory is left out along with everything within it.
+    ///
+    /// Exclusions only apply to directory inputs that are uploaded by the
+    /// backend (e.g., Docker); they are ignored for file inputs.
+    pub fn exclusions(&self) -> &[String] {
+        &self.exclusions
+    }
+
+    /// Compiles the [exclusions](Self::exclusions) into a set that matches
+    /// the relative paths (with `/` separators) to leave out.
+    pub fn exclusion_set(&self) -> Result<RegexSet, regex::Error> {
+        RegexSet::new(self.exclusions.iter().map(|glob| glob_to_regex(glob)))
+    }
+
     /// Fetch file contents
+    ///
+    /// `http(s)://` URLs are sent a `GET` request and `s3://` URLs are sent a
+    /// `GET` request at their public address (see
+    /// [`content_length`](Self::content_length)).
     pub async fn fetch(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
         match &self.contents {
             Contents::Literal(content) => Ok(content.as_bytes().to_vec()),
@@ -98,10 +138,213 @@ impl Input {
                     file.read_to_end(&mut contents).await?;
                     Ok(contents)
                 }
-                "http" | "https" => todo!("HTTP(S) URL support not implemented"),
-                "s3" => todo!("S3 URL support not implemented"),
+                "http" | "https" => Ok(get_contents(url.clone()).await?),
+                "s3" => {
+                    let public = public_s3_url(url)?;
+                    get_contents(public).await.map_err(|e| s3_error(url, e))
+                }
                 _ => Err("Unsupported URL scheme".into()),
             },
         }
     }
+
+    /// Gets the size of the contents in bytes without fetching them.
+    ///
+    /// Files are inspected with `stat`, while `http(s)://` URLs are sent a
+    /// `HEAD` request and `s3://` URLs are sent a `HEAD` request at their
+    /// public `https://<bucket>.s3.amazonaws.com/<key>` address. Returns `None`
+    /// if the size is unknown (e.g., the server does not report a
+    /// `Content-Length`).
+    ///
+    /// Requests to S3 are not signed, so only objects in public buckets can be
+    /// sized; an object in a private bucket is reported as an error.
+    pub async fn content_length(&self) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        match &self.contents {
+            Contents::Literal(content) => Ok(Some(content.len() as u64)),
+            Contents::URL(url) => match url.scheme() {
+                "file" => {
+                    let path = url.to_file_path().map_err(|_| "Invalid file path")?;
+                    Ok(Some(tokio::fs::metadata(path).await?.len()))
+                }
+                "http" | "https" => Ok(head_content_length(url.clone()).await?),
+                "s3" => {
+                    let public = public_s3_url(url)?;
+                    head_content_length(public)
+                        .await
+                        .map_err(|e| s3_error(url, e))
+                }
+                _ => Err("Unsupported URL scheme".into()),
+            },
+        }
+    }
+}
+
+/// Translates a `.dockerignore`-style glob pattern into an anchored regular
+/// expression.
+fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.trim_matches('/');
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+/// Gets the public `https://<bucket>.s3.amazonaws.com/<key>` address of an
+/// `s3://` URL.
+fn public_s3_url(url: &Url) -> Result<Url, Box<dyn std::error::Error>> {
+    let bucket = url.host_str().ok_or("Invalid S3 URL: missing bucket")?;
+    Ok(Url::parse(&format!("https://{bucket}.s3.amazonaws.com"))?.join(url.path())?)
+}
+
+/// Converts an error from an unsigned request to S3, explaining that a
+/// refused request is likely for an object that is not public.
+fn s3_error(url: &Url, error: reqwest::Error) -> Box<dyn std::error::Error> {
+    match error.status() {
+        Some(StatusCode::FORBIDDEN) => format!(
+            "access to `{url}` was denied: requests to S3 are not signed, so only objects in \
+             public buckets are supported"
+        )
+        .into(),
+        _ => error.into(),
+    }
+}
+
+/// Sends a `GET` request to a URL and reads the body of the response.
+async fn get_contents(url: Url) -> reqwest::Result<Vec<u8>> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Sends a `HEAD` request to a URL and reads the `Content-Length` of the
+/// response (if any).
+async fn head_content_length(url: Url) -> reqwest::Result<Option<u64>> {
+    let response = reqwest::Client::new()
+        .head(url)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // NOTE: `Response::content_length` reports the length of the (empty) body
+    // of a `HEAD` response, so the header is read directly.
+    Ok(response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use tokio::io::AsyncWriteExt as _;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Creates a file input with the given contents.
+    fn input(contents: impl Into<Contents>) -> Input {
+        Input::builder()
+            .contents(contents)
+            .path("/inputs/a.txt")
+            .r#type(Type::File)
+            .try_build()
+            .unwrap()
+    }
+
+    #[test]
+    fn exclusions_match_relative_paths() {
+        let input = Input::builder()
+            .contents(Contents::Literal(String::new()))
+            .path("/inputs/dir")
+            .r#type(Type::Directory)
+            .extend_exclusions([".git", "**/*.tmp", "cache/?/", "/build"])
+            .try_build()
+            .unwrap();
+        let set = input.exclusion_set().unwrap();
+
+        for path in [".git", "a.tmp", "src/deep/b.tmp", "cache/a", "build"] {
+            assert!(set.is_match(path), "`{path}` should be excluded");
+        }
+
+        for path in ["src/.git", "a.tmp.txt", "cache/ab", "cache", "src/build"] {
+            assert!(!set.is_match(path), "`{path}` should be included");
+        }
+    }
+
+    #[tokio::test]
+    async fn content_length_of_literals_and_files() {
+        let literal = input(Contents::Literal(String::from("hello")));
+        assert_eq!(literal.content_length().await.unwrap(), Some(5));
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"hello, world!").unwrap();
+        let file = input(file.path().to_path_buf());
+        assert_eq!(file.content_length().await.unwrap(), Some(13));
+    }
+
+    #[tokio::test]
+    async fn content_length_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1234\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let url = Url::parse(&format!("http://{addr}/file.txt")).unwrap();
+        let input = input(Contents::URL(url));
+        assert_eq!(input.content_length().await.unwrap(), Some(1234));
+    }
+
+    #[tokio::test]
+    async fn fetch_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+                .await
+                .unwrap();
+        });
+
+        let url = Url::parse(&format!("http://{addr}/file.txt")).unwrap();
+        let input = input(Contents::URL(url));
+        assert_eq!(input.fetch().await.unwrap(), b"hello");
+    }
+
+    #[test]
+    fn s3_urls_are_sent_to_their_public_address() {
+        let url = Url::parse("s3://bucket/path/to/file.txt").unwrap();
+        assert_eq!(
+            public_s3_url(&url).unwrap().as_str(),
+            "https://bucket.s3.amazonaws.com/path/to/file.txt"
+        );
+    }
 }
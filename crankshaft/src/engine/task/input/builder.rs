@@ -43,6 +43,9 @@ pub struct Builder {
 
     /// The type of the input.
     r#type: Option<Type>,
+
+    /// The glob patterns of the paths to leave out of a directory input.
+    exclusions: Vec<String>,
 }
 
 impl Builder {
@@ -100,6 +103,19 @@ impl Builder {
         self
     }
 
+    /// Extends the set of exclusions within the [`Builder`].
+    ///
+    /// See [`Input::exclusions()`] for how the glob patterns are matched.
+    pub fn extend_exclusions<Iter>(mut self, exclusions: Iter) -> Self
+    where
+        Iter: IntoIterator,
+        Iter::Item: Into<String>,
+    {
+        self.exclusions
+            .extend(exclusions.into_iter().map(Into::into));
+        self
+    }
+
     /// Consumes `self` and attempts to return a built [`Input`].
     pub fn try_build(self) -> Result<Input> {
         let contents = self
@@ -116,6 +132,7 @@ impl Builder {
             contents,
             path,
             r#type,
+            exclusions: self.exclusions,
         })
     }
 }
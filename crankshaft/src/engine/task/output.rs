@@ -4,10 +4,13 @@ mod builder;
 
 pub use builder::Builder;
 
+use serde::Deserialize;
+use serde::Serialize;
 use url::Url;
 
 /// A type of task output.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Type {
     /// A file.
     File,
@@ -17,12 +20,14 @@ pub enum Type {
 }
 
 /// A task output.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct Output {
     /// An optional name.
+    #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 
     /// An optional description.
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
 
     /// The URL to copy the output to when complete.
@@ -36,6 +41,11 @@ pub struct Output {
 }
 
 impl Output {
+    /// Gets a new builder for an [`Output`].
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
     /// The name of the output (if it exists).
     pub fn name(&self) -> Option<&str> {
         self.name.as_deref()
@@ -2,39 +2,64 @@
 
 mod builder;
 
-use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
 
 use bollard::secret::HostConfig;
 pub use builder::Builder;
 
 use nonempty::NonEmpty;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The number of times a task with preemptible resources is retried when no
+/// maximum number of retries is specified (see [`Task::retries()`]).
+///
+/// [`Task::retries()`]: crate::engine::Task::retries
+pub const PREEMPTIBLE_MAX_RETRIES: u32 = 3;
 
 /// A set of requested resources.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Resources {
     /// The number of CPU cores requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
     cpu_cores: Option<u64>,
 
     /// Whether or not the task may use preemptible resources.
+    #[serde(skip_serializing_if = "Option::is_none")]
     preemptible: Option<bool>,
 
     /// The requested random access memory size in gigabytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
     ram_gb: Option<f64>,
 
     /// The requested disk size in gigabytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
     disk_gb: Option<f64>,
 
     /// The associated compute zones.
+    #[serde(skip_serializing_if = "Option::is_none")]
     zones: Option<NonEmpty<String>>,
 }
 
 impl Resources {
+    /// Gets a new resources builder.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
     /// A number of CPU cores.
     pub fn cpu_cores(&self) -> Option<u64> {
         self.cpu_cores
     }
 
     /// Whether the instance should be preemptible.
+    ///
+    /// On cloud backends, this is a real scheduling attribute. Local Docker has
+    /// no notion of preemption, so there it is only a best-effort hint: the
+    /// task is made eligible for retries (see [`PREEMPTIBLE_MAX_RETRIES`]).
+    ///
+    /// [`PREEMPTIBLE_MAX_RETRIES`]: crate::engine::task::resources::PREEMPTIBLE_MAX_RETRIES
     pub fn preemptible(&self) -> Option<bool> {
         self.preemptible
     }
@@ -45,6 +70,11 @@ impl Resources {
     }
 
     /// The amount of disk space in gigabytes.
+    ///
+    /// Backends that schedule onto their own machines (e.g., TES) can always
+    /// honor this. The Docker backend can only limit the size of a container
+    /// when the storage driver of the daemon supports it; otherwise, the
+    /// request is ignored with a warning.
     pub fn disk_gb(&self) -> Option<f64> {
         self.disk_gb
     }
@@ -53,8 +83,49 @@ impl Resources {
     pub fn zones(&self) -> Option<&NonEmpty<String>> {
         self.zones.as_ref()
     }
+
+    /// Gets the fields that identify the resources.
+    ///
+    /// Floating point numbers are not [`Eq`] or [`Hash`], so the sizes are
+    /// compared by their bits instead.
+    #[allow(clippy::type_complexity)]
+    fn key(
+        &self,
+    ) -> (
+        Option<u64>,
+        Option<bool>,
+        Option<u64>,
+        Option<u64>,
+        Option<&NonEmpty<String>>,
+    ) {
+        (
+            self.cpu_cores,
+            self.preemptible,
+            self.ram_gb.map(f64::to_bits),
+            self.disk_gb.map(f64::to_bits),
+            self.zones.as_ref(),
+        )
+    }
+}
+
+impl PartialEq for Resources {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
 }
 
+impl Eq for Resources {}
+
+impl Hash for Resources {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Converts a set of [`Resources`] to a Docker [`HostConfig`].
+///
+/// Note that the requested disk size is not included, as whether it can be
+/// honored depends on the storage driver of the Docker daemon.
 impl From<&Resources> for HostConfig {
     fn from(resources: &Resources) -> Self {
         let mut host_config = HostConfig::default();
@@ -66,12 +137,6 @@ impl From<&Resources> for HostConfig {
             host_config.cpu_count = Some(cpu_cores as i64);
         }
 
-        if let Some(disk_gb) = resources.disk_gb() {
-            let mut storage_opt: HashMap<String, String> = HashMap::new();
-            storage_opt.insert("size".to_string(), disk_gb.to_string());
-            host_config.storage_opt = Some(storage_opt);
-        }
-
         host_config
     }
 }
@@ -1,7 +1,6 @@
 //! Builders for a [`Resources`].
 
 use nonempty::NonEmpty;
-use tracing::warn;
 
 use crate::engine::task::resources::Resources;
 
@@ -39,6 +38,9 @@ impl Builder {
     /// Sets whether the request resources are preemptible or not within the
     /// [`Builder`].
     ///
+    /// This is a best-effort hint on local Docker, where it only makes the
+    /// task eligible for retries (see [`Resources::preemptible()`]).
+    ///
     /// # Notes
     ///
     /// This will silently overwrite any previous preemptible designation
@@ -65,12 +67,8 @@ impl Builder {
     ///
     /// This will silently overwrite any previously requested amount of disk
     /// space provided to the builder.
-    pub fn disk_gb(self, _: impl Into<f64>) -> Self {
-        warn!(
-            "setting disk space does not work on containers unless \
-            XFS is used on the host machine"
-        );
-
+    pub fn disk_gb(mut self, value: impl Into<f64>) -> Self {
+        self.disk_gb = Some(value.into());
         self
     }
 
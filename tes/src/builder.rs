@@ -0,0 +1,139 @@
+//! Builders for a [`Client`].
+
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use reqwest_middleware::Middleware;
+
+use crate::logging::LoggingMiddleware;
+use crate::Client;
+use crate::Result;
+
+/// The default number of times a transient failure is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// A builder for a [`Client`].
+pub struct Builder {
+    /// The base URL.
+    url: String,
+
+    /// The headers sent with every request.
+    headers: HeaderMap,
+
+    /// The number of times a transient failure is retried.
+    max_retries: u32,
+
+    /// Whether the creation of a task is retried.
+    retry_create_task: bool,
+
+    /// The additional middleware, in the order it was added.
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl std::fmt::Debug for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builder")
+            .field("url", &self.url)
+            .field("max_retries", &self.max_retries)
+            .field("retry_create_task", &self.retry_create_task)
+            .field("middleware", &self.middleware.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Builder {
+    /// Creates a new [`Builder`] for a client of the service at `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: HeaderMap::new(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_create_task: true,
+            middleware: Vec::new(),
+        }
+    }
+
+    /// Sets the headers sent with every request.
+    ///
+    /// # Notes
+    ///
+    /// This will silently overwrite any headers previously provided to the
+    /// builder.
+    pub fn default_headers(mut self, headers: impl Into<HeaderMap>) -> Self {
+        self.headers = headers.into();
+        self
+    }
+
+    /// Sets the number of times a transient failure is retried.
+    ///
+    /// # Notes
+    ///
+    /// This overwrites the [default number of retries](DEFAULT_MAX_RETRIES).
+    pub fn max_retries(mut self, value: u32) -> Self {
+        self.max_retries = value;
+        self
+    }
+
+    /// Sets whether the creation of a task is retried (`true` by default).
+    ///
+    /// Retries of [`Client::create_task()`] first look for a task that was
+    /// created by a failed attempt, which relies on the server supporting
+    /// the filtering of tasks by tag. Disable them for servers that do not.
+    pub fn retry_create_task(mut self, value: bool) -> Self {
+        self.retry_create_task = value;
+        self
+    }
+
+    /// Adds a middleware that sees every request and response of the client.
+    ///
+    /// Middleware runs after the retry middleware, so it sees every attempt
+    /// of a retried request.
+    pub fn with(mut self, middleware: impl Middleware) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Adds the built-in [`LoggingMiddleware`] (without bodies) when `debug`
+    /// is `true`.
+    pub fn debug(self, debug: bool) -> Self {
+        if debug {
+            self.with(LoggingMiddleware::new())
+        } else {
+            self
+        }
+    }
+
+    /// Consumes `self` and attempts to build a [`Client`].
+    pub fn try_build(self) -> Result<Client> {
+        let client = reqwest::ClientBuilder::new()
+            .default_headers(self.headers)
+            .build()?;
+
+        let retry_policy = reqwest_retry::policies::ExponentialBackoff::builder()
+            .build_with_max_retries(self.max_retries);
+        let retrying = reqwest_middleware::ClientBuilder::new(client.clone()).with(
+            reqwest_retry::RetryTransientMiddleware::new_with_policy(retry_policy),
+        );
+
+        let (retrying, create) = self.middleware.into_iter().fold(
+            (retrying, reqwest_middleware::ClientBuilder::new(client)),
+            |(retrying, create), middleware| {
+                (
+                    retrying.with_arc(middleware.clone()),
+                    create.with_arc(middleware),
+                )
+            },
+        );
+
+        Ok(Client {
+            url: self.url,
+            client: retrying.build(),
+            create_client: create.build(),
+            create_retries: if self.retry_create_task {
+                self.max_retries
+            } else {
+                0
+            },
+        })
+    }
+}
@@ -1,19 +1,38 @@
 //! Task execution service.
 
+use std::time::Duration;
+
 use reqwest::header;
 use reqwest::header::HeaderMap;
 use reqwest::StatusCode;
 use reqwest_middleware::ClientWithMiddleware;
 use reqwest_middleware::Error;
 
+pub mod builder;
+pub mod logging;
 pub mod responses;
 pub mod task;
 
+pub use builder::Builder;
+pub use logging::LoggingMiddleware;
 pub use task::Task;
 
 /// A [`Result`](std::result::Result) with an [`Error`].
 type Result<T> = std::result::Result<T, Error>;
 
+/// The tag under which [`Client::create_task()`] stores the idempotency key
+/// of a task.
+pub const IDEMPOTENCY_KEY_TAG: &str = "idempotency_key";
+
+/// The header in which [`Client::create_task()`] sends the idempotency key of
+/// a task.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The delay before the first retry of a failed task creation.
+///
+/// The delay doubles with every further retry.
+const CREATE_TASK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 /// A task execution service (TES) client.
 #[derive(Debug)]
 pub struct Client {
@@ -22,27 +41,26 @@ pub struct Client {
 
     /// The client.
     client: ClientWithMiddleware,
+
+    /// The client used to create tasks, which never retries on its own.
+    create_client: ClientWithMiddleware,
+
+    /// The number of times the creation of a task is retried.
+    create_retries: u32,
 }
 
 impl Client {
-    /// Creates a new [`Client`].
+    /// Creates a new [`Client`] with the default settings.
+    ///
+    /// Use [`Client::builder()`] to configure retries or add middleware (such
+    /// as a [`LoggingMiddleware`]).
     pub fn new(url: impl Into<String>, headers: impl Into<HeaderMap>) -> Result<Self> {
-        let url = url.into();
-        let headers = headers.into();
-
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .build()?;
-
-        let retry_policy =
-            reqwest_retry::policies::ExponentialBackoff::builder().build_with_max_retries(3);
-        let client = reqwest_middleware::ClientBuilder::new(client)
-            .with(reqwest_retry::RetryTransientMiddleware::new_with_policy(
-                retry_policy,
-            ))
-            .build();
-
-        Ok(Self { url, client })
+        Self::builder(url).default_headers(headers).try_build()
+    }
+
+    /// Gets a [`Builder`] for a client of the service at `url`.
+    pub fn builder(url: impl Into<String>) -> Builder {
+        Builder::new(url)
     }
 
     /// Returns whether the URL is healthy by hitting the `GET /service-info`
@@ -59,39 +77,262 @@ impl Client {
     }
 
     /// Attempts to create a task.
-    pub async fn create_task(&self, task: Task) -> Result<String> {
+    ///
+    /// Every task is submitted with an idempotency key, both in the
+    /// [`IDEMPOTENCY_KEY_HEADER`] header and in the [`IDEMPOTENCY_KEY_TAG`]
+    /// tag. A key already present in the tags is reused; otherwise, a random
+    /// one is generated.
+    ///
+    /// # Notes
+    ///
+    /// A `POST` that fails after the server has created the task would create
+    /// a duplicate task if blindly retried, so task creation bypasses the
+    /// retry middleware. Instead, before each retry, the tasks carrying the
+    /// idempotency key are listed and the ID of an existing one is returned.
+    /// Retries can be disabled entirely with
+    /// [`Builder::retry_create_task()`].
+    pub async fn create_task(&self, mut task: Task) -> Result<String> {
         let url = format!("{}tasks", self.url);
 
+        let key = task
+            .tags
+            .get_or_insert_with(Default::default)
+            .entry(IDEMPOTENCY_KEY_TAG.to_string())
+            .or_insert_with(|| format!("{:032x}", rand::random::<u128>()))
+            .clone();
+
         let mut headers = header::HeaderMap::new();
         headers.insert(
             "Content-Type",
             header::HeaderValue::from_static("application/json"),
         );
+        if let Ok(value) = header::HeaderValue::from_str(&key) {
+            headers.insert(IDEMPOTENCY_KEY_HEADER, value);
+        }
 
         let json = serde_json::to_string(&task).unwrap();
 
+        let mut delay = CREATE_TASK_RETRY_DELAY;
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .create_client
+                .post(&url)
+                .body(json.clone())
+                .headers(headers.clone())
+                .send()
+                .await
+                .and_then(|res| res.error_for_status().map_err(Error::from));
+
+            let err = match result {
+                Ok(res) => {
+                    let text = &res.text().await?;
+                    return serde_json::from_str::<responses::CreateTask>(text)
+                        .map(|created| created.id)
+                        .map_err(Error::middleware);
+                }
+                Err(e) => e,
+            };
+
+            if attempt >= self.create_retries || !is_transient(&err) {
+                return Err(err);
+            }
+
+            attempt += 1;
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+
+            if let Ok(Some(id)) = self.find_task(&key).await {
+                return Ok(id);
+            }
+        }
+    }
+
+    /// Finds the ID of a task with the given idempotency key, if one exists.
+    async fn find_task(&self, key: &str) -> Result<Option<String>> {
+        let url = format!("{}tasks", self.url);
         let res = self
             .client
-            .post(&url)
-            .body(json)
-            .headers(headers.clone())
+            .get(&url)
+            .query(&[
+                ("tag_key", IDEMPOTENCY_KEY_TAG),
+                ("tag_value", key),
+                ("view", "MINIMAL"),
+            ])
             .send()
-            .await?;
-
+            .await?
+            .error_for_status()?;
         let text = &res.text().await?;
+        let list = match serde_json::from_str::<responses::ListTasks>(text) {
+            Ok(list) => list,
+            Err(_) => return Ok(None),
+        };
 
-        Ok(serde_json::from_str::<responses::CreateTask>(text)
-            .unwrap()
-            .id)
+        Ok(list.tasks.into_iter().next().map(|task| task.id))
+    }
+
+    /// Cancels a task.
+    pub async fn cancel_task(&self, id: &str) -> Result<()> {
+        let url = format!("{}tasks/{}:cancel", self.url, id);
+        self.client.post(&url).send().await?.error_for_status()?;
+
+        Ok(())
     }
 
     /// Gets a task.
     pub async fn get_task(&self, id: &str) -> Result<Task> {
         let url = format!("{}tasks/{}?view=FULL", self.url, id);
-        let res = self.client.get(&url).send().await?;
+        let res = self.client.get(&url).send().await?.error_for_status()?;
         let text = &res.text().await?;
-        let task: Task = serde_json::from_str(text).unwrap();
 
-        Ok(task)
+        serde_json::from_str(text).map_err(Error::middleware)
+    }
+}
+
+/// Returns whether a failed request may succeed if it is retried.
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.is_request()
+                || e.status().is_some_and(|status| {
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                })
+        }
+        Error::Middleware(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use tokio::io::AsyncReadExt as _;
+    use tokio::io::AsyncWriteExt as _;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Starts a TES server that replies to each request line with the
+    /// response returned by `respond`, recording the request lines.
+    ///
+    /// Returns the base URL of the server and the recorded request lines.
+    async fn server(
+        respond: impl Fn(&str) -> (u16, &'static str) + Send + 'static,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                // NOTE: the whole request is read (including the body) so
+                // that closing the connection does not reset it.
+                let mut request = Vec::new();
+                let mut buffer = [0; 4096];
+                let line = loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+
+                    let length = head
+                        .lines()
+                        .filter_map(|line| line.split_once(": "))
+                        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                        .map_or(0, |(_, value)| value.parse().unwrap());
+
+                    if read == 0 || body.len() >= length {
+                        break head.lines().next().unwrap_or_default().to_string();
+                    }
+                };
+
+                let (status, body) = respond(&line);
+                recorded.lock().unwrap().push(line);
+
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (format!("http://{addr}/"), requests)
+    }
+
+    /// Counts the requests whose request line starts with `prefix`.
+    fn count(requests: &Mutex<Vec<String>>, prefix: &str) -> usize {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.starts_with(prefix))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn failed_creations_reuse_a_task_created_by_the_server() {
+        let (url, requests) = server(|line| {
+            if line.starts_with("POST /tasks ") {
+                (503, "{}")
+            } else if line.starts_with("GET /tasks?tag_key=idempotency_key&tag_value=key-1&") {
+                (200, r#"{"tasks":[{"id":"task-1"}]}"#)
+            } else {
+                (404, "{}")
+            }
+        })
+        .await;
+
+        let task = Task {
+            tags: Some([(IDEMPOTENCY_KEY_TAG.to_string(), String::from("key-1"))].into()),
+            ..Default::default()
+        };
+
+        let client = Client::builder(url).max_retries(1).try_build().unwrap();
+        assert_eq!(client.create_task(task).await.unwrap(), "task-1");
+        assert_eq!(count(&requests, "POST /tasks "), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_creations_are_retried_when_no_task_was_created() {
+        let posts = Mutex::new(0);
+        let (url, requests) = server(move |line| {
+            if line.starts_with("POST /tasks ") {
+                let mut posts = posts.lock().unwrap();
+                *posts += 1;
+                if *posts == 1 {
+                    (503, "{}")
+                } else {
+                    (200, r#"{"id":"task-2"}"#)
+                }
+            } else if line.starts_with("GET /tasks?") {
+                (200, r#"{"tasks":[]}"#)
+            } else {
+                (404, "{}")
+            }
+        })
+        .await;
+
+        let client = Client::builder(url).max_retries(1).try_build().unwrap();
+        assert_eq!(client.create_task(Task::default()).await.unwrap(), "task-2");
+        assert_eq!(count(&requests, "POST /tasks "), 2);
+        assert_eq!(count(&requests, "GET /tasks?"), 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_creation_responses_are_errors() {
+        let (url, _) = server(|_| (200, "not json")).await;
+
+        let client = Client::new(url, HeaderMap::new()).unwrap();
+        assert!(client.create_task(Task::default()).await.is_err());
     }
 }
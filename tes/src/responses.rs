@@ -7,3 +7,21 @@ pub struct CreateTask {
     /// The ID of the created task.
     pub id: String,
 }
+
+/// A task within a response from `GET /tasks` with the `MINIMAL` view.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MinimalTask {
+    /// The ID of the task.
+    pub id: String,
+}
+
+/// A response from `GET /tasks`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ListTasks {
+    /// The listed tasks.
+    #[serde(default)]
+    pub tasks: Vec<MinimalTask>,
+
+    /// The token for the next page of tasks, if there is one.
+    pub next_page_token: Option<String>,
+}
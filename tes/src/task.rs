@@ -49,14 +49,46 @@ pub enum State {
     /// A task that has been cancelled.
     #[serde(rename = "CANCELED")]
     Canceled,
+
+    /// A task that is being cancelled.
+    #[serde(rename = "CANCELING")]
+    Canceling,
+
+    /// A task that was stopped because the system reclaimed its resources.
+    #[serde(rename = "PREEMPTED")]
+    Preempted,
 }
 
 impl State {
     /// Returns whether a task is still executing or not.
+    ///
+    /// A task that is being cancelled is still executing until it reaches the
+    /// cancelled state.
     pub fn is_executing(&self) -> bool {
         matches!(
             self,
-            Self::Unknown | Self::Queued | Self::Initializing | Self::Running | Self::Paused
+            Self::Queued | Self::Initializing | Self::Running | Self::Paused | Self::Canceling
+        )
+    }
+
+    /// Returns whether a task has stopped executing (successfully or not).
+    pub fn is_terminal(&self) -> bool {
+        !self.is_executing()
+    }
+
+    /// Returns whether a task has completed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Complete)
+    }
+
+    /// Returns whether a task has stopped executing because of an error.
+    ///
+    /// Note that a cancelled task is terminal but is not considered to have
+    /// errored, whereas a preempted task is.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Self::ExecutorError | Self::SystemError | Self::Preempted
         )
     }
 }
@@ -94,6 +126,8 @@ pub struct Output {
     pub description: Option<String>,
 
     /// The URL where the result will be stored.
+    ///
+    /// This is required by TES; a server rejects an output with an empty URL.
     pub url: String,
 
     /// The path to the output within the container.
@@ -191,3 +225,34 @@ pub struct Task {
     /// The logs.
     pub logs: Option<Vec<TaskLog>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_state_deserializes() {
+        let states = [
+            ("UNKNOWN", false),
+            ("QUEUED", true),
+            ("INITIALIZING", true),
+            ("RUNNING", true),
+            ("PAUSED", true),
+            ("COMPLETE", false),
+            ("EXECUTOR_ERROR", false),
+            ("SYSTEM_ERROR", false),
+            ("CANCELED", false),
+            ("CANCELING", true),
+            ("PREEMPTED", false),
+        ];
+
+        for (name, executing) in states {
+            let state = serde_json::from_str::<State>(&format!("\"{name}\"")).unwrap();
+            assert_eq!(state.is_executing(), executing, "state `{name}`");
+            assert_eq!(
+                serde_json::to_string(&state).unwrap(),
+                format!("\"{name}\"")
+            );
+        }
+    }
+}
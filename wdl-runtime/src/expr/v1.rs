@@ -10,13 +10,13 @@ use wdl_analysis::diagnostics::{
 use wdl_analysis::stdlib::FunctionBindError;
 use wdl_analysis::types::{Coercible, Type};
 use wdl_ast::v1::{
-    CallExpr, Expr, LiteralArray, LiteralExpr, LiteralMap, LiteralObject, LiteralPair,
-    LiteralStringKind, LiteralStruct, Placeholder, StringPart,
+    AccessExpr, CallExpr, Expr, LiteralArray, LiteralExpr, LiteralMap, LiteralObject, LiteralPair,
+    LiteralString, LiteralStringKind, LiteralStruct, Placeholder, PlaceholderOption, StringPart,
 };
 use wdl_ast::{AstNode, AstNodeExt, AstToken, Diagnostic, Ident, Span, SyntaxKind, TokenStrHash};
 
 use crate::util::strip_leading_whitespace;
-use crate::{read_string, Runtime, Value};
+use crate::{Runtime, StoredValue, Value, FUNCTIONS};
 use std::fmt::Write;
 
 /// Creates an "integer not in range" diagnostic
@@ -48,6 +48,15 @@ fn cannot_call(target: &Ident) -> Diagnostic {
     .with_highlight(target.span())
 }
 
+/// Creates an "unimplemented function" diagnostic.
+fn unimplemented_function(target: &Ident) -> Diagnostic {
+    Diagnostic::error(format!(
+        "function `{target}` is not supported by this runtime",
+        target = target.as_str()
+    ))
+    .with_highlight(target.span())
+}
+
 /// Creates a "call failed" diagnostic.
 fn call_failed(target: &Ident, error: &anyhow::Error) -> Diagnostic {
     Diagnostic::error(format!(
@@ -57,6 +66,40 @@ fn call_failed(target: &Ident, error: &anyhow::Error) -> Diagnostic {
     .with_highlight(target.span())
 }
 
+/// Creates an "unknown member" diagnostic.
+fn unknown_member(member: &Ident) -> Diagnostic {
+    Diagnostic::error(format!(
+        "value does not have a member named `{member}`",
+        member = member.as_str()
+    ))
+    .with_highlight(member.span())
+}
+
+/// Creates a "cannot access" diagnostic.
+fn cannot_access(runtime: &Runtime<'_>, value: &Value, span: Span) -> Diagnostic {
+    Diagnostic::error(format!(
+        "cannot access a member of a value of type `{ty}`",
+        ty = value.ty().display(runtime.types())
+    ))
+    .with_highlight(span)
+}
+
+/// Creates a "placeholder option mismatch" diagnostic.
+fn placeholder_option_mismatch(
+    runtime: &Runtime<'_>,
+    option: &str,
+    expected: &str,
+    value: &Value,
+    span: Span,
+) -> Diagnostic {
+    Diagnostic::error(format!(
+        "the `{option}` placeholder option requires a value of type `{expected}`, but found \
+         type `{ty}`",
+        ty = value.ty().display(runtime.types())
+    ))
+    .with_highlight(span)
+}
+
 /// Represents a WDL expression evaluator.
 #[derive(Debug)]
 pub struct ExprEvaluator<'a> {
@@ -130,7 +173,7 @@ impl<'a> ExprEvaluator<'a> {
             Expr::Exponentiation(_) => todo!(),
             Expr::Call(expr) => self.evaluate_call_expr(runtime, expr),
             Expr::Index(_) => todo!(),
-            Expr::Access(_) => todo!(),
+            Expr::Access(expr) => self.evaluate_access_expr(runtime, expr),
         }
     }
 
@@ -197,6 +240,10 @@ impl<'a> ExprEvaluator<'a> {
     }
 
     /// Evaluates a placeholder into the given string buffer.
+    ///
+    /// The `sep` option joins the elements of an array, the `true`/`false`
+    /// option selects a string for a boolean, and the `default` option is
+    /// used in place of a `None` value.
     pub(crate) fn evaluate_placeholder(
         &self,
         runtime: &mut Runtime<'_>,
@@ -204,7 +251,91 @@ impl<'a> ExprEvaluator<'a> {
         buffer: &mut String,
     ) -> Result<(), Diagnostic> {
         let expr = placeholder.expr();
-        match self.evaluate_expr(runtime, &expr)? {
+        let value = self.evaluate_expr(runtime, &expr)?;
+
+        match placeholder.option() {
+            Some(PlaceholderOption::Sep(option)) => {
+                let elements = match value {
+                    Value::Stored(_, id) => match runtime.stored(id) {
+                        StoredValue::Array(elements) => elements.clone(),
+                        _ => {
+                            return Err(placeholder_option_mismatch(
+                                runtime,
+                                "sep",
+                                "Array",
+                                &value,
+                                expr.span(),
+                            ))
+                        }
+                    },
+                    _ => {
+                        return Err(placeholder_option_mismatch(
+                            runtime,
+                            "sep",
+                            "Array",
+                            &value,
+                            expr.span(),
+                        ))
+                    }
+                };
+
+                let separator = self.evaluate_option_string(runtime, &option.separator())?;
+                for (i, element) in elements.into_iter().enumerate() {
+                    if i > 0 {
+                        buffer.push_str(&separator);
+                    }
+
+                    Self::write_value(runtime, element, expr.span(), buffer)?;
+                }
+
+                Ok(())
+            }
+            Some(PlaceholderOption::TrueFalse(option)) => match value {
+                Value::Boolean(v) => {
+                    let (when_true, when_false) = option.values();
+                    let s = self.evaluate_option_string(
+                        runtime,
+                        if v { &when_true } else { &when_false },
+                    )?;
+                    buffer.push_str(&s);
+                    Ok(())
+                }
+                _ => Err(placeholder_option_mismatch(
+                    runtime,
+                    "true/false",
+                    "Boolean",
+                    &value,
+                    expr.span(),
+                )),
+            },
+            Some(PlaceholderOption::Default(option)) if value == Value::None => {
+                let default = option.value();
+                let value = self.evaluate_literal_expr(runtime, &default)?;
+                Self::write_value(runtime, value, default.span(), buffer)
+            }
+            _ => Self::write_value(runtime, value, expr.span(), buffer),
+        }
+    }
+
+    /// Evaluates the string literal of a placeholder option.
+    fn evaluate_option_string(
+        &self,
+        runtime: &mut Runtime<'_>,
+        literal: &LiteralString,
+    ) -> Result<String, Diagnostic> {
+        let value = self.evaluate_literal_expr(runtime, &LiteralExpr::String(literal.clone()))?;
+        Ok(value.unwrap_string(runtime).to_string())
+    }
+
+    /// Writes the string representation of a primitive value into the given
+    /// string buffer.
+    fn write_value(
+        runtime: &Runtime<'_>,
+        value: Value,
+        span: Span,
+        buffer: &mut String,
+    ) -> Result<(), Diagnostic> {
+        match value {
             Value::Boolean(v) => buffer.push_str(if v { "true" } else { "false" }),
             Value::Integer(v) => write!(buffer, "{v}").unwrap(),
             Value::Float(v) => write!(buffer, "{v}").unwrap(),
@@ -213,7 +344,7 @@ impl<'a> ExprEvaluator<'a> {
             }
             Value::None => {}
             Value::Stored(ty, _) => {
-                return Err(cannot_coerce_to_string(runtime.types(), ty, expr.span()));
+                return Err(cannot_coerce_to_string(runtime.types(), ty, span));
             }
         }
 
@@ -369,6 +500,35 @@ impl<'a> ExprEvaluator<'a> {
         todo!()
     }
 
+    /// Evaluates an access expression.
+    ///
+    /// Members of `Object` values (including the outputs of workflow calls)
+    /// and the `left` and `right` members of `Pair` values can be accessed.
+    fn evaluate_access_expr(
+        &self,
+        runtime: &mut Runtime<'_>,
+        expr: &AccessExpr,
+    ) -> Result<Value, Diagnostic> {
+        let (target, member) = expr.operands();
+        let value = self.evaluate_expr(runtime, &target)?;
+
+        match value {
+            Value::Stored(_, id) => match runtime.stored(id) {
+                StoredValue::Object(members) => members
+                    .get(member.as_str())
+                    .copied()
+                    .ok_or_else(|| unknown_member(&member)),
+                StoredValue::Pair(left, right) => match member.as_str() {
+                    "left" => Ok(*left),
+                    "right" => Ok(*right),
+                    _ => Err(unknown_member(&member)),
+                },
+                _ => Err(cannot_access(runtime, &value, target.span())),
+            },
+            _ => Err(cannot_access(runtime, &value, target.span())),
+        }
+    }
+
     /// Evaluates a call expression.
     fn evaluate_call_expr(
         &self,
@@ -402,19 +562,28 @@ impl<'a> ExprEvaluator<'a> {
                     },
                 )?;
 
-                // TODO: implement a `can_bind` which doesn't mutate the types collection
-                match f.bind(runtime.types_mut(), &types) {
-                    Ok(_) => {
-                        // TODO: dispatch the function call in a better way
-                        let r = match target.as_str() {
-                            "read_string" => read_string(runtime, &arguments),
-                            "stdout" => return self.stdout.ok_or_else(|| cannot_call(&target)),
-                            "stderr" => return self.stderr.ok_or_else(|| cannot_call(&target)),
-                            _ => unreachable!("unknown function"),
-                        };
-
-                        r.map_err(|e| call_failed(&target, &e))
-                    }
+                // Binding mutates the types collection, so a signature that has
+                // already been bound successfully is not bound again (see
+                // `Runtime::add_binding`)
+                let bound = if runtime.is_bound(target.as_str(), &types) {
+                    Ok(())
+                } else {
+                    f.bind(runtime.types_mut(), &types).map(|_| {
+                        runtime.add_binding(target.as_str(), &types);
+                    })
+                };
+
+                match bound {
+                    Ok(()) => match target.as_str() {
+                        "stdout" => self.stdout.ok_or_else(|| cannot_call(&target)),
+                        "stderr" => self.stderr.ok_or_else(|| cannot_call(&target)),
+                        name => {
+                            let f = FUNCTIONS
+                                .get(name)
+                                .ok_or_else(|| unimplemented_function(&target))?;
+                            f(runtime, &arguments).map_err(|e| call_failed(&target, &e))
+                        }
+                    },
                     Err(FunctionBindError::TooFewArguments(minimum)) => Err(too_few_arguments(
                         target.as_str(),
                         target.span(),
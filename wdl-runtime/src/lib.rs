@@ -12,8 +12,10 @@ mod runtime;
 mod stdlib;
 mod task;
 mod util;
+mod workflow;
 
 pub use expr::*;
 pub use runtime::*;
 pub use stdlib::*;
 pub use task::*;
+pub use workflow::*;
@@ -1,15 +1,21 @@
 //! Implementation of the WDL runtime and values.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
 
+use anyhow::{bail, Context, Result};
 use id_arena::{Arena, Id};
 use ordered_float::OrderedFloat;
 use string_interner::{symbol::SymbolU32, DefaultStringInterner};
 use wdl_analysis::{
     diagnostics::unknown_type,
     scope::DocumentScope,
-    types::{ArrayType, MapType, PairType, PrimitiveTypeKind, Type, TypeEq, Types},
+    types::{
+        ArrayType, CompoundTypeDef, MapType, PairType, PrimitiveTypeKind, Type, TypeEq, Types,
+    },
 };
 use wdl_ast::{AstToken, Diagnostic, Ident};
 
@@ -123,12 +129,23 @@ impl Value {
 
     /// Coerces the value into the given type.
     ///
+    /// A `None` value may only be coerced to an optional type, and any other
+    /// value coerced to an optional type is coerced to the required type.
+    ///
     /// Returns `None` if the coercion is not supported.
     pub fn coerce(&self, runtime: &mut Runtime<'_>, ty: Type) -> Option<Self> {
         if self.ty().type_eq(runtime.types(), &ty) {
             return Some(*self);
         }
 
+        if let Value::None = self {
+            return ty.is_optional().then_some(Value::None);
+        }
+
+        if ty.is_optional() {
+            return self.coerce(runtime, ty.require());
+        }
+
         match (self, ty) {
             (Value::String(sym), ty) => {
                 if let Some(ty) = ty.as_primitive() {
@@ -141,6 +158,12 @@ impl Value {
                     None
                 }
             }
+            (Value::File(sym) | Value::Directory(sym), ty) => {
+                match ty.as_primitive().map(|ty| ty.kind()) {
+                    Some(PrimitiveTypeKind::String) => Some(Self::String(*sym)),
+                    _ => None,
+                }
+            }
             (Value::Integer(v), ty) => {
                 if let Some(ty) = ty.as_primitive() {
                     match ty.kind() {
@@ -151,7 +174,61 @@ impl Value {
                     None
                 }
             }
-            _ => todo!("implement the remainder coercions"),
+            _ => None,
+        }
+    }
+
+    /// Converts the value to its JSON representation.
+    ///
+    /// A `Pair` is represented as an object with `left` and `right` members,
+    /// the keys of a `Map` are converted to strings, and a struct is
+    /// represented as an object keyed by the names of its members.
+    pub fn to_json(&self, runtime: &Runtime<'_>) -> serde_json::Value {
+        match *self {
+            Value::Boolean(v) => v.into(),
+            Value::Integer(v) => v.into(),
+            Value::Float(v) => serde_json::Number::from_f64(v.into_inner())
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::String(sym) | Value::File(sym) | Value::Directory(sym) => {
+                runtime.resolve_str(sym).into()
+            }
+            Value::None => serde_json::Value::Null,
+            Value::Stored(ty, id) => match runtime.stored(id) {
+                StoredValue::Pair(left, right) => serde_json::json!({
+                    "left": left.to_json(runtime),
+                    "right": right.to_json(runtime),
+                }),
+                StoredValue::Array(elements) => {
+                    elements.iter().map(|v| v.to_json(runtime)).collect()
+                }
+                StoredValue::Map(items) => items
+                    .iter()
+                    .map(|(k, v)| (k.display(runtime).to_string(), v.to_json(runtime)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+                StoredValue::Object(items) => items
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.to_json(runtime)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into(),
+                StoredValue::Struct(members) => {
+                    let ty = match ty
+                        .as_compound()
+                        .map(|ty| runtime.types().type_definition(ty.definition()))
+                    {
+                        Some(CompoundTypeDef::Struct(ty)) => ty,
+                        _ => panic!("a struct value should have a struct type"),
+                    };
+
+                    ty.members()
+                        .keys()
+                        .zip(members)
+                        .map(|(name, v)| (name.clone(), v.to_json(runtime)))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into()
+                }
+            },
         }
     }
 
@@ -230,6 +307,8 @@ pub type StoredValueId = Id<StoredValue>;
 pub struct Runtime<'a> {
     /// The reference to the document scope being evaluated.
     document: &'a DocumentScope,
+    /// The directory in which files created during evaluation are written.
+    execution_dir: PathBuf,
     /// The types collection for values.
     types: Types,
     /// The storage arena for values.
@@ -238,17 +317,38 @@ pub struct Runtime<'a> {
     interner: DefaultStringInterner,
     /// The map of known structs to already imported types.
     structs: HashMap<String, Type>,
+    /// The set of function signatures (the function name and the names of the
+    /// argument types) that have been successfully bound.
+    bindings: HashSet<(String, Vec<String>)>,
 }
 
 impl<'a> Runtime<'a> {
     /// Constructs a new runtime for the given document being evaluated.
+    ///
+    /// Files created during evaluation (e.g. by `write_lines`) are written to
+    /// the system's temporary directory; use
+    /// [`new_with_execution_dir`](Self::new_with_execution_dir) to write them
+    /// elsewhere.
     pub fn new(document: &'a DocumentScope) -> Self {
+        Self::new_with_execution_dir(document, std::env::temp_dir())
+    }
+
+    /// Constructs a new runtime for the given document being evaluated with
+    /// the directory in which files created during evaluation are written.
+    ///
+    /// The directory is created when the first file is written.
+    pub fn new_with_execution_dir(
+        document: &'a DocumentScope,
+        execution_dir: impl Into<PathBuf>,
+    ) -> Self {
         Self {
             document,
+            execution_dir: execution_dir.into(),
             types: Types::default(),
             values: Arena::default(),
             interner: DefaultStringInterner::default(),
             structs: HashMap::default(),
+            bindings: HashSet::default(),
         }
     }
 
@@ -257,6 +357,12 @@ impl<'a> Runtime<'a> {
         self.document
     }
 
+    /// Gets the directory in which files created during evaluation are
+    /// written.
+    pub fn execution_dir(&self) -> &Path {
+        &self.execution_dir
+    }
+
     /// Gets the types collection associated with the runtime.
     pub fn types(&self) -> &Types {
         &self.types
@@ -336,13 +442,285 @@ impl<'a> Runtime<'a> {
         Ok(Value::Stored(ty, id))
     }
 
+    /// Gets a value stored in the runtime.
+    pub fn stored(&self, id: StoredValueId) -> &StoredValue {
+        &self.values[id]
+    }
+
+    /// Creates a new value from its JSON representation.
+    ///
+    /// JSON objects are converted to `Object` values, arrays to `Array` values,
+    /// and `null` to `None`. Integral numbers are converted to `Int` values and
+    /// all other numbers to `Float` values.
+    pub fn value_from_json(&mut self, json: &serde_json::Value) -> Result<Value> {
+        match json {
+            serde_json::Value::Null => Ok(Value::None),
+            serde_json::Value::Bool(v) => Ok((*v).into()),
+            serde_json::Value::Number(v) => match v.as_i64() {
+                Some(v) => Ok(v.into()),
+                None => Ok(v
+                    .as_f64()
+                    .with_context(|| format!("number `{v}` is out of range"))?
+                    .into()),
+            },
+            serde_json::Value::String(v) => Ok(self.new_string(v)),
+            serde_json::Value::Array(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.value_from_json(e))
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Arrays must be homogenous
+                if let Some(first) = elements.first() {
+                    let expected = first.ty();
+                    if elements
+                        .iter()
+                        .any(|e| !e.ty().type_eq(&self.types, &expected))
+                    {
+                        bail!("array elements must all be the same type");
+                    }
+                }
+
+                Ok(self.new_array(elements))
+            }
+            serde_json::Value::Object(items) => {
+                let items = items
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), self.value_from_json(v)?)))
+                    .collect::<Result<HashMap<_, _>>>()?;
+                Ok(self.new_object(items))
+            }
+        }
+    }
+
+    /// Creates an isolated runtime for the same document with the values of
+    /// the given scope imported into it.
+    ///
+    /// The returned runtime shares no state with this one, so evaluations in
+    /// separate snapshots (e.g. the elements of a scatter) may happen
+    /// concurrently; values produced by a snapshot can be brought back into
+    /// this runtime with [`Runtime::import_value`].
+    pub fn snapshot<K>(&self, scope: &HashMap<K, Value>) -> (Runtime<'a>, HashMap<K, Value>)
+    where
+        K: Clone + Eq + Hash,
+    {
+        let mut runtime = Runtime::new_with_execution_dir(self.document, &self.execution_dir);
+        let scope = scope
+            .iter()
+            .map(|(k, v)| (k.clone(), runtime.import_value(self, *v)))
+            .collect();
+        (runtime, scope)
+    }
+
+    /// Imports a value from another runtime into this runtime.
+    ///
+    /// Strings are interned and compound values (and their types) are copied
+    /// into this runtime, so the returned value no longer refers to the other
+    /// runtime.
+    pub fn import_value(&mut self, from: &Runtime<'_>, value: Value) -> Value {
+        match value {
+            Value::String(sym) => self.new_string(from.resolve_str(sym)),
+            Value::File(sym) => self.new_file(from.resolve_str(sym)),
+            Value::Directory(sym) => self.new_directory(from.resolve_str(sym)),
+            Value::Stored(ty, id) => {
+                let stored = match from.stored(id) {
+                    StoredValue::Pair(left, right) => StoredValue::Pair(
+                        self.import_value(from, *left),
+                        self.import_value(from, *right),
+                    ),
+                    StoredValue::Array(elements) => StoredValue::Array(
+                        elements
+                            .iter()
+                            .map(|v| self.import_value(from, *v))
+                            .collect(),
+                    ),
+                    StoredValue::Map(items) => StoredValue::Map(
+                        items
+                            .iter()
+                            .map(|(k, v)| {
+                                (self.import_value(from, *k), self.import_value(from, *v))
+                            })
+                            .collect(),
+                    ),
+                    StoredValue::Object(items) => StoredValue::Object(
+                        items
+                            .iter()
+                            .map(|(k, v)| (k.clone(), self.import_value(from, *v)))
+                            .collect(),
+                    ),
+                    StoredValue::Struct(members) => StoredValue::Struct(
+                        members
+                            .iter()
+                            .map(|v| self.import_value(from, *v))
+                            .collect(),
+                    ),
+                };
+
+                let ty = self.types.import(from.types(), ty);
+                Value::Stored(ty, self.values.alloc(stored))
+            }
+            Value::Boolean(_) | Value::Integer(_) | Value::Float(_) | Value::None => value,
+        }
+    }
+
     /// Resolves a previously interned string from a symbol.
     pub fn resolve_str(&self, sym: SymbolU32) -> &str {
         self.interner.resolve(sym).expect("should have symbol")
     }
 
+    /// Gets the signature of a call to a function with the given argument
+    /// types.
+    ///
+    /// The argument types are identified by name rather than by [`Type`], as
+    /// every compound value gets a type of its own; arguments of the same
+    /// named type bind the same way.
+    fn signature(&self, function: &str, arguments: &[Type]) -> (String, Vec<String>) {
+        (
+            function.to_string(),
+            arguments
+                .iter()
+                .map(|ty| ty.display(&self.types).to_string())
+                .collect(),
+        )
+    }
+
+    /// Determines if a function has already been successfully bound to the
+    /// given argument types.
+    pub(crate) fn is_bound(&self, function: &str, arguments: &[Type]) -> bool {
+        self.bindings.contains(&self.signature(function, arguments))
+    }
+
+    /// Records that a function was successfully bound to the given argument
+    /// types.
+    ///
+    /// Binding a function adds types to the types collection, so recording the
+    /// binding allows later calls with the same signature to skip it.
+    ///
+    /// # Notes
+    ///
+    /// Signatures that fail to bind are not recorded, so a failed binding
+    /// still adds types to the collection. A failed binding ends evaluation
+    /// with a diagnostic, so this happens at most once per evaluation.
+    pub(crate) fn add_binding(&mut self, function: &str, arguments: &[Type]) {
+        let signature = self.signature(function, arguments);
+        self.bindings.insert(signature);
+    }
+
     /// Imports a type from the document types collection.
     pub(crate) fn import_type(&mut self, ty: Type) -> Type {
         self.types.import(self.document.types(), ty)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wdl_analysis::Analyzer;
+
+    #[tokio::test]
+    async fn snapshots_are_isolated() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            "version 1.1\n\nworkflow test {}\n",
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let left = runtime.new_string("foo");
+        let right = runtime.new_file("/foo/bar");
+        let pair = runtime.new_pair(left, right);
+        let array = runtime.new_array(vec![pair]);
+
+        let scope = HashMap::from([("x", array)]);
+        let (mut snapshot, mut snapshot_scope) = runtime.snapshot(&scope);
+        assert_eq!(
+            snapshot_scope["x"].to_json(&snapshot),
+            array.to_json(&runtime)
+        );
+
+        // Values created in the snapshot don't affect the original runtime
+        let value = snapshot.new_string("only in the snapshot");
+        snapshot_scope.insert("y", value);
+        assert!(runtime.interner.get("only in the snapshot").is_none());
+
+        let imported = runtime.import_value(&snapshot, snapshot_scope["y"]);
+        assert_eq!(imported.unwrap_string(&runtime), "only in the snapshot");
+    }
+
+    #[tokio::test]
+    async fn compound_values_serialize_to_json() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+struct Sample {
+    String name
+    Int reads
+}
+
+workflow test {}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+        let name = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .structs()
+            .next()
+            .expect("should have a struct")
+            .name();
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let sample = runtime.new_string("SJ001");
+        let sample = runtime
+            .new_struct(&name, vec![sample, Value::Integer(42)])
+            .expect("should create struct");
+        assert_eq!(
+            sample.to_json(&runtime),
+            serde_json::json!({ "name": "SJ001", "reads": 42 })
+        );
+
+        let key = runtime.new_string("SJ001");
+        let map = runtime.new_map(HashMap::from([(key, Value::Integer(42))]));
+        assert_eq!(map.to_json(&runtime), serde_json::json!({ "SJ001": 42 }));
+
+        let array = runtime.new_array(vec![sample, sample]);
+        assert_eq!(
+            array.to_json(&runtime),
+            serde_json::json!([
+                { "name": "SJ001", "reads": 42 },
+                { "name": "SJ001", "reads": 42 },
+            ])
+        );
+    }
+}
@@ -1,21 +1,423 @@
 //! Implementation of stdlib functions.
 
+use std::collections::HashMap;
 use std::fs;
+use std::hash::DefaultHasher;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
+use std::sync::LazyLock;
 
+use anyhow::bail;
 use anyhow::Result;
 
 use anyhow::Context;
 use wdl_analysis::types::PrimitiveTypeKind;
 
-use crate::{Runtime, Value};
+use crate::util::unit_bytes;
+use crate::{Runtime, StoredValue, Value};
+
+/// A stdlib function implementation.
+pub type Function = fn(&mut Runtime<'_>, &[Value]) -> Result<Value>;
+
+/// The implemented stdlib functions, keyed by name.
+///
+/// Note that `stdout` and `stderr` are not included as they are implemented by
+/// the expression evaluator itself.
+pub static FUNCTIONS: LazyLock<HashMap<&'static str, Function>> = LazyLock::new(|| {
+    HashMap::from([
+        ("basename", basename as Function),
+        ("ceil", ceil),
+        ("floor", floor),
+        ("read_json", read_json),
+        ("read_map", read_map),
+        ("read_string", read_string),
+        ("round", round),
+        ("size", size),
+        ("write_lines", write_lines),
+        ("write_tsv", write_tsv),
+    ])
+});
+
+/// Gets the path of the `File` argument to a stdlib function.
+fn file_argument(runtime: &mut Runtime<'_>, arg: &Value) -> String {
+    arg.coerce(runtime, PrimitiveTypeKind::File.into())
+        .unwrap()
+        .unwrap_file(runtime)
+        .to_string()
+}
 
 /// Implements the `read_string` stdlib function.
 pub fn read_string(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
-    let path = args[0]
-        .coerce(runtime, PrimitiveTypeKind::File.into())
-        .unwrap()
-        .unwrap_file(runtime);
+    let path = file_argument(runtime, &args[0]);
     Ok(runtime.new_string(
-        fs::read_to_string(path).with_context(|| format!("failed to read file `{path}`"))?,
+        fs::read_to_string(&path).with_context(|| format!("failed to read file `{path}`"))?,
     ))
 }
+
+/// Implements the `read_json` stdlib function.
+///
+/// JSON objects are read as `Object` values, arrays as `Array` values, and
+/// `null` as `None`. Numbers are read as `Int` values when they are integral
+/// and as `Float` values otherwise.
+pub fn read_json(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let path = file_argument(runtime, &args[0]);
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("failed to read file `{path}`"))?;
+    let json: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse JSON file `{path}`"))?;
+    runtime
+        .value_from_json(&json)
+        .with_context(|| format!("invalid JSON file `{path}`"))
+}
+
+/// Implements the `read_map` stdlib function.
+///
+/// The file is expected to contain two tab-separated columns, with each line
+/// mapping the value of the first column to the value of the second.
+pub fn read_map(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let path = file_argument(runtime, &args[0]);
+    let contents =
+        fs::read_to_string(&path).with_context(|| format!("failed to read file `{path}`"))?;
+
+    let mut items = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        let columns = line.split('\t').collect::<Vec<_>>();
+        let [key, value] = columns[..] else {
+            bail!(
+                "line {line} of file `{path}` has {count} columns, but two were expected",
+                line = i + 1,
+                count = columns.len()
+            );
+        };
+
+        let key = runtime.new_string(key);
+        let value = runtime.new_string(value);
+        if items.insert(key, value).is_some() {
+            bail!(
+                "line {line} of file `{path}` contains duplicate key `{key}`",
+                line = i + 1,
+                key = key.unwrap_string(runtime)
+            );
+        }
+    }
+
+    Ok(runtime.new_map(items))
+}
+
+/// Gets the elements of the `Array` argument to a stdlib function.
+fn array_argument(runtime: &Runtime<'_>, arg: &Value) -> Vec<Value> {
+    match arg {
+        Value::Stored(_, id) => match runtime.stored(*id) {
+            StoredValue::Array(elements) => elements.clone(),
+            _ => unreachable!("argument should be an array"),
+        },
+        _ => unreachable!("argument should be an array"),
+    }
+}
+
+/// Writes the contents produced by a `write_*` stdlib function to a file in
+/// the execution directory of the runtime.
+///
+/// The file is named after the function and a hash of the contents, so the
+/// same contents are always written to the same file.
+fn write_file(runtime: &mut Runtime<'_>, function: &str, contents: &str) -> Result<Value> {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+
+    let dir = runtime.execution_dir();
+    fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create directory `{}`", dir.display()))?;
+
+    let path = dir.join(format!(
+        "{function}_{hash:016x}.txt",
+        hash = hasher.finish()
+    ));
+    fs::write(&path, contents)
+        .with_context(|| format!("failed to write file `{}`", path.display()))?;
+
+    Ok(runtime.new_file(path.to_string_lossy()))
+}
+
+/// Implements the `write_lines` stdlib function.
+///
+/// Each string is written on its own line (including the last).
+pub fn write_lines(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let mut contents = String::new();
+    for line in array_argument(runtime, &args[0]) {
+        contents.push_str(line.unwrap_string(runtime));
+        contents.push('\n');
+    }
+
+    write_file(runtime, "write_lines", &contents)
+}
+
+/// Implements the `write_tsv` stdlib function.
+///
+/// Each array of strings is written on its own line with its elements
+/// separated by tabs.
+pub fn write_tsv(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let mut contents = String::new();
+    for row in array_argument(runtime, &args[0]) {
+        let columns = array_argument(runtime, &row)
+            .into_iter()
+            .map(|column| column.unwrap_string(runtime))
+            .collect::<Vec<_>>();
+        contents.push_str(&columns.join("\t"));
+        contents.push('\n');
+    }
+
+    write_file(runtime, "write_tsv", &contents)
+}
+
+/// Implements the `basename` stdlib function.
+pub fn basename(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let path = match args[0] {
+        Value::String(sym) | Value::File(sym) | Value::Directory(sym) => runtime.resolve_str(sym),
+        _ => unreachable!("argument should be a string"),
+    };
+
+    let path = path.trim_end_matches('/');
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = match args.get(1) {
+        Some(suffix) => name
+            .strip_suffix(suffix.unwrap_string(runtime))
+            .unwrap_or(name),
+        None => name,
+    };
+
+    Ok(runtime.new_string(name.to_string()))
+}
+
+/// Gets the number of bytes in the given unit for the `size` stdlib function.
+fn size_unit(unit: &str) -> Result<f64> {
+    match unit_bytes(unit) {
+        Some(bytes) => Ok(bytes as f64),
+        None => bail!("unknown size unit `{unit}`"),
+    }
+}
+
+/// Calculates the total size, in bytes, of the files within a value.
+fn total_size(runtime: &Runtime<'_>, value: Value) -> Result<u64> {
+    match value {
+        Value::File(sym) => {
+            let path = runtime.resolve_str(sym);
+            Ok(fs::metadata(path)
+                .with_context(|| format!("failed to read metadata of file `{path}`"))?
+                .len())
+        }
+        Value::Stored(_, id) => match runtime.stored(id) {
+            StoredValue::Pair(left, right) => {
+                Ok(total_size(runtime, *left)? + total_size(runtime, *right)?)
+            }
+            StoredValue::Array(elements) | StoredValue::Struct(elements) => {
+                elements.iter().map(|v| total_size(runtime, *v)).sum()
+            }
+            StoredValue::Map(items) => items
+                .iter()
+                .map(|(k, v)| Ok(total_size(runtime, *k)? + total_size(runtime, *v)?))
+                .sum(),
+            StoredValue::Object(items) => items.values().map(|v| total_size(runtime, *v)).sum(),
+        },
+        _ => Ok(0),
+    }
+}
+
+/// Implements the `size` stdlib function.
+///
+/// The sizes of all files within the value are summed; a `None` value has a
+/// size of zero.
+pub fn size(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    let value = match args[0] {
+        Value::String(sym) => Value::File(sym),
+        value => value,
+    };
+
+    let unit = match args.get(1) {
+        Some(unit) => size_unit(unit.unwrap_string(runtime))?,
+        None => 1.0,
+    };
+
+    Ok((total_size(runtime, value)? as f64 / unit).into())
+}
+
+/// Gets the value of the `Float` argument to a stdlib function.
+fn float_argument(runtime: &mut Runtime<'_>, arg: &Value) -> f64 {
+    arg.coerce(runtime, PrimitiveTypeKind::Float.into())
+        .unwrap()
+        .unwrap_float()
+}
+
+/// Implements the `floor` stdlib function.
+pub fn floor(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    Ok((float_argument(runtime, &args[0]).floor() as i64).into())
+}
+
+/// Implements the `ceil` stdlib function.
+pub fn ceil(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    Ok((float_argument(runtime, &args[0]).ceil() as i64).into())
+}
+
+/// Implements the `round` stdlib function.
+///
+/// Halves are rounded up (towards positive infinity).
+pub fn round(runtime: &mut Runtime<'_>, args: &[Value]) -> Result<Value> {
+    Ok(((float_argument(runtime, &args[0]) + 0.5).floor() as i64).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tempfile::TempDir;
+    use wdl_analysis::{AnalysisResult, Analyzer};
+
+    /// Analyzes an empty document to create runtimes from.
+    async fn analyze(dir: &TempDir) -> Vec<AnalysisResult> {
+        fs::write(
+            dir.path().join("foo.wdl"),
+            "version 1.1\n\nworkflow test {}\n",
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        results
+    }
+
+    #[tokio::test]
+    async fn read_json_reads_values() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let results = analyze(&dir).await;
+        let mut runtime = Runtime::new(results[0].scope());
+
+        let path = dir.path().join("values.json");
+        fs::write(
+            &path,
+            r#"{ "name": "SJ001", "reads": 42, "quality": 0.5, "tags": ["a", null] }"#,
+        )
+        .expect("failed to write JSON file");
+
+        let file = runtime.new_file(path.to_string_lossy());
+        let value = read_json(&mut runtime, &[file]).expect("should read JSON");
+        assert_eq!(
+            value.to_json(&runtime),
+            serde_json::json!({ "name": "SJ001", "reads": 42, "quality": 0.5, "tags": ["a", null] })
+        );
+
+        fs::write(&path, "{").expect("failed to write JSON file");
+        let file = runtime.new_file(path.to_string_lossy());
+        assert!(read_json(&mut runtime, &[file]).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_map_reads_two_columns() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let results = analyze(&dir).await;
+        let mut runtime = Runtime::new(results[0].scope());
+
+        let path = dir.path().join("map.tsv");
+        fs::write(&path, "SJ001\tbam\nSJ002\tcram\n").expect("failed to write map file");
+        let file = runtime.new_file(path.to_string_lossy());
+        let value = read_map(&mut runtime, &[file]).expect("should read map");
+        assert_eq!(
+            value.to_json(&runtime),
+            serde_json::json!({ "SJ001": "bam", "SJ002": "cram" })
+        );
+
+        fs::write(&path, "SJ001\tbam\tcram\n").expect("failed to write map file");
+        let file = runtime.new_file(path.to_string_lossy());
+        let error = read_map(&mut runtime, &[file]).unwrap_err().to_string();
+        assert!(error.contains("has 3 columns"), "{error}");
+
+        fs::write(&path, "SJ001\tbam\nSJ001\tcram\n").expect("failed to write map file");
+        let file = runtime.new_file(path.to_string_lossy());
+        let error = read_map(&mut runtime, &[file]).unwrap_err().to_string();
+        assert!(error.contains("duplicate key `SJ001`"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn basename_strips_directories_and_suffixes() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let results = analyze(&dir).await;
+        let mut runtime = Runtime::new(results[0].scope());
+
+        let path = runtime.new_file("/data/SJ001.bam");
+        let value = basename(&mut runtime, &[path]).expect("should succeed");
+        assert_eq!(value.unwrap_string(&runtime), "SJ001.bam");
+
+        let suffix = runtime.new_string(".bam");
+        let value = basename(&mut runtime, &[path, suffix]).expect("should succeed");
+        assert_eq!(value.unwrap_string(&runtime), "SJ001");
+
+        let path = runtime.new_directory("/data/samples/");
+        let value = basename(&mut runtime, &[path]).expect("should succeed");
+        assert_eq!(value.unwrap_string(&runtime), "samples");
+    }
+
+    #[tokio::test]
+    async fn size_sums_files_in_the_given_unit() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let results = analyze(&dir).await;
+        let mut runtime = Runtime::new(results[0].scope());
+
+        let first = dir.path().join("first.txt");
+        fs::write(&first, vec![b'a'; 1024]).expect("failed to write file");
+        let second = dir.path().join("second.txt");
+        fs::write(&second, vec![b'a'; 1024]).expect("failed to write file");
+
+        let first = runtime.new_file(first.to_string_lossy());
+        let value = size(&mut runtime, &[first]).expect("should succeed");
+        assert_eq!(value.unwrap_float(), 1024.0);
+
+        let second = runtime.new_file(second.to_string_lossy());
+        let files = runtime.new_array(vec![first, second, Value::None]);
+        let unit = runtime.new_string("KiB");
+        let value = size(&mut runtime, &[files, unit]).expect("should succeed");
+        assert_eq!(value.unwrap_float(), 2.0);
+
+        let unit = runtime.new_string("KB");
+        let value = size(&mut runtime, &[files, unit]).expect("should succeed");
+        assert_eq!(value.unwrap_float(), 2.048);
+
+        let value = size(&mut runtime, &[Value::None]).expect("should succeed");
+        assert_eq!(value.unwrap_float(), 0.0);
+
+        let unit = runtime.new_string("lots");
+        let error = size(&mut runtime, &[files, unit]).unwrap_err().to_string();
+        assert_eq!(error, "unknown size unit `lots`");
+    }
+
+    #[tokio::test]
+    async fn rounding_functions_return_integers() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let results = analyze(&dir).await;
+        let mut runtime = Runtime::new(results[0].scope());
+
+        for (value, floored, ceiled, rounded) in [
+            (1.5, 1, 2, 2),
+            (-1.5, -2, -1, -1),
+            (2.25, 2, 3, 2),
+            (3.0, 3, 3, 3),
+        ] {
+            let value = Value::from(value);
+            assert_eq!(
+                floor(&mut runtime, &[value]).unwrap().unwrap_integer(),
+                floored
+            );
+            assert_eq!(
+                ceil(&mut runtime, &[value]).unwrap().unwrap_integer(),
+                ceiled
+            );
+            assert_eq!(
+                round(&mut runtime, &[value]).unwrap().unwrap_integer(),
+                rounded
+            );
+        }
+    }
+}
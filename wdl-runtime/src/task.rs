@@ -2,12 +2,13 @@
 
 use std::{collections::HashMap, path::Path};
 
-use indexmap::IndexMap;
+use anyhow::{bail, Context, Result};
+use indexmap::{IndexMap, IndexSet};
 use petgraph::{
-    algo::{has_path_connecting, toposort},
+    algo::{has_path_connecting, tarjan_scc, toposort},
     graph::{DiGraph, NodeIndex},
 };
-use wdl_analysis::types::{Coercible, Type, Types};
+use wdl_analysis::types::{Coercible, PrimitiveTypeKind, Type, Types};
 use wdl_ast::{
     v1::{
         CommandPart, CommandSection, Decl, HintsSection, NameRef, RequirementsSection,
@@ -16,7 +17,46 @@ use wdl_ast::{
     AstNode, AstNodeExt, AstToken, Diagnostic, Ident, Span, SyntaxNode, TokenStrHash,
 };
 
-use crate::{util::strip_leading_whitespace, v1::ExprEvaluator, Runtime, Value};
+use crate::{
+    util::{command_leading_whitespace, parse_memory, parse_size, push_command_text, GIBIBYTE},
+    v1::ExprEvaluator,
+    Runtime, StoredValue, Value,
+};
+
+/// Represents the resources requested by an evaluated task.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Resources {
+    /// The number of CPUs requested.
+    cpu: Option<f64>,
+    /// The amount of memory requested, in bytes.
+    memory: Option<u64>,
+    /// The amount of disk space requested, in bytes.
+    disks: Option<u64>,
+    /// The maximum number of times the task may be retried.
+    max_retries: Option<u64>,
+}
+
+impl Resources {
+    /// Gets the number of CPUs requested.
+    pub fn cpu(&self) -> Option<f64> {
+        self.cpu
+    }
+
+    /// Gets the amount of memory requested, in bytes.
+    pub fn memory(&self) -> Option<u64> {
+        self.memory
+    }
+
+    /// Gets the total amount of disk space requested, in bytes.
+    pub fn disks(&self) -> Option<u64> {
+        self.disks
+    }
+
+    /// Gets the maximum number of times the task may be retried.
+    pub fn max_retries(&self) -> Option<u64> {
+        self.max_retries
+    }
+}
 
 /// Creates a "missing input" diagnostic.
 fn missing_input(task: &str, input: &Ident) -> Diagnostic {
@@ -43,6 +83,28 @@ fn input_type_mismatch(
     .with_label("the expected input type", span)
 }
 
+/// Creates a "required declaration is `None`" diagnostic.
+fn required_is_none(name: &Ident, span: Span) -> Diagnostic {
+    Diagnostic::error(format!(
+        "declaration `{name}` evaluated to `None`, but its type is not optional",
+        name = name.as_str()
+    ))
+    .with_label("this type is not optional", span)
+}
+
+/// Creates an "evaluation cycle" diagnostic.
+fn evaluation_cycle(names: &[String], span: Span) -> Diagnostic {
+    Diagnostic::error(format!(
+        "task evaluation contains a cycle between {names}",
+        names = names
+            .iter()
+            .map(|n| format!("`{n}`"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+    .with_highlight(span)
+}
+
 /// Represents a node in an evaluation graph.
 #[derive(Debug, Clone)]
 pub enum GraphNode {
@@ -62,6 +124,35 @@ pub enum GraphNode {
     Hints(HintsSection),
 }
 
+impl GraphNode {
+    /// Gets the name of the node.
+    ///
+    /// This is the name of the declaration for declaration nodes and the name
+    /// of the section otherwise.
+    fn name(&self) -> String {
+        match self {
+            Self::Input(decl) | Self::Decl(decl) | Self::Output(decl) => {
+                decl.name().as_str().to_string()
+            }
+            Self::Command(_) => "command".to_string(),
+            Self::Runtime(_) => "runtime".to_string(),
+            Self::Requirements(_) => "requirements".to_string(),
+            Self::Hints(_) => "hints".to_string(),
+        }
+    }
+
+    /// Gets the span of the node.
+    fn span(&self) -> Span {
+        match self {
+            Self::Input(decl) | Self::Decl(decl) | Self::Output(decl) => decl.name().span(),
+            Self::Command(section) => section.span(),
+            Self::Runtime(section) => section.span(),
+            Self::Requirements(section) => section.span(),
+            Self::Hints(section) => section.span(),
+        }
+    }
+}
+
 /// Represents a task evaluation graph.
 ///
 /// This is used to evaluate declarations and sections in topological order.
@@ -163,12 +254,27 @@ impl TaskEvaluationGraph {
     }
 
     /// Performs a topological sort of the graph nodes.
-    pub fn toposort(&self) -> Vec<GraphNode> {
-        toposort(&self.inner, None)
-            .expect("graph should be acyclic")
-            .into_iter()
-            .map(|i| self.inner[i].clone())
-            .collect()
+    ///
+    /// Returns a diagnostic naming the nodes involved if the graph contains a
+    /// cycle.
+    pub fn toposort(&self) -> Result<Vec<GraphNode>, Diagnostic> {
+        match toposort(&self.inner, None) {
+            Ok(indexes) => Ok(indexes.into_iter().map(|i| self.inner[i].clone()).collect()),
+            Err(cycle) => {
+                // The nodes in the cycle are those strongly connected to the
+                // node that was reported
+                let index = cycle.node_id();
+                let names = tarjan_scc(&self.inner)
+                    .into_iter()
+                    .find(|component| component.contains(&index))
+                    .unwrap_or_else(|| vec![index])
+                    .into_iter()
+                    .map(|i| self.inner[i].name())
+                    .collect::<Vec<_>>();
+
+                Err(evaluation_cycle(&names, self.inner[index].span()))
+            }
+        }
     }
 
     /// Adds a declaration node to the graph.
@@ -273,10 +379,15 @@ pub struct EvaluatedTask<'a> {
     command: String,
     /// The evaluated requirements for running the command.
     requirements: IndexMap<TokenStrHash<Ident>, Value>,
+    /// The requirements overridden by the caller, which take precedence over
+    /// the evaluated requirements.
+    overrides: IndexMap<String, Value>,
     /// The evaluated hints for running the command.
     hints: IndexMap<String, Value>,
     /// The map from input paths to localized paths within the execution environment.
     paths: IndexMap<String, String>,
+    /// The input paths that are directories.
+    directories: IndexSet<String>,
     /// The evaluation scope for evaluating the task so far.
     scope: HashMap<TokenStrHash<Ident>, Value>,
     /// The evaluation nodes; this is used to evaluate the outputs after the task is executed.
@@ -291,8 +402,10 @@ impl<'a> EvaluatedTask<'a> {
         Self {
             command: String::new(),
             requirements: Default::default(),
+            overrides: Default::default(),
             hints: Default::default(),
             paths: Default::default(),
+            directories: Default::default(),
             scope: Default::default(),
             nodes,
             outputs: None,
@@ -311,16 +424,101 @@ impl<'a> EvaluatedTask<'a> {
         &self.paths
     }
 
+    /// The local paths (i.e. keys of [`paths`](Self::paths)) of the
+    /// `Directory` inputs.
+    ///
+    /// A directory should be localized with its contents, recursively.
+    pub fn directories(&self) -> &IndexSet<String> {
+        &self.directories
+    }
+
     /// The evaluated requirements for running the command.
+    ///
+    /// These do not include any [overridden
+    /// requirements](Self::override_requirement); use
+    /// [`requirement`](Self::requirement) to get the effective value of a
+    /// requirement.
     pub fn requirements(&self) -> &IndexMap<TokenStrHash<Ident>, Value> {
         &self.requirements
     }
 
+    /// Overrides the value of a requirement, regardless of whether the task
+    /// declares it.
+    ///
+    /// # Notes
+    ///
+    /// An override takes precedence over an evaluated requirement of the same
+    /// name or any of its aliases (e.g. overriding `container` also overrides
+    /// a declared `docker`).
+    pub fn override_requirement(&mut self, name: impl Into<String>, value: Value) {
+        self.overrides.insert(name.into(), value);
+    }
+
+    /// Gets the effective value of the requirement with the first of the
+    /// given names (i.e. a name and its aliases) that is present.
+    ///
+    /// Overridden requirements are considered before evaluated requirements.
+    pub fn requirement(&self, names: &[&str]) -> Option<&Value> {
+        names
+            .iter()
+            .find_map(|n| self.overrides.get(*n))
+            .or_else(|| names.iter().find_map(|n| self.requirements.get(*n)))
+    }
+
     /// The evaluated hints for running the command.
     pub fn hints(&self) -> &IndexMap<String, Value> {
         &self.hints
     }
 
+    /// Gets the resources requested by the `cpu`, `memory`, `disks` (or
+    /// `disk`), and `max_retries` (or `maxRetries`) requirements.
+    ///
+    /// Memory sizes without a unit are in bytes and disk sizes without a unit
+    /// are in gibibytes. The sizes of multiple disks are summed.
+    pub fn resources(&self, runtime: &Runtime<'_>) -> Result<Resources> {
+        let cpu = match self.requirement(&["cpu"]) {
+            Some(Value::Integer(v)) => Some(*v as f64),
+            Some(Value::Float(v)) => Some(v.into_inner()),
+            Some(_) => bail!("requirement `cpu` must be an `Int` or a `Float`"),
+            None => None,
+        };
+
+        let memory = match self.requirement(&["memory"]) {
+            Some(Value::Integer(v)) => match u64::try_from(*v) {
+                Ok(v) => Some(v),
+                Err(_) => bail!("requirement `memory` must not be negative"),
+            },
+            Some(Value::String(sym)) => Some(
+                parse_memory(runtime.resolve_str(*sym)).context("invalid requirement `memory`")?,
+            ),
+            Some(_) => bail!("requirement `memory` must be an `Int` or a `String`"),
+            None => None,
+        };
+
+        let disks = match self.requirement(&["disks", "disk"]) {
+            Some(value) => {
+                Some(disks_size(runtime, *value).context("invalid requirement `disks`")?)
+            }
+            None => None,
+        };
+
+        let max_retries = match self.requirement(&["max_retries", "maxRetries"]) {
+            Some(Value::Integer(v)) => match u64::try_from(*v) {
+                Ok(v) => Some(v),
+                Err(_) => bail!("requirement `max_retries` must not be negative"),
+            },
+            Some(_) => bail!("requirement `max_retries` must be an `Int`"),
+            None => None,
+        };
+
+        Ok(Resources {
+            cpu,
+            memory,
+            disks,
+            max_retries,
+        })
+    }
+
     /// Evaluates the outputs of the task given the stdout and stderr from task execution.
     pub fn outputs(
         &self,
@@ -351,6 +549,83 @@ impl<'a> EvaluatedTask<'a> {
     }
 }
 
+/// Calculates the total size, in bytes, of a `disks` requirement.
+///
+/// Each disk specification is a size, optionally preceded by a mount point and
+/// followed by a unit (e.g. `/mnt/data 10 GiB`).
+fn disks_size(runtime: &Runtime<'_>, value: Value) -> Result<u64> {
+    match value {
+        Value::Integer(v) => {
+            let v = match u64::try_from(v) {
+                Ok(v) => v,
+                Err(_) => bail!("size `{v}` must not be negative"),
+            };
+            v.checked_mul(GIBIBYTE)
+                .with_context(|| format!("size `{v}` is too large"))
+        }
+        Value::String(sym) => {
+            let spec = runtime.resolve_str(sym).trim();
+            let size = match spec.split_once(char::is_whitespace) {
+                Some((mount, size)) if mount.starts_with('/') => size,
+                _ => spec,
+            };
+            parse_size(size, GIBIBYTE)
+        }
+        Value::Stored(_, id) => match runtime.stored(id) {
+            StoredValue::Array(elements) => elements.iter().try_fold(0u64, |total, v| {
+                total
+                    .checked_add(disks_size(runtime, *v)?)
+                    .context("the total size is too large")
+            }),
+            _ => bail!("expected an `Int`, a `String`, or an `Array[String]`"),
+        },
+        _ => bail!("expected an `Int`, a `String`, or an `Array[String]`"),
+    }
+}
+
+/// Localizes a `File` or `Directory` input value beneath the given base path.
+///
+/// The host path is mirrored beneath the base path (e.g. `/data/reads.bam` is
+/// localized to `<base>/data/reads.bam`) and the mapping is recorded in
+/// `paths`; the paths of directories are also recorded in `directories`.
+/// Values of any other type are returned unchanged.
+fn localize(
+    runtime: &mut Runtime<'_>,
+    paths: &mut IndexMap<String, String>,
+    directories: &mut IndexSet<String>,
+    base: &Path,
+    value: Value,
+    ty: Type,
+) -> Value {
+    let kind = match ty.as_primitive() {
+        Some(ty) => ty.kind(),
+        None => return value,
+    };
+
+    let path = match (kind, value) {
+        (
+            PrimitiveTypeKind::File | PrimitiveTypeKind::Directory,
+            Value::String(sym) | Value::File(sym) | Value::Directory(sym),
+        ) => runtime.resolve_str(sym).to_string(),
+        _ => return value,
+    };
+
+    let localized = base
+        .join(path.trim_start_matches('/'))
+        .to_string_lossy()
+        .into_owned();
+    let value = match kind {
+        PrimitiveTypeKind::File => runtime.new_file(&localized),
+        _ => {
+            directories.insert(path.clone());
+            runtime.new_directory(&localized)
+        }
+    };
+
+    paths.insert(path, localized);
+    value
+}
+
 /// Represents a task evaluator.
 #[derive(Debug)]
 pub struct TaskEvaluator {
@@ -362,22 +637,30 @@ pub struct TaskEvaluator {
 
 impl TaskEvaluator {
     /// Constructs a new task based on a definition and its inputs.
-    pub fn new(definition: TaskDefinition) -> Self {
+    ///
+    /// Returns a diagnostic if the declarations and sections of the task form
+    /// a cycle.
+    pub fn new(definition: TaskDefinition) -> Result<Self, Diagnostic> {
         let graph = TaskEvaluationGraph::new(&definition);
-        let nodes = graph.toposort();
-        Self {
+        let nodes = graph.toposort()?;
+        Ok(Self {
             name: definition.name(),
             nodes,
-        }
+        })
     }
 
     /// Evaluates the task with the given base path to use for file localization.
+    ///
+    /// `File` and `Directory` inputs are localized beneath the base path (see
+    /// [`EvaluatedTask::paths`]) and the localized paths are used when
+    /// evaluating the command.
     pub fn evaluate<'a>(
         &'a self,
         runtime: &mut Runtime<'_>,
         inputs: &HashMap<String, Value>,
-        _base: impl AsRef<Path>,
+        base: impl AsRef<Path>,
     ) -> Result<EvaluatedTask<'a>, Diagnostic> {
+        let base = base.as_ref();
         let mut evaluated = EvaluatedTask::new(&self.nodes);
 
         // Start by walking the nodes looking for input decls to populate the scope
@@ -394,17 +677,33 @@ impl TaskEvaluator {
                         {
                             if let Some(ty) = n.ty() {
                                 let ty = runtime.import_type(ty);
-                                if !value.ty().is_coercible_to(runtime.types(), &ty) {
-                                    return Err(input_type_mismatch(
-                                        runtime.types(),
-                                        name.as_str(),
-                                        ty,
-                                        value.ty(),
-                                        decl.ty().span(),
-                                    ));
-                                }
+                                let value = match value.coerce(runtime, ty) {
+                                    Some(value) => value,
+                                    // NOTE: `coerce` does not yet convert compound values, so
+                                    // those that are coercible are bound as provided
+                                    None if value.ty().is_coercible_to(runtime.types(), &ty) => {
+                                        *value
+                                    }
+                                    None => {
+                                        return Err(input_type_mismatch(
+                                            runtime.types(),
+                                            name.as_str(),
+                                            ty,
+                                            value.ty(),
+                                            decl.ty().span(),
+                                        ));
+                                    }
+                                };
 
-                                evaluated.scope.insert(TokenStrHash::new(name), *value);
+                                let value = localize(
+                                    runtime,
+                                    &mut evaluated.paths,
+                                    &mut evaluated.directories,
+                                    base,
+                                    value,
+                                    ty,
+                                );
+                                evaluated.scope.insert(TokenStrHash::new(name), value);
                             } else {
                                 todo!("handle unknown type");
                             }
@@ -443,6 +742,13 @@ impl TaskEvaluator {
                     let expr = decl.expr().expect("declaration should be bound");
                     let evaluator = ExprEvaluator::new(&evaluated.scope);
                     let value = evaluator.evaluate_expr(runtime, &expr)?;
+
+                    // Only optional declarations may be `None`
+                    let ty = decl.ty();
+                    if value == Value::None && !ty.is_optional() {
+                        return Err(required_is_none(&name, ty.span()));
+                    }
+
                     evaluated.scope.insert(TokenStrHash::new(name), value);
                 }
                 GraphNode::Requirements(section) => {
@@ -466,19 +772,38 @@ impl TaskEvaluator {
                 GraphNode::Command(section) => {
                     // TODO: set `task` variable in scope for 1.2 documents
                     let evaluator = ExprEvaluator::new(&evaluated.scope);
-                    for part in section.parts() {
+                    let parts = section.parts().collect::<Vec<_>>();
+
+                    // The common leading whitespace is calculated from the
+                    // command as written so that multi-line values of
+                    // placeholders cannot change it
+                    let leading_whitespace =
+                        command_leading_whitespace(parts.iter().map(|part| match part {
+                            CommandPart::Text(text) => Some(text.as_str()),
+                            CommandPart::Placeholder(_) => None,
+                        }));
+
+                    let mut line_start = true;
+                    for part in parts {
                         match part {
-                            CommandPart::Text(text) => evaluated.command.push_str(text.as_str()),
-                            CommandPart::Placeholder(placeholder) => evaluator
-                                .evaluate_placeholder(
+                            CommandPart::Text(text) => {
+                                line_start = push_command_text(
+                                    &mut evaluated.command,
+                                    text.as_str(),
+                                    &leading_whitespace,
+                                    line_start,
+                                );
+                            }
+                            CommandPart::Placeholder(placeholder) => {
+                                evaluator.evaluate_placeholder(
                                     runtime,
                                     &placeholder,
                                     &mut evaluated.command,
-                                )?,
+                                )?;
+                                line_start = false;
+                            }
                         }
                     }
-
-                    evaluated.command = strip_leading_whitespace(&evaluated.command, true);
                 }
                 GraphNode::Output(_) => {
                     evaluated.outputs = Some(index);
@@ -555,7 +880,7 @@ task test {
 
         let mut runtime = Runtime::new(results[0].scope());
         let inputs = HashMap::new();
-        let evaluator = TaskEvaluator::new(task);
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
         let evaluated = evaluator
             .evaluate(&mut runtime, &inputs, "/tmp")
             .expect("should evaluate");
@@ -573,4 +898,716 @@ task test {
             }
         }
     }
+
+    #[tokio::test]
+    async fn placeholder_options() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    input {
+        Array[String] names = ["Peter", "Paul", "Mary"]
+        Array[Int] numbers = [1, 2, 3]
+        Array[String] empty = []
+        Boolean yes = true
+        Boolean no = false
+        String? missing
+        String present = "here"
+        String delimiter = "; "
+    }
+
+    command <<<
+        greet ~{sep=", " names}
+        sum ~{sep="+" numbers} [~{sep="," empty}]
+        ~{true="--yes" false="--no" yes} ~{true="--yes" false="--no" no}
+        ~{default="n/a" missing} ~{default="n/a" present} ~{default=1 missing}
+        ~{sep="~{delimiter}" names}
+    >>>
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let inputs = HashMap::new();
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        let evaluated = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .expect("should evaluate");
+
+        assert_eq!(
+            evaluated.command().trim(),
+            "greet Peter, Paul, Mary\nsum 1+2+3 []\n--yes --no\nn/a here 1\nPeter; Paul; Mary"
+        );
+    }
+
+    /// Analyzes a document containing a single task and evaluates its command
+    /// with the given inputs.
+    async fn evaluate_command(
+        source: &str,
+        inputs: impl FnOnce(&mut Runtime<'_>) -> HashMap<String, Value>,
+    ) -> Result<String, String> {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(dir.path().join("foo.wdl"), source).expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+
+        // `None` may only be coerced to optional types
+        let string: Type = PrimitiveTypeKind::String.into();
+        assert_eq!(
+            Value::None.coerce(&mut runtime, string.optional()),
+            Some(Value::None)
+        );
+        assert_eq!(Value::None.coerce(&mut runtime, string), None);
+
+        let inputs = inputs(&mut runtime);
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .map(|evaluated| evaluated.command().trim().to_string())
+            .map_err(|diagnostic| diagnostic.message().to_string())
+    }
+
+    #[tokio::test]
+    async fn optional_inputs_may_be_none() {
+        let source = r#"version 1.1
+
+task test {
+    input {
+        String? maybe
+        Int? number = None
+        String name = "Peter"
+    }
+
+    String? copy = maybe
+
+    command <<<
+        echo [~{maybe}] [~{copy}] [~{number}] ~{name}
+    >>>
+}
+"#;
+
+        let command = evaluate_command(source, |_| HashMap::new())
+            .await
+            .expect("should evaluate");
+        assert_eq!(command, "echo [] [] [] Peter");
+
+        let command = evaluate_command(source, |runtime| {
+            HashMap::from([
+                (String::from("maybe"), runtime.new_string("hi")),
+                (String::from("number"), Value::None),
+            ])
+        })
+        .await
+        .expect("should evaluate");
+        assert_eq!(command, "echo [hi] [hi] [] Peter");
+
+        // A required input cannot be `None`
+        let message = evaluate_command(source, |_| {
+            HashMap::from([(String::from("name"), Value::None)])
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            message,
+            "type mismatch for input `name`: expected type `String`, but found type `None`"
+        );
+    }
+
+    #[tokio::test]
+    async fn multi_line_placeholders_do_not_change_the_indentation() {
+        let source = r#"version 1.1
+
+task test {
+    input {
+        String text
+    }
+
+    command <<<
+        cat <<EOF
+        ~{text}
+          indented
+        EOF
+    >>>
+}
+"#;
+
+        let command = evaluate_command(source, |runtime| {
+            HashMap::from([(
+                String::from("text"),
+                runtime.new_string("line one\nline two"),
+            )])
+        })
+        .await
+        .expect("should evaluate");
+        assert_eq!(command, "cat <<EOF\nline one\nline two\n  indented\nEOF");
+    }
+
+    #[tokio::test]
+    async fn inputs_are_coerced_to_their_declared_types() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    input {
+        Float ratio
+        String? label
+        Int count = 1
+    }
+
+    command <<<
+        echo ~{ratio} ~{label} ~{count}
+    >>>
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+
+        let inputs = HashMap::from([
+            (String::from("ratio"), Value::Integer(2)),
+            (String::from("label"), runtime.new_file("/data/reads.bam")),
+        ]);
+        let evaluated = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .expect("should evaluate");
+        assert_eq!(evaluated.scope["ratio"], Value::Float(2.0_f64.into()));
+        assert!(matches!(evaluated.scope["label"], Value::String(_)));
+
+        // A value that cannot be coerced is reported against the declaration
+        let inputs = HashMap::from([(String::from("count"), runtime.new_string("many"))]);
+        let message = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .map(|_| ())
+            .unwrap_err()
+            .message()
+            .to_string();
+        assert_eq!(
+            message,
+            "type mismatch for input `count`: expected type `Int`, but found type `String`"
+        );
+    }
+
+    #[tokio::test]
+    async fn overridden_requirements_take_precedence() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    command <<<
+        echo hi
+    >>>
+
+    runtime {
+        docker: "ubuntu:22.04"
+        cpu: 1
+        memory: "2 GiB"
+    }
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        let mut evaluated = evaluator
+            .evaluate(&mut runtime, &HashMap::new(), "/tmp")
+            .expect("should evaluate");
+
+        let container = runtime.new_string("ubuntu:24.04");
+        let memory = runtime.new_string("16GB");
+        evaluated.override_requirement("container", container);
+        evaluated.override_requirement("cpu", Value::Integer(4));
+        evaluated.override_requirement("memory", memory);
+
+        // The override of an alias takes precedence over the declared name
+        assert_eq!(
+            evaluated
+                .requirement(&["container", "docker"])
+                .map(|v| v.unwrap_string(&runtime)),
+            Some("ubuntu:24.04")
+        );
+        assert_eq!(
+            evaluated.requirements()["cpu"],
+            Value::Integer(1),
+            "evaluated requirements should be unchanged"
+        );
+
+        let resources = evaluated.resources(&runtime).expect("should be valid");
+        assert_eq!(resources.cpu(), Some(4.0));
+        assert_eq!(resources.memory(), Some(16_000_000_000));
+    }
+
+    #[tokio::test]
+    async fn directory_inputs_are_localized() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    input {
+        Directory data
+        File reads
+    }
+
+    command <<<
+        ls ~{data} ~{reads}
+    >>>
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let inputs = HashMap::from([
+            (String::from("data"), runtime.new_string("/data/refs")),
+            (String::from("reads"), runtime.new_string("/data/reads.bam")),
+        ]);
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        let evaluated = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .expect("should evaluate");
+
+        assert_eq!(
+            evaluated.command().trim(),
+            "ls /tmp/data/refs /tmp/data/reads.bam"
+        );
+        assert_eq!(evaluated.paths()["/data/refs"], "/tmp/data/refs");
+        assert_eq!(
+            evaluated.directories().iter().collect::<Vec<_>>(),
+            ["/data/refs"]
+        );
+    }
+
+    #[tokio::test]
+    async fn files_are_written_to_the_execution_dir() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    File lines = write_lines(["a", "b"])
+    File tsv = write_tsv([["a", "b"], ["c", "d"]])
+
+    command <<<
+        cat ~{lines} ~{tsv}
+    >>>
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let execution_dir = dir.path().join("execution");
+        let mut runtime = Runtime::new_with_execution_dir(results[0].scope(), &execution_dir);
+        assert_eq!(runtime.execution_dir(), execution_dir);
+
+        let inputs = HashMap::new();
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        let evaluated = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp")
+            .expect("should evaluate");
+
+        let paths = evaluated
+            .command()
+            .trim()
+            .strip_prefix("cat ")
+            .expect("should have paths")
+            .split(' ')
+            .map(Path::new)
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.starts_with(&execution_dir)));
+        assert_eq!(fs::read_to_string(paths[0]).unwrap(), "a\nb\n");
+        assert_eq!(fs::read_to_string(paths[1]).unwrap(), "a\tb\nc\td\n");
+    }
+
+    /// Analyzes a document containing a single task and evaluates the
+    /// resources it requests.
+    async fn evaluate_resources(source: &str) -> Result<Resources, String> {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(dir.path().join("foo.wdl"), source).expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let evaluator = TaskEvaluator::new(task).expect("should not have a cycle");
+        let evaluated = evaluator
+            .evaluate(&mut runtime, &HashMap::new(), "/tmp")
+            .expect("should evaluate");
+        evaluated.resources(&runtime).map_err(|e| format!("{e:#}"))
+    }
+
+    #[tokio::test]
+    async fn resources_are_read_from_the_requirements() {
+        let resources = evaluate_resources(
+            r#"version 1.1
+
+task test {
+    command <<<
+        echo hi
+    >>>
+
+    runtime {
+        cpu: 1.5
+        memory: "512 MiB"
+        disks: ["/mnt/data 10 GiB", "5"]
+        maxRetries: 3
+    }
+}
+"#,
+        )
+        .await
+        .expect("should be valid");
+        assert_eq!(resources.cpu(), Some(1.5));
+        assert_eq!(resources.memory(), Some(512 * 1024 * 1024));
+        assert_eq!(resources.disks(), Some(15 * GIBIBYTE));
+        assert_eq!(resources.max_retries(), Some(3));
+
+        let resources = evaluate_resources(
+            r#"version 1.1
+
+task test {
+    command <<<
+        echo hi
+    >>>
+}
+"#,
+        )
+        .await
+        .expect("should be valid");
+        assert_eq!(resources, Resources::default());
+
+        let message = evaluate_resources(
+            r#"version 1.1
+
+task test {
+    command <<<
+        echo hi
+    >>>
+
+    runtime {
+        memory: "lots"
+    }
+}
+"#,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(message, "invalid requirement `memory`: invalid size `lots`");
+
+        for (requirement, message) in [
+            ("memory: -1", "requirement `memory` must not be negative"),
+            (
+                "maxRetries: -1",
+                "requirement `max_retries` must not be negative",
+            ),
+            (
+                "disks: -1",
+                "invalid requirement `disks`: size `-1` must not be negative",
+            ),
+        ] {
+            let source = format!(
+                r#"version 1.1
+
+task test {{
+    command <<<
+        echo hi
+    >>>
+
+    runtime {{
+        {requirement}
+    }}
+}}
+"#
+            );
+            assert_eq!(evaluate_resources(&source).await.unwrap_err(), message);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_file_and_directory_values_are_localized() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            "version 1.1\n\nworkflow test {}\n",
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let mut paths = IndexMap::new();
+        let mut directories = IndexSet::new();
+        let base = Path::new("/mnt/inputs");
+
+        let value = runtime.new_string("/data/reads.bam");
+        let localized = localize(
+            &mut runtime,
+            &mut paths,
+            &mut directories,
+            base,
+            value,
+            PrimitiveTypeKind::File.into(),
+        );
+        assert!(matches!(localized, Value::File(_)));
+        assert_eq!(
+            localized.unwrap_file(&runtime),
+            "/mnt/inputs/data/reads.bam"
+        );
+
+        let value = runtime.new_string("/data/reads.bam");
+        let unchanged = localize(
+            &mut runtime,
+            &mut paths,
+            &mut directories,
+            base,
+            value,
+            PrimitiveTypeKind::String.into(),
+        );
+        assert_eq!(unchanged, value);
+
+        assert_eq!(
+            paths.into_iter().collect::<Vec<_>>(),
+            [(
+                String::from("/data/reads.bam"),
+                String::from("/mnt/inputs/data/reads.bam")
+            )]
+        );
+        assert!(directories.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cycles_are_reported_as_diagnostics() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("foo.wdl"),
+            r#"version 1.1
+
+task test {
+    Int a = 1
+    Int b = a
+
+    command <<<
+        echo ~{b}
+    >>>
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+
+        let task = document
+            .ast()
+            .as_v1()
+            .expect("should be a V1 AST")
+            .tasks()
+            .next()
+            .expect("should have task");
+
+        // Name references that would form a cycle are never added, so add one
+        // directly to make `a` depend on `b`
+        let mut graph = TaskEvaluationGraph::new(&task);
+        let a = graph.names["a"];
+        let b = graph.names["b"];
+        graph.inner.update_edge(b, a, ());
+
+        let diagnostic = graph.toposort().unwrap_err();
+        assert!(
+            diagnostic.message() == "task evaluation contains a cycle between `a`, `b`"
+                || diagnostic.message() == "task evaluation contains a cycle between `b`, `a`",
+            "{message}",
+            message = diagnostic.message()
+        );
+    }
 }
@@ -1,6 +1,8 @@
 //! Module for utility functions.
 //!
-//! Currently that is whitespace stripping.
+//! Currently that is whitespace stripping and size parsing.
+
+use anyhow::{Context, Result};
 
 /// Removes line continuations from a string.
 fn remove_line_continuations(s: &str) -> String {
@@ -26,32 +28,30 @@ fn remove_line_continuations(s: &str) -> String {
     result
 }
 
-/// Calculates the leading whitespace of a string.
-fn calculate_leading_whitespace(s: &str) -> usize {
-    let mut min_leading_whitespace = usize::MAX;
-    let mut parsing_leading_whitespace = true;
-    let mut cur_char_index = 0;
-    let mut cur_line_index = 0;
-    s.chars().for_each(|c| match c {
-        ' ' | '\t' if parsing_leading_whitespace => cur_char_index += 1,
-        '\n' => {
-            parsing_leading_whitespace = true;
-            cur_line_index += 1;
-            cur_char_index = 0;
-        }
-        _ => {
-            parsing_leading_whitespace = false;
-            if cur_char_index < min_leading_whitespace {
-                min_leading_whitespace = cur_char_index;
-            }
-            cur_char_index += 1;
+/// Calculates the length of the common prefix of two strings.
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(a, b)| a == b).count()
+}
+
+/// Calculates the leading whitespace common to each line of a string.
+///
+/// This is the longest literal prefix of whitespace shared by every line, so a
+/// tab and a space are never considered interchangeable. Lines that consist
+/// only of whitespace do not contribute to the prefix.
+fn calculate_leading_whitespace(s: &str) -> &str {
+    let mut common: Option<&str> = None;
+    for line in s.lines() {
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() {
+            continue;
         }
-    });
-    if min_leading_whitespace == usize::MAX {
-        0
-    } else {
-        min_leading_whitespace
+        let leading = &line[..line.len() - content.len()];
+        common = Some(match common {
+            Some(common) => &common[..common_prefix_len(common, leading)],
+            None => leading,
+        });
     }
+    common.unwrap_or_default()
 }
 
 /// Strips leading whitespace from a string.
@@ -65,17 +65,102 @@ pub fn strip_leading_whitespace(s: &str, command: bool) -> String {
     let result = s_owned
         .lines()
         .map(|line| {
-            if line.len() >= leading_whitespace {
-                &line[leading_whitespace..]
-            } else {
-                ""
-            }
+            // NOTE: only lines consisting entirely of whitespace may not start
+            // with the common prefix. Those lines are only stripped of the part
+            // of the prefix they share so that the remaining whitespace is
+            // preserved.
+            &line[common_prefix_len(line, leading_whitespace)..]
         })
         .collect::<Vec<&str>>()
         .join("\n");
     result
 }
 
+/// Calculates the leading whitespace common to each line of a command before
+/// its placeholders are substituted.
+///
+/// The command is given as its parts, where [`None`] stands for a
+/// placeholder. A placeholder counts as content, so the values it expands to
+/// never affect the result.
+pub fn command_leading_whitespace<'a>(parts: impl Iterator<Item = Option<&'a str>>) -> String {
+    let raw = parts.map(|part| part.unwrap_or("~{}")).collect::<String>();
+    calculate_leading_whitespace(&raw).to_string()
+}
+
+/// Appends a literal text part of a command to `command`, stripping the
+/// command's common leading whitespace from every line that starts within
+/// the text.
+///
+/// `line_start` is whether the text starts a line. Returns whether the next
+/// part of the command starts a line.
+pub fn push_command_text(
+    command: &mut String,
+    text: &str,
+    leading_whitespace: &str,
+    mut line_start: bool,
+) -> bool {
+    for line in text.split_inclusive('\n') {
+        // NOTE: as in `strip_leading_whitespace`, a line consisting entirely
+        // of whitespace is only stripped of the part of the prefix it shares.
+        let line = if line_start {
+            &line[common_prefix_len(line, leading_whitespace)..]
+        } else {
+            line
+        };
+        command.push_str(line);
+        line_start = line.ends_with('\n');
+    }
+    line_start
+}
+
+/// The number of bytes in a gibibyte.
+pub const GIBIBYTE: u64 = 1024 * 1024 * 1024;
+
+/// Gets the number of bytes in a size unit.
+pub fn unit_bytes(unit: &str) -> Option<u64> {
+    Some(match unit {
+        "B" => 1,
+        "KB" | "K" => 1000,
+        "MB" | "M" => 1000u64.pow(2),
+        "GB" | "G" => 1000u64.pow(3),
+        "TB" | "T" => 1000u64.pow(4),
+        "KiB" | "Ki" => 1024,
+        "MiB" | "Mi" => 1024u64.pow(2),
+        "GiB" | "Gi" => GIBIBYTE,
+        "TiB" | "Ti" => 1024u64.pow(4),
+        _ => return None,
+    })
+}
+
+/// Parses a size with an optional unit suffix (e.g. `4 GB` or `4GB`) into
+/// bytes.
+///
+/// A size without a unit is multiplied by the given default unit size.
+pub fn parse_size(s: &str, default: u64) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("invalid size `{s}`"))?;
+    let unit = match unit.trim() {
+        "" => default,
+        unit => unit_bytes(unit).with_context(|| format!("invalid size unit `{unit}`"))?,
+    };
+
+    Ok((number * unit as f64) as u64)
+}
+
+/// Parses a WDL memory size (e.g. `4 GB`, `4GB`, or `512 MiB`) into bytes.
+///
+/// Both decimal (`KB`, `MB`, `GB`, `TB`) and binary (`KiB`, `MiB`, `GiB`,
+/// `TiB`) units are supported; a number without a unit is in bytes.
+pub fn parse_memory(s: &str) -> Result<u64> {
+    parse_size(s, 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,11 +179,14 @@ second line";
         let input = "    first line is indented 4 spaces
         second line is indented 8 spaces
     third line is indented 4 spaces";
-        assert_eq!(calculate_leading_whitespace(input), 4);
+        assert_eq!(calculate_leading_whitespace(input), "    ");
         let input = "    first line is indented 4 spaces
  \t \t    second line is indented with a mix of 8 spaces and tabs
 \t\t\t\tfourth line is indented 4 tabs";
-        assert_eq!(calculate_leading_whitespace(input), 4);
+        assert_eq!(calculate_leading_whitespace(input), "");
+        let input = "\t  first line is indented a tab and 2 spaces
+\t\tsecond line is indented 2 tabs";
+        assert_eq!(calculate_leading_whitespace(input), "\t");
     }
 
     #[test]
@@ -119,4 +207,66 @@ second line";
         let expected = "first line is indented 4 spaces and trails a backslash \\\n    second line is indented 8 spaces\nthird line is indented 4 spaces";
         assert_eq!(strip_leading_whitespace(input, true), expected);
     }
+
+    #[test]
+    fn test_strip_leading_whitespace_mixed_tabs_and_spaces() {
+        let input = "\t\tfirst line is indented 2 tabs
+\t  second line is indented a tab and 2 spaces";
+        let expected =
+            "\tfirst line is indented 2 tabs\n  second line is indented a tab and 2 spaces";
+        assert_eq!(strip_leading_whitespace(input, true), expected);
+
+        let input = "\tfirst line is indented a tab
+    second line is indented 4 spaces";
+        assert_eq!(strip_leading_whitespace(input, true), input);
+    }
+
+    #[test]
+    fn test_strip_leading_whitespace_ignores_blank_lines() {
+        let input = "    first line is indented 4 spaces
+
+    third line is indented 4 spaces
+  
+\t\t
+    sixth line is indented 4 spaces";
+        assert_eq!(calculate_leading_whitespace(input), "    ");
+        let expected = "first line is indented 4 spaces\n\nthird line is indented 4 spaces\n\n\t\t\nsixth line is indented 4 spaces";
+        assert_eq!(strip_leading_whitespace(input, true), expected);
+    }
+
+    #[test]
+    fn test_command_leading_whitespace_treats_placeholders_as_content() {
+        let parts = [
+            Some("\n    echo "),
+            None,
+            Some("\n    "),
+            None,
+            Some("\n      done\n"),
+        ];
+        assert_eq!(command_leading_whitespace(parts.into_iter()), "    ");
+
+        let parts = [Some("\n    echo\n"), None, Some("\n    done\n")];
+        assert_eq!(command_leading_whitespace(parts.into_iter()), "");
+    }
+
+    #[test]
+    fn test_push_command_text() {
+        let mut command = String::new();
+        let line_start = push_command_text(&mut command, "\n    echo ", "    ", true);
+        assert!(!line_start);
+        command.push_str("a\n  b");
+        let line_start = push_command_text(&mut command, "\n      done\n  \n", "    ", line_start);
+        assert!(line_start);
+        assert_eq!(command, "\necho a\n  b\n  done\n\n");
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("4 GB").unwrap(), 4_000_000_000);
+        assert_eq!(parse_memory("4GB").unwrap(), 4_000_000_000);
+        assert_eq!(parse_memory("4096 MiB").unwrap(), 4 * GIBIBYTE);
+        assert_eq!(parse_memory("1024").unwrap(), 1024);
+        assert!(parse_memory("lots").is_err());
+        assert!(parse_memory("4 GBs").is_err());
+    }
 }
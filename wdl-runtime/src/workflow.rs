@@ -0,0 +1,604 @@
+//! Representation of workflow evaluation.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use futures::future::join_all;
+use wdl_analysis::diagnostics::unknown_name;
+use wdl_ast::{
+    v1::{
+        CallStatement, Decl, ScatterStatement, TaskDefinition, TaskItem, WorkflowDefinition,
+        WorkflowItem, WorkflowStatement,
+    },
+    AstNodeExt, AstToken, Diagnostic, Ident, Span, TokenStrHash,
+};
+
+use crate::{v1::ExprEvaluator, EvaluatedTask, Runtime, StoredValue, TaskEvaluator, Value};
+
+/// Creates a "missing input" diagnostic.
+fn missing_input(workflow: &str, input: &Ident) -> Diagnostic {
+    Diagnostic::error(format!(
+        "missing input `{input}` for workflow `{workflow}`",
+        input = input.as_str()
+    ))
+    .with_label("a value must be specified for this input", input.span())
+}
+
+/// Creates an "unsupported statement" diagnostic.
+fn unsupported_statement(kind: &str, span: Span) -> Diagnostic {
+    Diagnostic::error(format!("{kind} statements are not yet supported"))
+        .with_label("this statement cannot be evaluated", span)
+}
+
+/// Creates an "unknown task" diagnostic.
+fn unknown_task(call: &CallStatement) -> Diagnostic {
+    let target = call.target();
+    Diagnostic::error(format!(
+        "unknown task `{target}`: only tasks defined in the same document can be called",
+        target = target
+            .names()
+            .map(|n| n.as_str().to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    ))
+    .with_highlight(target.span())
+}
+
+/// Creates a "call failed" diagnostic.
+fn call_failed(name: &Ident, error: &anyhow::Error) -> Diagnostic {
+    Diagnostic::error(format!(
+        "call `{name}` failed: {error:#}",
+        name = name.as_str()
+    ))
+    .with_highlight(name.span())
+}
+
+/// Creates a "cannot scatter" diagnostic.
+fn cannot_scatter(runtime: &Runtime<'_>, value: &Value, span: Span) -> Diagnostic {
+    Diagnostic::error(format!(
+        "cannot scatter over a value of type `{ty}`",
+        ty = value.ty().display(runtime.types())
+    ))
+    .with_label("this expression must evaluate to an array", span)
+}
+
+/// Gets the names of the outputs of a task.
+fn output_names(task: &TaskDefinition) -> Vec<String> {
+    task.items()
+        .filter_map(|item| match item {
+            TaskItem::Output(section) => Some(section),
+            _ => None,
+        })
+        .flat_map(|section| section.declarations())
+        .map(|decl| decl.name().as_str().to_string())
+        .collect()
+}
+
+/// Represents the output of running the task of a call.
+#[derive(Debug, Clone)]
+pub struct CallOutput {
+    /// The path to a file containing the standard output of the task.
+    pub stdout: PathBuf,
+    /// The path to a file containing the standard error of the task.
+    pub stderr: PathBuf,
+}
+
+/// Represents the scope in which workflow statements are evaluated.
+///
+/// The body of a scatter statement is evaluated in one frame per element of
+/// its collection.
+#[derive(Debug, Clone)]
+struct Frame {
+    /// The values of the names in scope.
+    scope: HashMap<TokenStrHash<Ident>, Value>,
+    /// The directory beneath which the files of calls are localized.
+    base: PathBuf,
+}
+
+/// Represents a name introduced by the body of a scatter statement.
+#[derive(Debug)]
+enum Scattered {
+    /// The name of a declaration.
+    Declaration(Ident),
+    /// The name of a call, the names of the called task's outputs, and the
+    /// outputs of the call for each element of the collection.
+    Call(Ident, Vec<String>, Vec<HashMap<String, Value>>),
+}
+
+/// Represents a workflow evaluator.
+///
+/// Currently, only workflows made up of declarations, calls to tasks defined
+/// in the same document, and (non-nested) scatter statements are supported;
+/// statements are evaluated in the order they appear in the workflow, so a
+/// statement may only refer to the declarations and calls that precede it.
+#[derive(Debug)]
+pub struct WorkflowEvaluator {
+    /// The workflow being evaluated.
+    definition: WorkflowDefinition,
+    /// The tasks that may be called by the workflow.
+    tasks: HashMap<String, TaskDefinition>,
+}
+
+impl WorkflowEvaluator {
+    /// Constructs a new workflow evaluator for a definition and the tasks
+    /// defined in the same document.
+    pub fn new(
+        definition: WorkflowDefinition,
+        tasks: impl IntoIterator<Item = TaskDefinition>,
+    ) -> Self {
+        Self {
+            definition,
+            tasks: tasks
+                .into_iter()
+                .map(|task| (task.name().as_str().to_string(), task))
+                .collect(),
+        }
+    }
+
+    /// Gets the name of the workflow being evaluated.
+    pub fn name(&self) -> Ident {
+        self.definition.name()
+    }
+
+    /// Evaluates the workflow with the given inputs and base path to use for
+    /// file localization, returning the workflow's outputs.
+    ///
+    /// The task of each call is evaluated in the scope of the workflow (with
+    /// files localized beneath a directory named after the call) and then
+    /// handed to `run` along with the name of the call. The task's outputs are
+    /// evaluated from the [`CallOutput`] returned by `run` and are then
+    /// accessible to subsequent statements as members of the call (e.g.
+    /// `call_name.output_name`).
+    ///
+    /// Within a scatter statement, `run` is invoked for every element of the
+    /// collection before any of the returned futures are awaited, so the
+    /// number of tasks running at once is left to whatever `run` submits the
+    /// tasks to (e.g. an engine and its concurrency limit). After the
+    /// scatter, its declarations are arrays and the outputs of its calls are
+    /// arrays of the outputs of each element.
+    pub async fn evaluate<F, Fut>(
+        &self,
+        runtime: &mut Runtime<'_>,
+        inputs: &HashMap<String, Value>,
+        base: impl AsRef<Path>,
+        mut run: F,
+    ) -> Result<HashMap<TokenStrHash<Ident>, Value>, Diagnostic>
+    where
+        F: FnMut(&Runtime<'_>, &str, &EvaluatedTask<'_>) -> Result<Fut>,
+        Fut: Future<Output = Result<CallOutput>>,
+    {
+        let workflow = self.definition.name();
+        let mut frame = Frame {
+            scope: HashMap::new(),
+            base: base.as_ref().to_path_buf(),
+        };
+        let mut outputs = HashMap::new();
+
+        for item in self.definition.items() {
+            match item {
+                WorkflowItem::Input(section) => {
+                    for decl in section.declarations() {
+                        let name = decl.name();
+                        let value = match (inputs.get(name.as_str()), &decl) {
+                            (Some(value), _) => *value,
+                            (None, Decl::Bound(decl)) => ExprEvaluator::new(&frame.scope)
+                                .evaluate_expr(runtime, &decl.expr())?,
+                            (None, Decl::Unbound(decl)) if decl.ty().is_optional() => Value::None,
+                            (None, Decl::Unbound(_)) => {
+                                return Err(missing_input(workflow.as_str(), &name))
+                            }
+                        };
+
+                        frame.scope.insert(TokenStrHash::new(name), value);
+                    }
+                }
+                WorkflowItem::Declaration(decl) => {
+                    let value =
+                        ExprEvaluator::new(&frame.scope).evaluate_expr(runtime, &decl.expr())?;
+                    frame.scope.insert(TokenStrHash::new(decl.name()), value);
+                }
+                WorkflowItem::Call(call) => {
+                    let (name, mut results) = self
+                        .evaluate_call(runtime, std::slice::from_ref(&frame), &call, &mut run)
+                        .await?;
+                    let value = runtime.new_object(results.pop().expect("should have outputs"));
+                    frame.scope.insert(TokenStrHash::new(name), value);
+                }
+                WorkflowItem::Output(section) => {
+                    for decl in section.declarations() {
+                        let value = ExprEvaluator::new(&frame.scope)
+                            .evaluate_expr(runtime, &decl.expr())?;
+                        outputs.insert(TokenStrHash::new(decl.name()), value);
+                    }
+                }
+                WorkflowItem::Conditional(statement) => {
+                    return Err(unsupported_statement("conditional", statement.span()))
+                }
+                WorkflowItem::Scatter(statement) => {
+                    let gathered = self
+                        .evaluate_scatter(runtime, &frame, &statement, &mut run)
+                        .await?;
+                    frame.scope.extend(gathered);
+                }
+                _ => continue,
+            }
+        }
+
+        Ok(outputs)
+    }
+
+    /// Evaluates a scatter statement, returning the gathered values of the
+    /// declarations and calls within it.
+    async fn evaluate_scatter<F, Fut>(
+        &self,
+        runtime: &mut Runtime<'_>,
+        frame: &Frame,
+        statement: &ScatterStatement,
+        run: &mut F,
+    ) -> Result<HashMap<TokenStrHash<Ident>, Value>, Diagnostic>
+    where
+        F: FnMut(&Runtime<'_>, &str, &EvaluatedTask<'_>) -> Result<Fut>,
+        Fut: Future<Output = Result<CallOutput>>,
+    {
+        let expr = statement.expr();
+        let collection = ExprEvaluator::new(&frame.scope).evaluate_expr(runtime, &expr)?;
+        let elements = match collection {
+            Value::Stored(_, id) => match runtime.stored(id) {
+                StoredValue::Array(elements) => elements.clone(),
+                _ => return Err(cannot_scatter(runtime, &collection, expr.span())),
+            },
+            _ => return Err(cannot_scatter(runtime, &collection, expr.span())),
+        };
+
+        // Each element is evaluated in its own frame with the variable bound
+        let variable = statement.variable();
+        let mut frames: Vec<_> = elements
+            .into_iter()
+            .enumerate()
+            .map(|(index, element)| {
+                let mut frame = Frame {
+                    scope: frame.scope.clone(),
+                    base: frame.base.join(format!("shard-{index}")),
+                };
+                frame
+                    .scope
+                    .insert(TokenStrHash::new(variable.clone()), element);
+                frame
+            })
+            .collect();
+
+        let mut names = Vec::new();
+
+        for statement in statement.statements() {
+            match statement {
+                WorkflowStatement::Declaration(decl) => {
+                    for frame in &mut frames {
+                        let value = ExprEvaluator::new(&frame.scope)
+                            .evaluate_expr(runtime, &decl.expr())?;
+                        frame.scope.insert(TokenStrHash::new(decl.name()), value);
+                    }
+
+                    names.push(Scattered::Declaration(decl.name()));
+                }
+                WorkflowStatement::Call(call) => {
+                    let (name, outputs) = self.evaluate_call(runtime, &frames, &call, run).await?;
+                    for (frame, outputs) in frames.iter_mut().zip(&outputs) {
+                        let value = runtime.new_object(outputs.clone());
+                        frame.scope.insert(TokenStrHash::new(name.clone()), value);
+                    }
+
+                    let (_, task) = self.resolve_call(&call)?;
+                    names.push(Scattered::Call(name, output_names(&task), outputs));
+                }
+                WorkflowStatement::Conditional(statement) => {
+                    return Err(unsupported_statement("conditional", statement.span()))
+                }
+                WorkflowStatement::Scatter(statement) => {
+                    return Err(unsupported_statement("nested scatter", statement.span()))
+                }
+            }
+        }
+
+        // Gather the values of each frame into arrays
+        let mut gathered = HashMap::new();
+        for name in names {
+            let (name, value) = match name {
+                Scattered::Declaration(name) => {
+                    let elements = frames
+                        .iter()
+                        .map(|frame| frame.scope[name.as_str()])
+                        .collect();
+                    let value = runtime.new_array(elements);
+                    (name, value)
+                }
+                Scattered::Call(name, output_names, outputs) => {
+                    let mut members = HashMap::new();
+                    for output in output_names {
+                        let elements = outputs.iter().map(|o| o[&output]).collect();
+                        members.insert(output, runtime.new_array(elements));
+                    }
+
+                    (name, runtime.new_object(members))
+                }
+            };
+
+            gathered.insert(TokenStrHash::new(name), value);
+        }
+
+        Ok(gathered)
+    }
+
+    /// Resolves the name of a call and the task it calls.
+    fn resolve_call(&self, call: &CallStatement) -> Result<(Ident, TaskDefinition), Diagnostic> {
+        let target = call.target();
+        let mut names = target.names();
+        let task = match (names.next(), names.next()) {
+            (Some(name), None) => self
+                .tasks
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| unknown_task(call))?,
+            _ => return Err(unknown_task(call)),
+        };
+
+        // The call is named after its alias or, failing that, its task
+        let name = call
+            .alias()
+            .map(|alias| alias.name())
+            .unwrap_or_else(|| task.name());
+
+        Ok((name, task))
+    }
+
+    /// Evaluates a call statement in each of the given frames, returning the
+    /// name of the call and the outputs of the called task for each frame.
+    ///
+    /// The task is submitted for every frame before any are awaited.
+    async fn evaluate_call<F, Fut>(
+        &self,
+        runtime: &mut Runtime<'_>,
+        frames: &[Frame],
+        call: &CallStatement,
+        run: &mut F,
+    ) -> Result<(Ident, Vec<HashMap<String, Value>>), Diagnostic>
+    where
+        F: FnMut(&Runtime<'_>, &str, &EvaluatedTask<'_>) -> Result<Fut>,
+        Fut: Future<Output = Result<CallOutput>>,
+    {
+        let (name, task) = self.resolve_call(call)?;
+        let evaluator = TaskEvaluator::new(task)?;
+
+        let mut evaluated = Vec::with_capacity(frames.len());
+        let mut pending = Vec::with_capacity(frames.len());
+        for frame in frames {
+            // Evaluate the call inputs in the scope of the frame; an input
+            // without an expression (e.g. `input: x`) refers to the name itself
+            let mut inputs = HashMap::new();
+            for input in call.inputs() {
+                let input_name = input.name();
+                let value = match input.expr() {
+                    Some(expr) => ExprEvaluator::new(&frame.scope).evaluate_expr(runtime, &expr)?,
+                    None => frame
+                        .scope
+                        .get(input_name.as_str())
+                        .copied()
+                        .ok_or_else(|| unknown_name(input_name.as_str(), input_name.span()))?,
+                };
+
+                inputs.insert(input_name.as_str().to_string(), value);
+            }
+
+            let task = evaluator.evaluate(runtime, &inputs, frame.base.join(name.as_str()))?;
+            pending.push(run(&*runtime, name.as_str(), &task).map_err(|e| call_failed(&name, &e))?);
+            evaluated.push(task);
+        }
+
+        let results = join_all(pending).await;
+
+        let mut outputs = Vec::with_capacity(frames.len());
+        for (task, result) in evaluated.iter().zip(results) {
+            let output = result.map_err(|e| call_failed(&name, &e))?;
+            outputs.push(
+                task.outputs(runtime, &output.stdout, &output.stderr)?
+                    .into_iter()
+                    .map(|(name, value)| (name.as_ref().as_str().to_string(), value))
+                    .collect(),
+            );
+        }
+
+        Ok((name, outputs))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    use tempfile::TempDir;
+    use wdl_analysis::Analyzer;
+
+    #[tokio::test]
+    async fn calls_are_chained() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let path = dir.path().join("foo.wdl");
+        fs::write(
+            &path,
+            r#"version 1.1
+
+workflow test {
+    input {
+        String name = "Peter"
+    }
+
+    call greet { input: name }
+
+    call greet as again { input: name = greet.message }
+
+    output {
+        String message = again.message
+    }
+}
+
+task greet {
+    input {
+        String name
+    }
+
+    command <<<
+        echo Hi, ~{name}!
+    >>>
+
+    output {
+        String message = read_string(stdout())
+    }
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+        let ast = document.ast();
+        let ast = ast.as_v1().expect("should be a V1 AST");
+        let workflow = ast.workflows().next().expect("should have a workflow");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let inputs = HashMap::new();
+        let evaluator = WorkflowEvaluator::new(workflow, ast.tasks());
+
+        // Rather than running each command, pretend that `echo` printed its
+        // arguments
+        let mut calls = Vec::new();
+        let outputs = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp", |_, name, task| {
+                calls.push(name.to_string());
+
+                let stdout = dir.path().join(format!("{name}.stdout"));
+                let message = task.command().trim().trim_start_matches("echo ");
+                fs::write(&stdout, message)?;
+
+                let stderr = dir.path().join(format!("{name}.stderr"));
+                fs::write(&stderr, "")?;
+
+                Ok(std::future::ready(Ok(CallOutput { stdout, stderr })))
+            })
+            .await
+            .expect("should evaluate");
+
+        assert_eq!(calls, ["greet", "again"]);
+        assert_eq!(outputs.len(), 1);
+        match outputs["message"] {
+            Value::String(sym) => assert_eq!(runtime.resolve_str(sym), "Hi, Hi, Peter!!"),
+            v => panic!("expected a string value, found {v:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn scatters_are_gathered() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let path = dir.path().join("foo.wdl");
+        fs::write(
+            &path,
+            r#"version 1.1
+
+workflow test {
+    input {
+        Array[String] names = ["Peter", "Paul"]
+    }
+
+    scatter (name in names) {
+        call greet { input: name }
+    }
+
+    output {
+        Array[String] messages = greet.message
+    }
+}
+
+task greet {
+    input {
+        String name
+    }
+
+    command <<<
+        echo Hi, ~{name}!
+    >>>
+
+    output {
+        String message = read_string(stdout())
+    }
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::new(|_: (), _, _, _| async {});
+        analyzer
+            .add_documents(vec![dir.path().to_path_buf()])
+            .await
+            .expect("should add documents");
+
+        let results = analyzer.analyze(()).await.expect("should succeed");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].diagnostics().is_empty());
+
+        let document = results[0]
+            .parse_result()
+            .document()
+            .expect("should have a document");
+        let ast = document.ast();
+        let ast = ast.as_v1().expect("should be a V1 AST");
+        let workflow = ast.workflows().next().expect("should have a workflow");
+
+        let mut runtime = Runtime::new(results[0].scope());
+        let inputs = HashMap::new();
+        let evaluator = WorkflowEvaluator::new(workflow, ast.tasks());
+
+        let mut calls = 0;
+        let outputs = evaluator
+            .evaluate(&mut runtime, &inputs, "/tmp", |_, _, task| {
+                calls += 1;
+
+                let stdout = dir.path().join(format!("{calls}.stdout"));
+                let message = task.command().trim().trim_start_matches("echo ");
+                fs::write(&stdout, message)?;
+
+                let stderr = dir.path().join(format!("{calls}.stderr"));
+                fs::write(&stderr, "")?;
+
+                Ok(std::future::ready(Ok(CallOutput { stdout, stderr })))
+            })
+            .await
+            .expect("should evaluate");
+
+        assert_eq!(calls, 2);
+        let messages = match outputs["messages"] {
+            Value::Stored(_, id) => match runtime.stored(id) {
+                StoredValue::Array(elements) => elements
+                    .iter()
+                    .map(|v| v.unwrap_string(&runtime))
+                    .collect::<Vec<_>>(),
+                v => panic!("expected an array value, found {v:?}"),
+            },
+            v => panic!("expected an array value, found {v:?}"),
+        };
+        assert_eq!(messages, ["Hi, Peter!", "Hi, Paul!"]);
+    }
+}